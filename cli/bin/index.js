@@ -3,7 +3,7 @@
 import inquirer from "inquirer";
 import chalk from "chalk";
 import figlet from "figlet";
-import * as anchor from '@project-serum/anchor';
+import * as anchor from '@coral-xyz/anchor';
 import * as web3 from '@solana/web3.js';
 import * as fs from 'fs';
 import { BN } from 'bn.js';
@@ -17,8 +17,7 @@ const connection = new web3.Connection('http://127.0.0.1:8899');
 const provider = new anchor.AnchorProvider(connection, wallet, {
   commitment: 'processed'
 });
-const programId = new anchor.web3.PublicKey('4sRbFuajHVG181psKiK7G2JBSzbcvVD9RBVbo72DE9TQ');
-const program = new anchor.Program(idl, programId, provider);
+const program = new anchor.Program(idl, provider);
 
 console.log(
   chalk.yellow(figlet.textSync("Anchor AMM CLI", { horizontalLayout: "full" }))
@@ -54,41 +53,22 @@ await inquirer
     },
   ])
   .then((answers) => {
-    feeTo = answers.feeTo;
+    feeTo = new web3.PublicKey(answers.feeTo);
   });
 
-await inquirer
-  .prompt([
-    {
-      type: "input",
-      name: "fee",
-      message: "Fee(%)?",
-    },
-  ])
-  .then((answers) => {
-    fee = answers.fee * 100;
-  });
-
-console.log(feeTo, fee);
+console.log(feeTo.toBase58());
 
 const [configPDA] = web3.PublicKey.findProgramAddressSync(
   [Buffer.from(anchor.utils.bytes.utf8.encode('config'))],
   program.programId
 );
 
-await program.rpc.initialize(
-  feeTo,  // fee_to: The public key of the fee recipient
-  new BN(fee),  // fee: The fee value
-  {
-    accounts: {
-      owner: provider.wallet.publicKey,  // The owner who pays for the transaction and initializes the config
-      config: configPDA,  // PDA for the config account
-      system_program: anchor.web3.SystemProgram.programId,  // System program
-      rent: anchor.web3.SYSVAR_RENT_PUBKEY,  // Rent sysvar
-    },
-    signers: [provider.wallet.payer],
-  }
-);
+await program.methods
+  .initialize(feeTo)  // fee_to: The public key of the fee recipient
+  .accounts({
+    owner: provider.wallet.publicKey,  // The owner who pays for the transaction and initializes the config
+  })
+  .rpc();
 
 console.log(chalk.green(`initialization success!`));
 
@@ -98,7 +78,7 @@ await inquirer
     {
       type: "confirm",
       name: "confirm",
-      message: "Do you want to change the fee?",
+      message: "Do you want to create a fee tier?",
     },
   ])
   .then((answers) => {
@@ -120,16 +100,13 @@ await inquirer
     fee = answers.fee * 100;
   });
 
-await program.rpc.setFee(
-  new BN(fee),  // fee: The fee value
-  {
-    accounts: {
-      owner: provider.wallet.publicKey,  // The owner who pays for the transaction and initializes the config
-      config: configPDA,  // PDA for the config account
-      system_program: anchor.web3.SystemProgram.programId,  // System program
-    },
-    signers: [provider.wallet.payer],
-  }
-);
+// Pools are created in a fee tier and charge its swap fee
+await program.methods
+  .createFeeTier(new BN(fee))  // fee: The swap fee in basis points
+  .accounts({
+    owner: provider.wallet.publicKey,  // The owner who pays for the fee tier account
+    config: configPDA,  // PDA for the config account
+  })
+  .rpc();
 
-console.log(chalk.green(`New Fee Set!`));
\ No newline at end of file
+console.log(chalk.green(`New Fee Tier Created!`));
\ No newline at end of file
@@ -0,0 +1,5 @@
+pub mod swap_math;
+pub mod tick_math;
+
+pub use swap_math::*;
+pub use tick_math::*;
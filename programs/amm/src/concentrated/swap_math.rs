@@ -0,0 +1,278 @@
+use crate::constant::BASIS_POINTS;
+use crate::error::ErrorCode;
+use crate::utils::{div_wide, div_wide_ceil, mul_div, mul_div_ceil};
+use anchor_lang::prelude::*;
+use fixed::types::U64F64;
+
+/// Returns the token0 amount liquidity `liquidity` holds between two sqrt prices, L * (1/sqrt_a - 1/sqrt_b).
+pub fn amount0_delta(
+    sqrt_price_a: U64F64,
+    sqrt_price_b: U64F64,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<u128> {
+    let (low, high) = sorted(sqrt_price_a, sqrt_price_b);
+
+    // L * 2^64 / sqrt_price as Q64.64 divided by Q64.64 gives a plain amount
+    let (numerator_high, numerator_low) = (liquidity >> 64, liquidity << 64);
+    let (amount_low, amount_high) = if round_up {
+        (
+            div_wide_ceil(numerator_high, numerator_low, low),
+            div_wide(numerator_high, numerator_low, high),
+        )
+    } else {
+        (
+            div_wide(numerator_high, numerator_low, low),
+            div_wide_ceil(numerator_high, numerator_low, high),
+        )
+    };
+    let amount_low = amount_low.ok_or(ErrorCode::CalculationFailure)?;
+    let amount_high = amount_high.ok_or(ErrorCode::CalculationFailure)?;
+    Ok(amount_low.saturating_sub(amount_high))
+}
+
+/// Returns the token1 amount liquidity `liquidity` holds between two sqrt prices, L * (sqrt_b - sqrt_a).
+pub fn amount1_delta(
+    sqrt_price_a: U64F64,
+    sqrt_price_b: U64F64,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<u128> {
+    let (low, high) = sorted(sqrt_price_a, sqrt_price_b);
+    let amount = if round_up {
+        mul_div_ceil(liquidity, high - low, 1 << 64)
+    } else {
+        mul_div(liquidity, high - low, 1 << 64)
+    };
+    amount.ok_or(ErrorCode::CalculationFailure.into())
+}
+
+// Returns the bits of two sqrt prices, lowest first
+fn sorted(sqrt_price_a: U64F64, sqrt_price_b: U64F64) -> (u128, u128) {
+    let (a, b) = (sqrt_price_a.to_bits(), sqrt_price_b.to_bits());
+    (a.min(b), a.max(b))
+}
+
+/// Returns the sqrt price after adding `amount_in` of the input token, rounded against the trader.
+fn next_sqrt_price_from_input(
+    sqrt_price: U64F64,
+    liquidity: u128,
+    amount_in: u64,
+    zero_for_one: bool,
+) -> Result<U64F64> {
+    if amount_in == 0 {
+        return Ok(sqrt_price);
+    }
+
+    let bits = sqrt_price.to_bits();
+    let next = if zero_for_one {
+        // L * sqrt_p / (L + amount * sqrt_p), rounded up
+        mul_div(amount_in as u128, bits, 1 << 64)
+            .and_then(|product| liquidity.checked_add(product))
+            .and_then(|denominator| mul_div_ceil(liquidity, bits, denominator))
+    } else {
+        // sqrt_p + amount / L, rounded down
+        div_wide(0, (amount_in as u128) << 64, liquidity).and_then(|delta| bits.checked_add(delta))
+    };
+    next.map(U64F64::from_bits)
+        .ok_or(ErrorCode::CalculationFailure.into())
+}
+
+/// Returns the sqrt price after removing `amount_out` of the output token, rounded against the trader.
+fn next_sqrt_price_from_output(
+    sqrt_price: U64F64,
+    liquidity: u128,
+    amount_out: u64,
+    zero_for_one: bool,
+) -> Result<U64F64> {
+    if amount_out == 0 {
+        return Ok(sqrt_price);
+    }
+
+    let bits = sqrt_price.to_bits();
+    let next = if zero_for_one {
+        // sqrt_p - amount / L, rounded down
+        div_wide_ceil(0, (amount_out as u128) << 64, liquidity)
+            .and_then(|delta| bits.checked_sub(delta))
+    } else {
+        // L * sqrt_p / (L - amount * sqrt_p), rounded up
+        mul_div_ceil(amount_out as u128, bits, 1 << 64)
+            .and_then(|product| liquidity.checked_sub(product))
+            .filter(|denominator| *denominator > 0)
+            .and_then(|denominator| mul_div_ceil(liquidity, bits, denominator))
+    };
+    next.map(U64F64::from_bits)
+        .ok_or(ErrorCode::InsufficientLiquidity.into())
+}
+
+/// Result of swapping within a range of constant liquidity.
+pub struct SwapStep {
+    pub sqrt_price_next: U64F64,
+    pub amount_in: u64, // Input excluding the fee
+    pub amount_out: u64,
+    pub fee_amount: u64,
+}
+
+/// Swaps from `sqrt_price` towards `sqrt_price_target` with constant `liquidity`, stopping early once
+/// `amount_remaining` is used up. The remaining amount is an input including the fee if `exact_input`, else an output.
+pub fn compute_swap_step(
+    sqrt_price: U64F64,
+    sqrt_price_target: U64F64,
+    liquidity: u128,
+    amount_remaining: u64,
+    fee: u64,
+    exact_input: bool,
+) -> Result<SwapStep> {
+    let zero_for_one = sqrt_price >= sqrt_price_target;
+
+    // Amount reaching the target would take, and the price reached with the amount remaining
+    let amount_to_target;
+    let sqrt_price_next;
+    if exact_input {
+        let amount_remaining_less_fee =
+            (amount_remaining as u128 * (BASIS_POINTS - fee) as u128 / BASIS_POINTS as u128) as u64;
+        amount_to_target = if zero_for_one {
+            amount0_delta(sqrt_price_target, sqrt_price, liquidity, true)?
+        } else {
+            amount1_delta(sqrt_price, sqrt_price_target, liquidity, true)?
+        };
+        sqrt_price_next = if amount_remaining_less_fee as u128 >= amount_to_target {
+            sqrt_price_target
+        } else {
+            next_sqrt_price_from_input(
+                sqrt_price,
+                liquidity,
+                amount_remaining_less_fee,
+                zero_for_one,
+            )?
+        };
+    } else {
+        amount_to_target = if zero_for_one {
+            amount1_delta(sqrt_price_target, sqrt_price, liquidity, false)?
+        } else {
+            amount0_delta(sqrt_price, sqrt_price_target, liquidity, false)?
+        };
+        sqrt_price_next = if amount_remaining as u128 >= amount_to_target {
+            sqrt_price_target
+        } else {
+            next_sqrt_price_from_output(sqrt_price, liquidity, amount_remaining, zero_for_one)?
+        };
+    }
+    let reached_target = sqrt_price_next == sqrt_price_target;
+
+    // Amounts moved between the two prices, reusing the amount to the target when it was reached
+    let (amount_in, mut amount_out) = if zero_for_one {
+        (
+            match reached_target && exact_input {
+                true => amount_to_target,
+                false => amount0_delta(sqrt_price_next, sqrt_price, liquidity, true)?,
+            },
+            match reached_target && !exact_input {
+                true => amount_to_target,
+                false => amount1_delta(sqrt_price_next, sqrt_price, liquidity, false)?,
+            },
+        )
+    } else {
+        (
+            match reached_target && exact_input {
+                true => amount_to_target,
+                false => amount1_delta(sqrt_price, sqrt_price_next, liquidity, true)?,
+            },
+            match reached_target && !exact_input {
+                true => amount_to_target,
+                false => amount0_delta(sqrt_price, sqrt_price_next, liquidity, false)?,
+            },
+        )
+    };
+
+    // Rounding up the price must not pay out more than was asked for
+    if !exact_input {
+        amount_out = amount_out.min(amount_remaining as u128);
+    }
+    let amount_in = u64::try_from(amount_in).map_err(|_| ErrorCode::CalculationFailure)?;
+    let amount_out = u64::try_from(amount_out).map_err(|_| ErrorCode::CalculationFailure)?;
+
+    // Stopping short of the target with the input used up, the fee takes what is left of it
+    let fee_amount = if exact_input && !reached_target {
+        amount_remaining - amount_in
+    } else {
+        mul_div_ceil(amount_in as u128, fee as u128, (BASIS_POINTS - fee) as u128)
+            .and_then(|fee_amount| u64::try_from(fee_amount).ok())
+            .ok_or(ErrorCode::CalculationFailure)?
+    };
+
+    Ok(SwapStep {
+        sqrt_price_next,
+        amount_in,
+        amount_out,
+        fee_amount,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::concentrated::sqrt_price_at_tick;
+    use crate::constant::{MAX_TICK, MIN_TICK};
+
+    #[test]
+    fn amount_deltas() {
+        // Between sqrt prices 1 and 2, L * (1/1 - 1/2) of token0 and L * (2 - 1) of token1
+        let (one, two) = (U64F64::from_num(1), U64F64::from_num(2));
+        for round_up in [false, true] {
+            assert_eq!(amount0_delta(one, two, 1000, round_up).unwrap(), 500);
+            assert_eq!(amount0_delta(two, one, 1000, round_up).unwrap(), 500);
+            assert_eq!(amount1_delta(one, two, 1000, round_up).unwrap(), 1000);
+            assert_eq!(amount1_delta(two, one, 1000, round_up).unwrap(), 1000);
+        }
+        assert_eq!(amount0_delta(one, one, 1000, true).unwrap(), 0);
+        assert_eq!(amount1_delta(one, one, 1000, true).unwrap(), 0);
+    }
+
+    #[test]
+    fn amount_deltas_round_by_at_most_one() {
+        let lower = sqrt_price_at_tick(0).unwrap();
+        let upper = sqrt_price_at_tick(1).unwrap();
+        let liquidity = 1_000_000_007;
+
+        let amount0_down = amount0_delta(lower, upper, liquidity, false).unwrap();
+        let amount0_up = amount0_delta(lower, upper, liquidity, true).unwrap();
+        assert!(amount0_up - amount0_down <= 1);
+        assert!(amount0_down > 0);
+
+        let amount1_down = amount1_delta(lower, upper, liquidity, false).unwrap();
+        let amount1_up = amount1_delta(lower, upper, liquidity, true).unwrap();
+        assert_eq!(amount1_up - amount1_down, 1);
+    }
+
+    #[test]
+    fn amount_deltas_at_price_bounds() {
+        let min = sqrt_price_at_tick(MIN_TICK).unwrap();
+        let max = sqrt_price_at_tick(MAX_TICK).unwrap();
+        let liquidity = u64::MAX as u128;
+
+        // Across the whole range, L * 2^64 * (1/min - 1/max) of token0 and L * (max - min) / 2^64 of token1
+        let amount0 = amount0_delta(min, max, liquidity, true).unwrap();
+        let amount1 = amount1_delta(min, max, liquidity, true).unwrap();
+        let numerator = liquidity << 64;
+        assert_eq!(
+            amount0,
+            numerator.div_ceil(min.to_bits()) - numerator / max.to_bits()
+        );
+        assert!(amount1 > liquidity * ((max.to_bits() - min.to_bits()) >> 64));
+
+        // Liquidity beyond 128 bits of either amount fails instead of wrapping
+        assert!(amount1_delta(min, max, u128::MAX, true).is_err());
+    }
+
+    #[test]
+    fn swap_step_uses_exact_input() {
+        // Stopping short of the target, the input and fee add up to the amount
+        let sqrt_price = sqrt_price_at_tick(0).unwrap();
+        let target = sqrt_price_at_tick(-1000).unwrap();
+        let step = compute_swap_step(sqrt_price, target, 1 << 64, 10_000, 30, true).unwrap();
+        assert!(step.sqrt_price_next < sqrt_price && step.sqrt_price_next > target);
+        assert_eq!(step.amount_in + step.fee_amount, 10_000);
+        assert!(step.amount_out < step.amount_in);
+    }
+}
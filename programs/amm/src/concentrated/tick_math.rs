@@ -0,0 +1,126 @@
+use crate::constant::{MAX_SQRT_PRICE, MAX_TICK, MIN_SQRT_PRICE, MIN_TICK};
+use crate::error::ErrorCode;
+use crate::utils::{div_wide_ceil, full_mul};
+use anchor_lang::prelude::*;
+use fixed::types::U64F64;
+
+// 1 / sqrt(1.0001)^(2^i) as Q0.128, one per bit of a tick's magnitude
+const RATIOS: [u128; 19] = [
+    0xfffcb933bd6fad37aa2d162d1a594001,
+    0xfff97272373d413259a46990580e2139,
+    0xfff2e50f5f656932ef12357cf3c7fdcb,
+    0xffe5caca7e10e4e61c3624eaa0941ccf,
+    0xffcb9843d60f6159c9db58835c926643,
+    0xff973b41fa98c081472e6896dfb254bf,
+    0xff2ea16466c96a3843ec78b326b52860,
+    0xfe5dee046a99a2a811c461f1969c3052,
+    0xfcbe86c7900a88aedcffc83b479aa3a3,
+    0xf987a7253ac413176f2b074cf7815e53,
+    0xf3392b0822b70005940c7a398e4b70f2,
+    0xe7159475a2c29b7443b29c7fa6e889d8,
+    0xd097f3bdfd2022b8845ad8f792aa5825,
+    0xa9f746462d870fdf8a65dc1f90e061e4,
+    0x70d869a156d2a1b890bb3df62baf32f6,
+    0x31be135f97d08fd981231505542fcfa5,
+    0x9aa508b5b7a84e1c677de54f3e99bc8,
+    0x5d6af8dedb81196699c329225ee604,
+    0x2216e584f5fa1ea926041bedfe97,
+];
+
+/// Returns sqrt(1.0001^tick) as Q64.64, rounded up.
+pub fn sqrt_price_at_tick(tick: i32) -> Result<U64F64> {
+    require!(
+        (MIN_TICK..=MAX_TICK).contains(&tick),
+        ErrorCode::InvalidTick
+    );
+
+    // Multiply the ratios of the set bits of |tick|, giving 1 / sqrt(1.0001)^|tick|
+    let abs_tick = tick.unsigned_abs();
+    let mut ratio: Option<u128> = None;
+    for (i, bit_ratio) in RATIOS.iter().enumerate() {
+        if abs_tick & (1 << i) != 0 {
+            ratio = Some(match ratio {
+                Some(ratio) => full_mul(ratio, *bit_ratio).0,
+                None => *bit_ratio,
+            });
+        }
+    }
+    let Some(ratio) = ratio else {
+        return Ok(U64F64::ONE);
+    };
+
+    let bits = if tick > 0 {
+        // Invert the Q0.128 ratio into Q64.64, 2^192 / ratio
+        div_wide_ceil(1 << 64, 0, ratio).ok_or(ErrorCode::CalculationFailure)?
+    } else {
+        (ratio >> 64) + (ratio as u64 != 0) as u128
+    };
+    Ok(U64F64::from_bits(bits))
+}
+
+/// Returns the greatest tick whose sqrt price is at most `sqrt_price`.
+pub fn tick_at_sqrt_price(sqrt_price: U64F64) -> Result<i32> {
+    require!(
+        (MIN_SQRT_PRICE..MAX_SQRT_PRICE).contains(&sqrt_price.to_bits()),
+        ErrorCode::InvalidSqrtPrice
+    );
+
+    // Binary search, sqrt_price_at_tick(low) <= sqrt_price < sqrt_price_at_tick(high)
+    let (mut low, mut high) = (MIN_TICK, MAX_TICK);
+    while high - low > 1 {
+        let mid = low + (high - low) / 2;
+        if sqrt_price_at_tick(mid)? <= sqrt_price {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    Ok(low)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sqrt_price_at_bounds() {
+        assert_eq!(sqrt_price_at_tick(0).unwrap(), U64F64::ONE);
+        assert_eq!(
+            sqrt_price_at_tick(MIN_TICK).unwrap().to_bits(),
+            MIN_SQRT_PRICE
+        );
+        assert_eq!(
+            sqrt_price_at_tick(MAX_TICK).unwrap().to_bits(),
+            MAX_SQRT_PRICE
+        );
+        assert!(sqrt_price_at_tick(MIN_TICK - 1).is_err());
+        assert!(sqrt_price_at_tick(MAX_TICK + 1).is_err());
+    }
+
+    #[test]
+    fn sqrt_price_increases_with_tick() {
+        for tick in [MIN_TICK, -1, 0, 1, MAX_TICK - 1] {
+            assert!(sqrt_price_at_tick(tick).unwrap() < sqrt_price_at_tick(tick + 1).unwrap());
+        }
+    }
+
+    #[test]
+    fn tick_round_trip() {
+        for tick in [MIN_TICK, MIN_TICK + 1, -60, -1, 0, 1, 60, MAX_TICK - 1] {
+            let sqrt_price = sqrt_price_at_tick(tick).unwrap();
+            assert_eq!(tick_at_sqrt_price(sqrt_price).unwrap(), tick);
+
+            // Just below a tick's price is the tick before it
+            if tick > MIN_TICK {
+                let below = U64F64::from_bits(sqrt_price.to_bits() - 1);
+                assert_eq!(tick_at_sqrt_price(below).unwrap(), tick - 1);
+            }
+        }
+
+        // The greatest price accepted is just below the price of MAX_TICK
+        let max = U64F64::from_bits(MAX_SQRT_PRICE - 1);
+        assert_eq!(tick_at_sqrt_price(max).unwrap(), MAX_TICK - 1);
+        assert!(tick_at_sqrt_price(U64F64::from_bits(MAX_SQRT_PRICE)).is_err());
+        assert!(tick_at_sqrt_price(U64F64::from_bits(MIN_SQRT_PRICE - 1)).is_err());
+    }
+}
@@ -1 +1,56 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_2022::spl_token_2022::extension::ExtensionType;
+
 pub const BASIS_POINTS: u64 = 10000;
+
+/// Liquidity permanently locked in the pool's LP vault on the first deposit.
+pub const MINIMUM_LIQUIDITY: u64 = 1000;
+
+/// Token-2022 mint extensions allowed in pools until the owner changes the allow-list.
+pub const DEFAULT_ALLOWED_EXTENSIONS: u64 = 1 << ExtensionType::TransferFeeConfig as u16
+    | 1 << ExtensionType::InterestBearingConfig as u16
+    | 1 << ExtensionType::MetadataPointer as u16
+    | 1 << ExtensionType::TokenMetadata as u16
+    | 1 << ExtensionType::GroupPointer as u16
+    | 1 << ExtensionType::TokenGroup as u16
+    | 1 << ExtensionType::GroupMemberPointer as u16
+    | 1 << ExtensionType::TokenGroupMember as u16;
+
+/// Number of observations a pool's oracle can hold.
+pub const OBSERVATION_CAPACITY: usize = 128;
+
+/// Maximum number of points a single observe call can return.
+pub const MAX_OBSERVE_QUERIES: usize = 16;
+
+/// Maximum number of pools a routed swap can go through.
+pub const MAX_ROUTE_HOPS: usize = 4;
+
+/// Number of remaining accounts describing each hop of a routed swap.
+pub const ROUTE_HOP_ACCOUNTS: usize = 7;
+
+/// Bounds of a StableSwap pool's amplification coefficient.
+pub const MIN_AMP: u64 = 1;
+pub const MAX_AMP: u64 = 1_000_000;
+
+/// Shortest time, in seconds, an amplification ramp may take.
+pub const MIN_AMP_RAMP_DURATION: i64 = 86400;
+
+/// Smallest normalized weight of a token in a weighted pool, in basis points.
+pub const MIN_WEIGHT: u64 = 100;
+
+/// Largest factor an amplification ramp may raise or lower the coefficient by.
+pub const MAX_AMP_CHANGE: u64 = 10;
+
+/// Bounds of a concentrated pool's ticks, the price at tick t is 1.0001^t.
+pub const MIN_TICK: i32 = -443636;
+pub const MAX_TICK: i32 = 443636;
+
+/// Q64.64 sqrt prices at MIN_TICK and MAX_TICK.
+pub const MIN_SQRT_PRICE: u128 = 4295048017;
+pub const MAX_SQRT_PRICE: u128 = 79226673515401279992447579062;
+
+/// Number of ticks held by each tick array account.
+pub const TICK_ARRAY_SIZE: i32 = 64;
+
+/// Owner of Pyth price accounts, the oracles of oracle-pegged pools.
+pub const PYTH_PROGRAM_ID: Pubkey = pubkey!("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH");
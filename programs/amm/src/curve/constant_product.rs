@@ -0,0 +1,66 @@
+use crate::constant::BASIS_POINTS;
+use crate::curve::Curve;
+use crate::error::ErrorCode;
+use crate::utils::full_mul;
+use anchor_lang::prelude::*;
+use fixed::types::U64F64;
+
+/// Uniswap V2's constant product x * y = k.
+pub struct ConstantProduct {
+    pub fee: u64, // Swap fee in basis points
+}
+
+impl Curve for ConstantProduct {
+    fn swap_exact_in(
+        &self,
+        amount_in: u64,
+        reserve_in: u64,
+        reserve_out: u64,
+        _zero_for_one: bool,
+    ) -> Result<u64> {
+        // Calculate output amount with fee applied
+        let amount_in_with_fee = amount_in as u128 * (BASIS_POINTS - self.fee) as u128;
+        let numerator = amount_in_with_fee * reserve_out as u128;
+        let denominator = reserve_in as u128 * BASIS_POINTS as u128 + amount_in_with_fee;
+
+        Ok((numerator / denominator) as u64)
+    }
+
+    fn swap_exact_out(
+        &self,
+        amount_out: u64,
+        reserve_in: u64,
+        reserve_out: u64,
+        _zero_for_one: bool,
+    ) -> Result<u64> {
+        require!(amount_out < reserve_out, ErrorCode::InsufficientLiquidity);
+
+        // Calculate input amount required to get the desired output
+        let numerator = reserve_in as u128 * amount_out as u128 * BASIS_POINTS as u128;
+        let denominator =
+            (reserve_out as u128 - amount_out as u128) * (BASIS_POINTS - self.fee) as u128;
+
+        (numerator / denominator)
+            .checked_add(1)
+            .and_then(|amount_in| u64::try_from(amount_in).ok())
+            .ok_or(error!(ErrorCode::InsufficientLiquidity))
+    }
+
+    fn spot_price(&self, reserve_in: u64, reserve_out: u64, _zero_for_one: bool) -> Result<u128> {
+        Ok((U64F64::from_num(reserve_out) / U64F64::from_num(reserve_in)).to_bits())
+    }
+
+    fn require_invariant(
+        &self,
+        reserve0: u128,
+        reserve1: u128,
+        balance0: u128,
+        balance1: u128,
+    ) -> Result<()> {
+        require!(
+            full_mul(balance0, balance1) >= full_mul(reserve0, reserve1),
+            ErrorCode::InvariantViolated
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,147 @@
+pub mod constant_product;
+pub mod oracle_pegged;
+pub mod stable_swap;
+pub mod weighted;
+
+pub use constant_product::*;
+pub use oracle_pegged::*;
+pub use stable_swap::*;
+pub use weighted::*;
+
+use crate::error::ErrorCode;
+use anchor_lang::prelude::*;
+use fixed::types::U128F0;
+use std::cmp::min;
+
+/// Swap and liquidity math of a pool's invariant. `zero_for_one` is true when token0 is the input,
+/// prices are UQ64.64.
+pub trait Curve {
+    /// Returns the output of swapping `amount_in`, net of the swap fee.
+    fn swap_exact_in(
+        &self,
+        amount_in: u64,
+        reserve_in: u64,
+        reserve_out: u64,
+        zero_for_one: bool,
+    ) -> Result<u64>;
+
+    /// Returns the input, including the swap fee, required to take `amount_out`.
+    fn swap_exact_out(
+        &self,
+        amount_out: u64,
+        reserve_in: u64,
+        reserve_out: u64,
+        zero_for_one: bool,
+    ) -> Result<u64>;
+
+    /// Returns the marginal price of the input token in the output token.
+    fn spot_price(&self, reserve_in: u64, reserve_out: u64, zero_for_one: bool) -> Result<u128>;
+
+    /// Ensures the invariant of the balances did not decrease from that of the reserves, both scaled alike.
+    fn require_invariant(
+        &self,
+        reserve0: u128,
+        reserve1: u128,
+        balance0: u128,
+        balance1: u128,
+    ) -> Result<()>;
+
+    /// Returns the invariant of the reserves as an amount of liquidity, growing in proportion to them, so its
+    /// growth between liquidity events measures the swap fees earned.
+    fn invariant(&self, reserve0: u64, reserve1: u64) -> Result<u128> {
+        Ok(U128F0::from_num((reserve0 as u128) * (reserve1 as u128))
+            .sqrt()
+            .to_num::<u128>())
+    }
+
+    /// Returns the liquidity minted by the first deposit, before the minimum liquidity is locked.
+    fn initial_liquidity(&self, amount0: u64, amount1: u64) -> Result<u64> {
+        Ok(U128F0::from_num((amount0 as u128) * (amount1 as u128))
+            .sqrt()
+            .to_num::<u64>())
+    }
+
+    /// Returns the amounts to deposit out of the desired ones, in proportion to the reserves.
+    fn deposit_amounts(
+        &self,
+        reserve0: u64,
+        reserve1: u64,
+        amount0_desired: u64,
+        amount1_desired: u64,
+        amount0_min: u64,
+        amount1_min: u64,
+    ) -> Result<(u64, u64)> {
+        if reserve0 == 0 && reserve1 == 0 {
+            return Ok((amount0_desired, amount1_desired));
+        }
+
+        let amount1_optimal = quote(amount0_desired, reserve0, reserve1)?;
+        if amount1_optimal <= amount1_desired {
+            require!(
+                amount1_optimal >= amount1_min,
+                ErrorCode::InsufficientAmount
+            );
+            Ok((amount0_desired, amount1_optimal))
+        } else {
+            let amount0_optimal = quote(amount1_desired, reserve1, reserve0)?;
+            require!(
+                amount0_optimal <= amount0_desired,
+                ErrorCode::InsufficientAmount
+            );
+            require!(
+                amount0_optimal >= amount0_min,
+                ErrorCode::InsufficientAmount
+            );
+            Ok((amount0_optimal, amount1_desired))
+        }
+    }
+
+    /// Returns the liquidity minted for depositing `amount0` and `amount1`.
+    fn deposit(
+        &self,
+        amount0: u64,
+        amount1: u64,
+        reserve0: u64,
+        reserve1: u64,
+        lp_supply: u64,
+    ) -> Result<u64> {
+        if lp_supply == 0 {
+            return self.initial_liquidity(amount0, amount1);
+        }
+
+        Ok(min(
+            amount0 as u128 * lp_supply as u128 / reserve0 as u128,
+            amount1 as u128 * lp_supply as u128 / reserve1 as u128,
+        ) as u64)
+    }
+
+    /// Returns the amounts withdrawn by burning `liquidity`, in proportion to the reserves.
+    fn withdraw(
+        &self,
+        liquidity: u64,
+        reserve0: u64,
+        reserve1: u64,
+        lp_supply: u64,
+    ) -> Result<(u64, u64)> {
+        let amount0: u64 = (liquidity as u128 * reserve0 as u128 / lp_supply as u128) as u64;
+        let amount1: u64 = (liquidity as u128 * reserve1 as u128 / lp_supply as u128) as u64;
+
+        require!(
+            amount0 != 0 && amount1 != 0,
+            ErrorCode::InsufficientLiquidityBurned
+        );
+
+        Ok((amount0, amount1))
+    }
+}
+
+// Given an amount of an asset and pair reserves, returns an equivalent amount of the other asset
+fn quote(amount0: u64, reserve0: u64, reserve1: u64) -> Result<u64> {
+    require!(amount0 > 0, ErrorCode::InsufficientAmount);
+    require!(
+        reserve0 > 0 && reserve1 > 0,
+        ErrorCode::InsufficientReserves
+    );
+
+    Ok((amount0 as u128 * reserve1 as u128 / reserve0 as u128) as u64)
+}
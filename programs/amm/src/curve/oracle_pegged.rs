@@ -0,0 +1,126 @@
+use crate::constant::BASIS_POINTS;
+use crate::curve::{compute_d, compute_spot_price, compute_y, Curve};
+use crate::error::ErrorCode;
+use crate::utils::{mul_div, mul_div_ceil};
+use anchor_lang::prelude::*;
+
+/// StableSwap invariant over the reserves valued at an oracle price, so the pool trades near that price
+/// instead of near 1:1. Balances are valued in token1.
+pub struct OraclePegged {
+    pub amp: u64,    // Amplification coefficient, current point of its ramp
+    pub price: u128, // Oracle price of token0 in token1 as UQ64.64
+    pub fee: u64,    // Swap fee in basis points
+}
+
+impl OraclePegged {
+    // Returns the value in token1 of an amount of token0, or of token1 itself
+    fn value(&self, amount: u128, token0: bool, round_up: bool) -> Result<u128> {
+        if !token0 {
+            return Ok(amount);
+        }
+        match round_up {
+            true => mul_div_ceil(amount, self.price, 1 << 64),
+            false => mul_div(amount, self.price, 1 << 64),
+        }
+        .ok_or(ErrorCode::CalculationFailure.into())
+    }
+
+    // Returns the amount of token0, or of token1 itself, worth `value` in token1
+    fn amount(&self, value: u128, token0: bool, round_up: bool) -> Result<u128> {
+        if !token0 {
+            return Ok(value);
+        }
+        match round_up {
+            true => mul_div_ceil(value, 1 << 64, self.price),
+            false => mul_div(value, 1 << 64, self.price),
+        }
+        .ok_or(ErrorCode::CalculationFailure.into())
+    }
+}
+
+impl Curve for OraclePegged {
+    fn swap_exact_in(
+        &self,
+        amount_in: u64,
+        reserve_in: u64,
+        reserve_out: u64,
+        zero_for_one: bool,
+    ) -> Result<u64> {
+        let amount_in_with_fee =
+            amount_in as u128 * (BASIS_POINTS - self.fee) as u128 / BASIS_POINTS as u128;
+
+        let x = self.value(reserve_in as u128, zero_for_one, false)?;
+        let y = self.value(reserve_out as u128, !zero_for_one, false)?;
+        let d = compute_d(self.amp, x, y)?;
+        let x_new = x + self.value(amount_in_with_fee, zero_for_one, false)?;
+        let y_new = compute_y(self.amp, x_new, d)?;
+
+        // Round against the trader
+        let amount_out = self.amount(y.saturating_sub(y_new + 1), !zero_for_one, false)?;
+        u64::try_from(amount_out).map_err(|_| error!(ErrorCode::CalculationFailure))
+    }
+
+    fn swap_exact_out(
+        &self,
+        amount_out: u64,
+        reserve_in: u64,
+        reserve_out: u64,
+        zero_for_one: bool,
+    ) -> Result<u64> {
+        require!(amount_out < reserve_out, ErrorCode::InsufficientLiquidity);
+
+        let x = self.value(reserve_in as u128, zero_for_one, false)?;
+        let y = self.value(reserve_out as u128, !zero_for_one, false)?;
+        let d = compute_d(self.amp, x, y)?;
+        let y_new = y
+            .checked_sub(self.value(amount_out as u128, !zero_for_one, true)?)
+            .filter(|y_new| *y_new > 0)
+            .ok_or(ErrorCode::InsufficientLiquidity)?;
+        let x_new = compute_y(self.amp, y_new, d)?;
+
+        // Round against the trader, then gross up for the fee charged on the input
+        let value_in = (x_new + 1)
+            .checked_sub(x)
+            .ok_or(ErrorCode::CalculationFailure)?;
+        let amount_in = self.amount(value_in, zero_for_one, true)?;
+        let amount_in =
+            (amount_in * BASIS_POINTS as u128).div_ceil((BASIS_POINTS - self.fee) as u128);
+
+        u64::try_from(amount_in).map_err(|_| error!(ErrorCode::InsufficientLiquidity))
+    }
+
+    fn spot_price(&self, reserve_in: u64, reserve_out: u64, zero_for_one: bool) -> Result<u128> {
+        // Price of the input's value in the output's value, converted back to token amounts
+        let x = self.value(reserve_in as u128, zero_for_one, false)?;
+        let y = self.value(reserve_out as u128, !zero_for_one, false)?;
+        let value_price = compute_spot_price(self.amp, x, y)?;
+        let price = if zero_for_one {
+            mul_div(value_price, self.price, 1 << 64)
+        } else {
+            mul_div(value_price, 1 << 64, self.price)
+        };
+        price.ok_or(error!(ErrorCode::CalculationFailure))
+    }
+
+    fn require_invariant(
+        &self,
+        reserve0: u128,
+        reserve1: u128,
+        balance0: u128,
+        balance1: u128,
+    ) -> Result<()> {
+        let d = compute_d(self.amp, self.value(reserve0, true, false)?, reserve1)?;
+        let d_new = compute_d(self.amp, self.value(balance0, true, false)?, balance1)?;
+        require!(d_new >= d, ErrorCode::InvariantViolated);
+        Ok(())
+    }
+
+    /// The invariant D of the reserves valued at the oracle price, which moves with the price as well as the fees.
+    fn invariant(&self, reserve0: u64, reserve1: u64) -> Result<u128> {
+        compute_d(
+            self.amp,
+            self.value(reserve0 as u128, true, false)?,
+            reserve1 as u128,
+        )
+    }
+}
@@ -0,0 +1,223 @@
+use crate::constant::BASIS_POINTS;
+use crate::curve::Curve;
+use crate::error::ErrorCode;
+use crate::utils::{div_wide, full_mul, mul_div};
+use anchor_lang::prelude::*;
+
+// Number of tokens in a pool
+const N_COINS: u128 = 2;
+
+// Newton iterations allowed before giving up on convergence
+const MAX_ITERATIONS: usize = 255;
+
+/// Returns the StableSwap invariant D of the balances `x0` and `x1`, solving
+/// `A·n^n·(x0 + x1) + D = A·n^n·D + D^(n+1) / (n^n·x0·x1)` by Newton's method.
+pub fn compute_d(amp: u64, x0: u128, x1: u128) -> Result<u128> {
+    let sum = x0.checked_add(x1).ok_or(ErrorCode::CalculationFailure)?;
+    if sum == 0 {
+        return Ok(0);
+    }
+
+    let ann = amp as u128 * N_COINS;
+    let ann_sum = ann.checked_mul(sum).ok_or(ErrorCode::CalculationFailure)?;
+    let mut d = sum;
+    for _ in 0..MAX_ITERATIONS {
+        // D_P = D^(n+1) / (n^n·x0·x1)
+        let mut d_p = d;
+        for x in [x0, x1] {
+            d_p = x
+                .checked_mul(N_COINS)
+                .and_then(|x| mul_div(d_p, d, x))
+                .ok_or(ErrorCode::CalculationFailure)?;
+        }
+
+        let d_prev = d;
+        let numerator = d_p
+            .checked_mul(N_COINS)
+            .and_then(|n| n.checked_add(ann_sum))
+            .ok_or(ErrorCode::CalculationFailure)?;
+        let denominator = d_p
+            .checked_mul(N_COINS + 1)
+            .and_then(|n| n.checked_add((ann - 1).checked_mul(d)?))
+            .ok_or(ErrorCode::CalculationFailure)?;
+        d = mul_div(numerator, d, denominator).ok_or(ErrorCode::CalculationFailure)?;
+
+        if d.abs_diff(d_prev) <= 1 {
+            return Ok(d);
+        }
+    }
+
+    err!(ErrorCode::CalculationFailure)
+}
+
+/// Returns the balance of one token that keeps the invariant at `d` when the other holds `x`.
+pub fn compute_y(amp: u64, x: u128, d: u128) -> Result<u128> {
+    let ann = amp as u128 * N_COINS;
+
+    // Solve y^2 + (b - D)·y = c, with c = D^(n+1) / (n^n·x·Ann) and b = x + D / Ann
+    let c = x
+        .checked_mul(N_COINS)
+        .and_then(|x| mul_div(d, d, x))
+        .and_then(|c| mul_div(c, d, ann * N_COINS))
+        .ok_or(ErrorCode::CalculationFailure)?;
+    let b = x
+        .checked_add(d / ann)
+        .ok_or(ErrorCode::CalculationFailure)?;
+
+    let mut y = d;
+    for _ in 0..MAX_ITERATIONS {
+        // y = (y^2 + c) / (2y + b - D), the numerator in 256 bits
+        let y_prev = y;
+        let (high, low) = full_mul(y, y);
+        let (low, carry) = low.overflowing_add(c);
+        let denominator = y
+            .checked_mul(2)
+            .and_then(|y2| y2.checked_add(b))
+            .and_then(|y2_b| y2_b.checked_sub(d))
+            .ok_or(ErrorCode::CalculationFailure)?;
+        y = high
+            .checked_add(carry as u128)
+            .and_then(|high| div_wide(high, low, denominator))
+            .ok_or(ErrorCode::CalculationFailure)?;
+
+        if y.abs_diff(y_prev) <= 1 {
+            return Ok(y);
+        }
+    }
+
+    err!(ErrorCode::CalculationFailure)
+}
+
+/// Returns the UQ64.64 marginal price of `x` in `y` on the invariant through the balances `x` and `y`.
+pub fn compute_spot_price(amp: u64, x: u128, y: u128) -> Result<u128> {
+    let ann = amp as u128 * N_COINS;
+    let d = compute_d(amp, x, y)?;
+
+    // The ratio of the invariant's partial derivatives, y·(Ann·x + D_P) / (x·(Ann·y + D_P))
+    let d_p = x
+        .checked_mul(N_COINS)
+        .and_then(|x| mul_div(d, d, x))
+        .and_then(|d_p| mul_div(d_p, d, y.checked_mul(N_COINS)?))
+        .ok_or(ErrorCode::CalculationFailure)?;
+    let partial = |balance: u128| ann.checked_mul(balance)?.checked_add(d_p);
+    partial(x)
+        .and_then(|partial_x| mul_div(y, partial_x, x))
+        .and_then(|numerator| mul_div(numerator, 1 << 64, partial(y)?))
+        .ok_or(error!(ErrorCode::CalculationFailure))
+}
+
+/// Curve's StableSwap invariant for two tokens, over the reserves brought to common decimals.
+pub struct StableSwap {
+    pub amp: u64,   // Amplification coefficient, current point of its ramp
+    pub rate0: u64, // Multiplier bringing token0 amounts to the common decimals
+    pub rate1: u64, // Multiplier bringing token1 amounts to the common decimals
+    pub fee: u64,   // Swap fee in basis points
+}
+
+impl StableSwap {
+    // Returns the multipliers of the input and output tokens
+    fn rates(&self, zero_for_one: bool) -> (u128, u128) {
+        match zero_for_one {
+            true => (self.rate0 as u128, self.rate1 as u128),
+            false => (self.rate1 as u128, self.rate0 as u128),
+        }
+    }
+}
+
+// Returns an amount in the common decimals
+fn scale(amount: u128, rate: u128) -> Result<u128> {
+    amount
+        .checked_mul(rate)
+        .ok_or(error!(ErrorCode::CalculationFailure))
+}
+
+impl Curve for StableSwap {
+    fn swap_exact_in(
+        &self,
+        amount_in: u64,
+        reserve_in: u64,
+        reserve_out: u64,
+        zero_for_one: bool,
+    ) -> Result<u64> {
+        let (rate_in, rate_out) = self.rates(zero_for_one);
+        let amount_in_with_fee =
+            amount_in as u128 * (BASIS_POINTS - self.fee) as u128 / BASIS_POINTS as u128;
+
+        let x = scale(reserve_in as u128, rate_in)?;
+        let y = scale(reserve_out as u128, rate_out)?;
+        let d = compute_d(self.amp, x, y)?;
+        let x_new = x
+            .checked_add(scale(amount_in_with_fee, rate_in)?)
+            .ok_or(ErrorCode::CalculationFailure)?;
+        let y_new = compute_y(self.amp, x_new, d)?;
+
+        // Round against the trader, back in the output token's decimals
+        Ok((y.saturating_sub(y_new.saturating_add(1)) / rate_out) as u64)
+    }
+
+    fn swap_exact_out(
+        &self,
+        amount_out: u64,
+        reserve_in: u64,
+        reserve_out: u64,
+        zero_for_one: bool,
+    ) -> Result<u64> {
+        require!(amount_out < reserve_out, ErrorCode::InsufficientLiquidity);
+
+        let (rate_in, rate_out) = self.rates(zero_for_one);
+        let x = scale(reserve_in as u128, rate_in)?;
+        let y = scale(reserve_out as u128, rate_out)?;
+        let d = compute_d(self.amp, x, y)?;
+        let x_new = compute_y(self.amp, y - scale(amount_out as u128, rate_out)?, d)?;
+
+        // Round against the trader, back in the input token's decimals, then gross up for the fee
+        // charged on the input
+        let amount_in = x_new
+            .checked_add(1)
+            .and_then(|x_new| x_new.checked_sub(x))
+            .and_then(|amount_in| {
+                amount_in
+                    .div_ceil(rate_in)
+                    .checked_mul(BASIS_POINTS as u128)
+            })
+            .ok_or(ErrorCode::CalculationFailure)?
+            .div_ceil((BASIS_POINTS - self.fee) as u128);
+
+        u64::try_from(amount_in).map_err(|_| error!(ErrorCode::InsufficientLiquidity))
+    }
+
+    fn spot_price(&self, reserve_in: u64, reserve_out: u64, zero_for_one: bool) -> Result<u128> {
+        // Price in the common decimals, converted back to token amounts
+        let (rate_in, rate_out) = self.rates(zero_for_one);
+        let price = compute_spot_price(
+            self.amp,
+            scale(reserve_in as u128, rate_in)?,
+            scale(reserve_out as u128, rate_out)?,
+        )?;
+        mul_div(price, rate_in, rate_out).ok_or(error!(ErrorCode::CalculationFailure))
+    }
+
+    fn require_invariant(
+        &self,
+        reserve0: u128,
+        reserve1: u128,
+        balance0: u128,
+        balance1: u128,
+    ) -> Result<()> {
+        let (rate0, rate1) = self.rates(true);
+        let d = compute_d(self.amp, scale(reserve0, rate0)?, scale(reserve1, rate1)?)?;
+        let d_new = compute_d(self.amp, scale(balance0, rate0)?, scale(balance1, rate1)?)?;
+        require!(d_new >= d, ErrorCode::InvariantViolated);
+        Ok(())
+    }
+
+    /// The invariant D of the scaled reserves.
+    fn invariant(&self, reserve0: u64, reserve1: u64) -> Result<u128> {
+        let (rate0, rate1) = self.rates(true);
+        compute_d(
+            self.amp,
+            scale(reserve0 as u128, rate0)?,
+            scale(reserve1 as u128, rate1)?,
+        )
+    }
+}
@@ -0,0 +1,206 @@
+use crate::constant::BASIS_POINTS;
+use crate::curve::Curve;
+use crate::error::ErrorCode;
+use anchor_lang::prelude::*;
+use fixed::types::{I64F64, U64F64};
+
+// ln(2) as UQ64.64
+const LN_2: U64F64 = U64F64::from_bits(0xB17217F7D1CF79AB);
+
+// Largest share of a reserve a single swap may move in or out, in basis points
+const MAX_RATIO: u64 = 3000;
+
+/// Returns log2 of the UQ64.64 value with the given bits, which must not be zero.
+fn log2(bits: u128) -> I64F64 {
+    // Split off the integer part, normalizing the rest into [1, 2)
+    let msb = 127 - bits.leading_zeros() as i32;
+    let shift = msb - 64;
+    let mut y = U64F64::from_bits(if shift >= 0 {
+        bits >> shift
+    } else {
+        bits << -shift
+    });
+    let mut result = I64F64::from_num(shift);
+
+    // Each squaring yields the next fractional bit
+    let two = U64F64::from_num(2);
+    for i in 1..64 {
+        y *= y;
+        if y >= two {
+            y /= 2;
+            result += I64F64::from_bits(1 << (64 - i));
+        }
+    }
+    result
+}
+
+/// Returns 2^x, or None if it does not fit a UQ64.64.
+fn exp2(x: I64F64) -> Option<U64F64> {
+    let n = x.floor();
+    let f = U64F64::from_num(x - n);
+
+    // 2^f = e^(f·ln2) by its Taylor series, f·ln2 < 1 so the terms vanish quickly
+    let z = f * LN_2;
+    let mut sum = U64F64::ONE;
+    let mut term = U64F64::ONE;
+    for k in 1..32u128 {
+        term = term * z / k;
+        if term == 0 {
+            break;
+        }
+        sum += term;
+    }
+
+    let n = n.to_num::<i64>();
+    match n {
+        n if n >= 64 => None,
+        n if n <= -64 => Some(U64F64::ZERO),
+        n if n >= 0 => Some(U64F64::from_bits(sum.to_bits() << n)),
+        n => Some(U64F64::from_bits(sum.to_bits() >> -n)),
+    }
+}
+
+/// Returns `base^exponent` rounded up by a margin covering the error of the log2 and exp2 approximations.
+fn pow_up(base: U64F64, exponent: U64F64) -> Result<U64F64> {
+    let power = exp2(log2(base.to_bits()) * I64F64::from_num(exponent))
+        .ok_or(ErrorCode::CalculationFailure)?;
+    power
+        .checked_add(power >> 44)
+        .and_then(|power| power.checked_add(U64F64::DELTA))
+        .ok_or(error!(ErrorCode::CalculationFailure))
+}
+
+// Returns log2 of the weighted geometric mean x0^weight0 · x1^weight1 of two amounts, weights in basis points
+fn log_invariant(weight0: u64, weight1: u64, x0: u128, x1: u128) -> Result<I64F64> {
+    require!(x0 > 0 && x1 > 0, ErrorCode::InsufficientLiquidity);
+
+    // Kept as log2, the amounts do not fit a UQ64.64
+    let bp = I64F64::from_num(BASIS_POINTS);
+    Ok(
+        (log2(x0) + I64F64::from_num(64)) * I64F64::from_num(weight0) / bp
+            + (log2(x1) + I64F64::from_num(64)) * I64F64::from_num(weight1) / bp,
+    )
+}
+
+/// Balancer's weighted product x^weight0 · y^weight1 = k.
+pub struct Weighted {
+    pub weight0: u64, // Normalized weight of token0 in basis points
+    pub weight1: u64, // Normalized weight of token1 in basis points
+    pub fee: u64,     // Swap fee in basis points
+}
+
+impl Weighted {
+    // Returns the (input, output) weights of a swap
+    fn swap_weights(&self, zero_for_one: bool) -> (u64, u64) {
+        if zero_for_one {
+            (self.weight0, self.weight1)
+        } else {
+            (self.weight1, self.weight0)
+        }
+    }
+}
+
+impl Curve for Weighted {
+    fn swap_exact_in(
+        &self,
+        amount_in: u64,
+        reserve_in: u64,
+        reserve_out: u64,
+        zero_for_one: bool,
+    ) -> Result<u64> {
+        require!(
+            amount_in as u128 * BASIS_POINTS as u128 <= reserve_in as u128 * MAX_RATIO as u128,
+            ErrorCode::InsufficientLiquidity
+        );
+        let amount_in_with_fee =
+            amount_in as u128 * (BASIS_POINTS - self.fee) as u128 / BASIS_POINTS as u128;
+
+        // amount_out = reserve_out · (1 - (reserve_in / (reserve_in + amount_in))^(weight_in / weight_out))
+        let (weight_in, weight_out) = self.swap_weights(zero_for_one);
+        let base = U64F64::from_bits(
+            ((reserve_in as u128) << 64) / (reserve_in as u128 + amount_in_with_fee),
+        );
+        let exponent = U64F64::from_num(weight_in) / U64F64::from_num(weight_out);
+        let power = pow_up(base, exponent)?;
+
+        // Round against the trader
+        Ok((U64F64::from_num(reserve_out) * U64F64::ONE.saturating_sub(power)).to_num::<u64>())
+    }
+
+    fn swap_exact_out(
+        &self,
+        amount_out: u64,
+        reserve_in: u64,
+        reserve_out: u64,
+        zero_for_one: bool,
+    ) -> Result<u64> {
+        require!(
+            amount_out as u128 * BASIS_POINTS as u128 <= reserve_out as u128 * MAX_RATIO as u128,
+            ErrorCode::InsufficientLiquidity
+        );
+
+        // amount_in = reserve_in · ((reserve_out / (reserve_out - amount_out))^(weight_out / weight_in) - 1)
+        let (weight_in, weight_out) = self.swap_weights(zero_for_one);
+        let base = U64F64::from_num(reserve_out) / U64F64::from_num(reserve_out - amount_out);
+        let exponent = U64F64::from_num(weight_out) / U64F64::from_num(weight_in);
+        let power = pow_up(base, exponent)?;
+
+        // Round against the trader, then gross up for the fee charged on the input
+        let amount_in = U64F64::from_num(reserve_in)
+            .checked_mul(power.saturating_sub(U64F64::ONE))
+            .ok_or(ErrorCode::CalculationFailure)?
+            .ceil()
+            .to_num::<u128>();
+        let amount_in =
+            (amount_in * BASIS_POINTS as u128).div_ceil((BASIS_POINTS - self.fee) as u128);
+
+        u64::try_from(amount_in).map_err(|_| error!(ErrorCode::InsufficientLiquidity))
+    }
+
+    fn spot_price(&self, reserve_in: u64, reserve_out: u64, zero_for_one: bool) -> Result<u128> {
+        // Each reserve is priced in proportion to its weight
+        let (weight_in, weight_out) = self.swap_weights(zero_for_one);
+        let price = U64F64::from_num(reserve_out) / U64F64::from_num(reserve_in);
+        Ok((price.saturating_mul_int(weight_in as u128) / weight_out as u128).to_bits())
+    }
+
+    fn require_invariant(
+        &self,
+        reserve0: u128,
+        reserve1: u128,
+        balance0: u128,
+        balance1: u128,
+    ) -> Result<()> {
+        let (weight0, weight1) = (self.weight0, self.weight1);
+        require!(
+            log_invariant(weight0, weight1, balance0, balance1)?
+                >= log_invariant(weight0, weight1, reserve0, reserve1)?,
+            ErrorCode::InvariantViolated
+        );
+        Ok(())
+    }
+
+    /// The weighted geometric mean of the reserves.
+    fn invariant(&self, reserve0: u64, reserve1: u64) -> Result<u128> {
+        if reserve0 == 0 || reserve1 == 0 {
+            return Ok(0);
+        }
+        let log = log_invariant(
+            self.weight0,
+            self.weight1,
+            reserve0 as u128,
+            reserve1 as u128,
+        )?;
+        exp2(log)
+            .map(|invariant| invariant.to_num::<u128>())
+            .ok_or(error!(ErrorCode::CalculationFailure))
+    }
+
+    /// Mints the weighted geometric mean of the amounts.
+    fn initial_liquidity(&self, amount0: u64, amount1: u64) -> Result<u64> {
+        let log = log_invariant(self.weight0, self.weight1, amount0 as u128, amount1 as u128)?;
+        exp2(log)
+            .and_then(|liquidity| liquidity.checked_to_num::<u64>())
+            .ok_or(error!(ErrorCode::CalculationFailure))
+    }
+}
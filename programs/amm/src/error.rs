@@ -22,4 +22,92 @@ pub enum ErrorCode {
     InsufficientLiquidity,
     #[msg("InsufficientUserBalance")]
     InsufficientUserBalance,
+    #[msg("InvalidVault")]
+    InvalidVault,
+    #[msg("NotPendingOwner")]
+    NotPendingOwner,
+    #[msg("Paused")]
+    Paused,
+    #[msg("PoolCreationRestricted")]
+    PoolCreationRestricted,
+    #[msg("InvalidTransferFee")]
+    InvalidTransferFee,
+    #[msg("ExtensionNotAllowed")]
+    ExtensionNotAllowed,
+    #[msg("PermanentDelegateNotAllowed")]
+    PermanentDelegateNotAllowed,
+    #[msg("NonTransferableNotAllowed")]
+    NonTransferableNotAllowed,
+    #[msg("TransferHookNotAllowed")]
+    TransferHookNotAllowed,
+    #[msg("ConfidentialTransferNotAllowed")]
+    ConfidentialTransferNotAllowed,
+    #[msg("DefaultAccountStateNotAllowed")]
+    DefaultAccountStateNotAllowed,
+    #[msg("MintCloseAuthorityNotAllowed")]
+    MintCloseAuthorityNotAllowed,
+    #[msg("FreezeAuthorityNotAllowed")]
+    FreezeAuthorityNotAllowed,
+    #[msg("InvalidNativeMint")]
+    InvalidNativeMint,
+    #[msg("ObservationCardinalityTooLarge")]
+    ObservationCardinalityTooLarge,
+    #[msg("ObservationTooOld")]
+    ObservationTooOld,
+    #[msg("TooManyObservations")]
+    TooManyObservations,
+    #[msg("Expired")]
+    Expired,
+    #[msg("InvalidRoute")]
+    InvalidRoute,
+    #[msg("InvariantViolated")]
+    InvariantViolated,
+    #[msg("InvalidCallbackProgram")]
+    InvalidCallbackProgram,
+    #[msg("FlashLoanActive")]
+    FlashLoanActive,
+    #[msg("FlashLoanNotActive")]
+    FlashLoanNotActive,
+    #[msg("FlashLoanEndMissing")]
+    FlashLoanEndMissing,
+    #[msg("FlashLoanNotRepaid")]
+    FlashLoanNotRepaid,
+    #[msg("Locked")]
+    Locked,
+    #[msg("InvalidCurve")]
+    InvalidCurve,
+    #[msg("InvalidAmp")]
+    InvalidAmp,
+    #[msg("InvalidAmpRamp")]
+    InvalidAmpRamp,
+    #[msg("CalculationFailure")]
+    CalculationFailure,
+    #[msg("InvalidWeights")]
+    InvalidWeights,
+    #[msg("InvalidTick")]
+    InvalidTick,
+    #[msg("InvalidTickSpacing")]
+    InvalidTickSpacing,
+    #[msg("InvalidTickArray")]
+    InvalidTickArray,
+    #[msg("InvalidSqrtPrice")]
+    InvalidSqrtPrice,
+    #[msg("InvalidSqrtPriceLimit")]
+    InvalidSqrtPriceLimit,
+    #[msg("UnsupportedMint")]
+    UnsupportedMint,
+    #[msg("InvalidOracle")]
+    InvalidOracle,
+    #[msg("OracleUnavailable")]
+    OracleUnavailable,
+    #[msg("OracleStale")]
+    OracleStale,
+    #[msg("OracleConfidenceTooWide")]
+    OracleConfidenceTooWide,
+    #[msg("MathOverflow")]
+    MathOverflow,
+    #[msg("InvalidDecimals")]
+    InvalidDecimals,
+    #[msg("NoLiquidity")]
+    NoLiquidity,
 }
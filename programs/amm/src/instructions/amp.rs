@@ -0,0 +1,55 @@
+use crate::state::Config;
+use crate::state::Pool;
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct RampAmp<'info> {
+    pub owner: Signer<'info>, // The owner of the contract
+    #[account(seeds = [b"config"], bump = config.bump, has_one = owner)]
+    pub config: Account<'info, Config>, // The configuration account
+    #[account(mut, seeds = [b"pool", pool.token0.as_ref(), pool.token1.as_ref(), pool.fee_tier.as_ref()], bump = pool.bump)]
+    pub pool: Account<'info, Pool>, // The StableSwap or oracle-pegged pool being ramped
+}
+
+#[event]
+pub struct AmpRampStarted {
+    pub pool: Pubkey,
+    pub amp_initial: u64,
+    pub amp_target: u64,
+    pub amp_ramp_start: i64,
+    pub amp_ramp_end: i64,
+}
+
+#[event]
+pub struct AmpRampStopped {
+    pub pool: Pubkey,
+    pub amp: u64,
+}
+
+pub fn ramp_amp(ctx: Context<RampAmp>, amp_target: u64, amp_ramp_end: i64) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+    let now = Clock::get()?.unix_timestamp;
+
+    pool.ramp_amp(amp_target, amp_ramp_end, now)?;
+    emit!(AmpRampStarted {
+        pool: pool.key(),
+        amp_initial: pool.amp_initial,
+        amp_target,
+        amp_ramp_start: now,
+        amp_ramp_end,
+    });
+
+    Ok(())
+}
+
+pub fn stop_ramp_amp(ctx: Context<RampAmp>) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+
+    pool.stop_ramp_amp(Clock::get()?.unix_timestamp)?;
+    emit!(AmpRampStopped {
+        pool: pool.key(),
+        amp: pool.amp_target,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,170 @@
+use crate::constant::{MAX_TICK, MIN_TICK};
+use crate::error::ErrorCode;
+use crate::state::ConcentratedPool;
+use crate::state::Config;
+use crate::state::FeeTier;
+use crate::state::TickArray;
+use crate::utils::{require_allowed_mint, require_no_transfer_fee};
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+#[derive(Accounts)]
+pub struct CreateConcentratedPool<'info> {
+    // Mints of the two tokens that will form the pool, owned by either token program.
+    #[account(mint::token_program = token_program0)]
+    pub mint0: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = token_program1)]
+    pub mint1: Box<InterfaceAccount<'info, Mint>>,
+
+    // Creator of the pool and payer for the transaction.
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    // Configuration account, only the owner may create pools unless pool creation is permissionless.
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = config.permissionless_pool_creation || config.owner == creator.key() @ ErrorCode::PoolCreationRestricted
+    )]
+    pub config: Account<'info, Config>,
+
+    // Recipient of the pool creation fee.
+    /// CHECK: protocol fee recipient, checked against the config
+    #[account(mut, address = config.fee_to)]
+    pub fee_to: AccountInfo<'info>,
+
+    // Fee tier the pool charges, one concentrated pool per mint pair and fee tier.
+    #[account(seeds = [b"fee_tier", fee_tier.fee.to_le_bytes().as_ref()], bump = fee_tier.bump)]
+    pub fee_tier: Account<'info, FeeTier>,
+
+    // The pool account being created, with seeds for uniqueness.
+    #[account(init, seeds = [b"concentrated_pool", mint0.key().as_ref(), mint1.key().as_ref(), fee_tier.key().as_ref()], bump, payer = creator, space = 8 + ConcentratedPool::INIT_SPACE)]
+    pub pool: Box<Account<'info, ConcentratedPool>>,
+
+    // Authority derived from the pool account, used to control associated PDAs.
+    /// CHECK: authority so 1 acc pass in can derive all other PDAs
+    #[account(seeds=[b"authority", pool.key().as_ref()], bump)]
+    pub pool_authority: AccountInfo<'info>,
+
+    // Associated token accounts for each token in the pool, tied to the pool's authority.
+    #[account(
+        associated_token::mint = mint0,
+        associated_token::authority = pool_authority,
+        associated_token::token_program = token_program0
+    )]
+    pub vault0: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        associated_token::mint = mint1,
+        associated_token::authority = pool_authority,
+        associated_token::token_program = token_program1
+    )]
+    pub vault1: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // Required programs and system accounts.
+    pub token_program0: Interface<'info, TokenInterface>,
+    pub token_program1: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(start_tick: i32)]
+pub struct InitializeTickArray<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    // Pool the ticks belong to
+    #[account(seeds = [b"concentrated_pool", pool.token0.as_ref(), pool.token1.as_ref(), pool.fee_tier.as_ref()], bump = pool.bump)]
+    pub pool: Box<Account<'info, ConcentratedPool>>,
+
+    // Tick array being created, one per start tick
+    #[account(init, payer = payer, seeds = [b"tick_array", pool.key().as_ref(), start_tick.to_le_bytes().as_ref()], bump, space = 8 + std::mem::size_of::<TickArray>())]
+    pub tick_array: AccountLoader<'info, TickArray>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// Define the event for concentrated pool creation.
+#[event]
+pub struct ConcentratedPoolCreated {
+    pub pool: Pubkey,
+    pub mint0: Pubkey,
+    pub mint1: Pubkey,
+    pub fee_tier: Pubkey,
+    pub fee: u64,
+    pub tick_spacing: u16,
+    pub sqrt_price: u128,
+    pub creator: Pubkey,
+}
+
+pub fn create_concentrated_pool(
+    ctx: Context<CreateConcentratedPool>,
+    tick_spacing: u16,
+    sqrt_price: u128,
+) -> Result<()> {
+    // Reject mints using extensions that are not on the allow-list, swaps are priced on the amounts sent
+    // so transfer fees are not supported either.
+    for mint in [&ctx.accounts.mint0, &ctx.accounts.mint1] {
+        require_allowed_mint(&ctx.accounts.config, mint)?;
+        require_no_transfer_fee(mint)?;
+    }
+
+    // Charge the pool creation fee, if any.
+    let creation_fee = ctx.accounts.config.pool_creation_fee;
+    if creation_fee > 0 {
+        transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.creator.to_account_info(),
+                    to: ctx.accounts.fee_to.to_account_info(),
+                },
+            ),
+            creation_fee,
+        )?;
+    }
+
+    let pool = &mut ctx.accounts.pool;
+
+    // Initialize the pool with its creator, the provided token mints, their vaults, the fee tier and its starting price.
+    pool.initialize(
+        ctx.bumps.pool,
+        ctx.accounts.creator.key(),
+        ctx.accounts.mint0.key(),
+        ctx.accounts.mint1.key(),
+        ctx.accounts.vault0.key(),
+        ctx.accounts.vault1.key(),
+        &ctx.accounts.fee_tier,
+        tick_spacing,
+        sqrt_price,
+    )?;
+
+    emit!(ConcentratedPoolCreated {
+        pool: pool.key(),
+        mint0: ctx.accounts.mint0.key(),
+        mint1: ctx.accounts.mint1.key(),
+        fee_tier: ctx.accounts.fee_tier.key(),
+        fee: pool.fee,
+        tick_spacing,
+        sqrt_price,
+        creator: ctx.accounts.creator.key(),
+    });
+
+    Ok(())
+}
+
+pub fn initialize_tick_array(ctx: Context<InitializeTickArray>, start_tick: i32) -> Result<()> {
+    // Arrays tile the tick range, each starting on a multiple of its size
+    let pool = &ctx.accounts.pool;
+    require!(
+        pool.tick_array_start(start_tick) == start_tick
+            && start_tick >= pool.tick_array_start(MIN_TICK)
+            && start_tick <= MAX_TICK,
+        ErrorCode::InvalidTickArray
+    );
+
+    ctx.accounts
+        .tick_array
+        .load_init()?
+        .initialize(pool.key(), start_tick)
+}
@@ -0,0 +1,216 @@
+use crate::error::ErrorCode;
+use crate::state::ConcentratedPool;
+use crate::state::Config;
+use crate::state::TickArray;
+use crate::utils::{lock_concentrated_pool, require_not_expired, transfer_tokens};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+#[derive(Accounts)]
+pub struct SwapConcentrated<'info> {
+    // Config PDA
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    pub owner: Signer<'info>,
+
+    // User's source token account, must be owned by the user
+    #[account(mut, has_one = owner)]
+    pub user_ata_src: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // User's destination token account, must be owned by the user
+    #[account(mut, has_one = owner)]
+    pub user_ata_des: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // Pool account, re-derived from its mint pair
+    #[account(mut, seeds = [b"concentrated_pool", pool.token0.as_ref(), pool.token1.as_ref(), pool.fee_tier.as_ref()], bump = pool.bump)]
+    pub pool: Box<Account<'info, ConcentratedPool>>,
+
+    // Pool authority PDA
+    /// CHECK: authority so 1 acc pass in can derive all other PDAs
+    #[account(seeds=[b"authority", pool.key().as_ref()], bump)]
+    pub pool_authority: AccountInfo<'info>,
+
+    // Source vault for the swap, must be one of the pool's vaults and match the mint of the user's source token account
+    #[account(
+        mut,
+        constraint = pool.is_vault(&vault_src.key()) @ ErrorCode::InvalidVault,
+        constraint = user_ata_src.mint == vault_src.mint
+    )]
+    pub vault_src: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // Destination vault for the swap, must be the pool's other vault and match the mint of the user's destination token account
+    #[account(
+        mut,
+        constraint = pool.is_vault(&vault_des.key()) @ ErrorCode::InvalidVault,
+        constraint = vault_des.key() != vault_src.key() @ ErrorCode::InvalidVault,
+        constraint = user_ata_des.mint == vault_des.mint
+    )]
+    pub vault_des: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // Mints of the source and destination vaults, owned by their token programs
+    #[account(address = vault_src.mint, mint::token_program = token_program_src)]
+    pub mint_src: Box<InterfaceAccount<'info, Mint>>,
+    #[account(address = vault_des.mint, mint::token_program = token_program_des)]
+    pub mint_des: Box<InterfaceAccount<'info, Mint>>,
+
+    pub token_program_src: Interface<'info, TokenInterface>,
+    pub token_program_des: Interface<'info, TokenInterface>,
+}
+
+#[event]
+pub struct ConcentratedSwapEvent {
+    pub owner: Pubkey,
+    pub pool: Pubkey,
+    pub input_amount: u64,
+    pub output_amount: u64,
+    pub vault_src: Pubkey,
+    pub vault_des: Pubkey,
+    pub sqrt_price: u128,
+    pub liquidity: u128,
+    pub tick_current: i32,
+}
+
+pub fn swap_concentrated_exact_input<'info>(
+    ctx: Context<'_, '_, 'info, 'info, SwapConcentrated<'info>>,
+    input_amount: u64,
+    min_output_amount: u64,
+    sqrt_price_limit: u128,
+) -> Result<()> {
+    swap_concentrated(ctx, input_amount, min_output_amount, true, sqrt_price_limit)
+}
+
+pub fn swap_concentrated_exact_output<'info>(
+    ctx: Context<'_, '_, 'info, 'info, SwapConcentrated<'info>>,
+    output_amount: u64,
+    max_input_amount: u64,
+    sqrt_price_limit: u128,
+) -> Result<()> {
+    swap_concentrated(
+        ctx,
+        output_amount,
+        max_input_amount,
+        false,
+        sqrt_price_limit,
+    )
+}
+
+pub fn swap_concentrated_exact_input_with_deadline<'info>(
+    ctx: Context<'_, '_, 'info, 'info, SwapConcentrated<'info>>,
+    input_amount: u64,
+    min_output_amount: u64,
+    sqrt_price_limit: u128,
+    deadline: i64,
+) -> Result<()> {
+    require_not_expired(deadline)?;
+    swap_concentrated_exact_input(ctx, input_amount, min_output_amount, sqrt_price_limit)
+}
+
+pub fn swap_concentrated_exact_output_with_deadline<'info>(
+    ctx: Context<'_, '_, 'info, 'info, SwapConcentrated<'info>>,
+    output_amount: u64,
+    max_input_amount: u64,
+    sqrt_price_limit: u128,
+    deadline: i64,
+) -> Result<()> {
+    require_not_expired(deadline)?;
+    swap_concentrated_exact_output(ctx, output_amount, max_input_amount, sqrt_price_limit)
+}
+
+// Swaps `amount`, an input if `exact_input` else an output, with `other_amount_threshold` the least output or
+// most input the user accepts. The tick arrays crossed are given in the remaining accounts.
+fn swap_concentrated<'info>(
+    ctx: Context<'_, '_, 'info, 'info, SwapConcentrated<'info>>,
+    amount: u64,
+    other_amount_threshold: u64,
+    exact_input: bool,
+    sqrt_price_limit: u128,
+) -> Result<()> {
+    let accounts = ctx.accounts;
+
+    // Lock the pool against re-entry through the token transfers
+    accounts.pool.require_not_paused(&accounts.config)?;
+    lock_concentrated_pool(&mut accounts.pool)?;
+    require!(amount > 0, ErrorCode::InsufficientAmount);
+
+    let tick_arrays = ctx
+        .remaining_accounts
+        .iter()
+        .map(AccountLoader::<TickArray>::try_from)
+        .collect::<Result<Vec<_>>>()?;
+    for tick_array in &tick_arrays {
+        require_keys_eq!(
+            tick_array.load()?.pool,
+            accounts.pool.key(),
+            ErrorCode::InvalidTickArray
+        );
+    }
+    let zero_for_one = accounts.vault_src.key() == accounts.pool.vault0;
+    let (amount_in, amount_out) = accounts.pool.swap(
+        &tick_arrays,
+        amount,
+        exact_input,
+        zero_for_one,
+        sqrt_price_limit,
+    )?;
+
+    // Ensure the amounts meet the user's limits
+    if exact_input {
+        require!(
+            amount_out >= other_amount_threshold,
+            ErrorCode::InsufficientOutputAmount
+        );
+    } else {
+        require!(
+            amount_in <= other_amount_threshold,
+            ErrorCode::InsufficientInputAmount
+        );
+    }
+    require!(amount_in > 0, ErrorCode::InsufficientInputAmount);
+    require!(amount_out > 0, ErrorCode::InsufficientOutputAmount);
+    require!(
+        amount_in <= accounts.user_ata_src.amount,
+        ErrorCode::InsufficientUserBalance
+    );
+    require!(
+        amount_out <= accounts.vault_des.amount,
+        ErrorCode::InsufficientLiquidity
+    );
+
+    // Transfer the input to the pool and the output to the user
+    transfer_tokens(
+        &accounts.token_program_src.to_account_info(),
+        &accounts.user_ata_src.to_account_info(),
+        &accounts.mint_src,
+        &accounts.vault_src.to_account_info(),
+        &accounts.owner.to_account_info(),
+        &[],
+        amount_in,
+    )?;
+    let pool_key = accounts.pool.key();
+    let pool_sign: &[&[u8]] = &[b"authority", pool_key.as_ref(), &[ctx.bumps.pool_authority]];
+    transfer_tokens(
+        &accounts.token_program_des.to_account_info(),
+        &accounts.vault_des.to_account_info(),
+        &accounts.mint_des,
+        &accounts.user_ata_des.to_account_info(),
+        &accounts.pool_authority,
+        &[pool_sign],
+        amount_out,
+    )?;
+
+    emit!(ConcentratedSwapEvent {
+        owner: accounts.owner.key(),
+        pool: pool_key,
+        input_amount: amount_in,
+        output_amount: amount_out,
+        vault_src: accounts.vault_src.key(),
+        vault_des: accounts.vault_des.key(),
+        sqrt_price: accounts.pool.sqrt_price,
+        liquidity: accounts.pool.liquidity,
+        tick_current: accounts.pool.tick_current,
+    });
+
+    accounts.pool.unlock();
+    Ok(())
+}
@@ -1,25 +1,47 @@
+use crate::error::ErrorCode;
 use crate::state::Config;
+use crate::state::FeeTier;
+use crate::state::Observations;
 use crate::state::Pool;
+use crate::state::PoolCurve;
+use crate::utils::require_allowed_mint;
 use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
 use anchor_spl::associated_token::AssociatedToken;
-use anchor_spl::token::{Mint, Token, TokenAccount};
+use anchor_spl::token::{self, Token};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
 #[derive(Accounts)]
 pub struct CreatePool<'info> {
-    // Mints of the two tokens that will form the pool.
-    pub mint0: Account<'info, Mint>,
-    pub mint1: Account<'info, Mint>,
+    // Mints of the two tokens that will form the pool, owned by either token program.
+    #[account(mint::token_program = token_program0)]
+    pub mint0: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = token_program1)]
+    pub mint1: Box<InterfaceAccount<'info, Mint>>,
 
-    // Owner of the pool and payer for the transaction.
+    // Creator of the pool and payer for the transaction.
     #[account(mut)]
-    pub owner: Signer<'info>,
+    pub creator: Signer<'info>,
 
-    // Configuration account with specific constraints (owner and bump).
-    #[account(mut, seeds = [b"config"], bump = config.bump, has_one = owner)]
+    // Configuration account, only the owner may create pools unless pool creation is permissionless.
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = config.permissionless_pool_creation || config.owner == creator.key() @ ErrorCode::PoolCreationRestricted
+    )]
     pub config: Account<'info, Config>,
 
+    // Recipient of the pool creation fee.
+    /// CHECK: protocol fee recipient, checked against the config
+    #[account(mut, address = config.fee_to)]
+    pub fee_to: AccountInfo<'info>,
+
+    // Fee tier the pool charges, one pool per mint pair and fee tier.
+    #[account(seeds = [b"fee_tier", fee_tier.fee.to_le_bytes().as_ref()], bump = fee_tier.bump)]
+    pub fee_tier: Account<'info, FeeTier>,
+
     // The pool account being created, with seeds for uniqueness.
-    #[account(init, seeds= [b"pool", mint0.key().as_ref(), mint1.key().as_ref()], bump, payer = owner, space = 8 + Pool::INIT_SPACE)]
+    #[account(init, seeds= [b"pool", mint0.key().as_ref(), mint1.key().as_ref(), fee_tier.key().as_ref()], bump, payer = creator, space = 8 + Pool::INIT_SPACE)]
     pub pool: Box<Account<'info, Pool>>,
 
     // Authority derived from the pool account, used to control associated PDAs.
@@ -27,48 +49,138 @@ pub struct CreatePool<'info> {
     #[account(seeds=[b"authority", pool.key().as_ref()], bump)]
     pub pool_authority: AccountInfo<'info>,
 
+    // Price oracle of the pool, a ring buffer of cumulative price observations.
+    #[account(init, payer = creator, seeds = [b"observations", pool.key().as_ref()], bump, space = 8 + std::mem::size_of::<Observations>())]
+    pub observations: AccountLoader<'info, Observations>,
+
     // Associated token accounts for each token in the pool, tied to the pool's authority.
     #[account(
         associated_token::mint = mint0,
-        associated_token::authority = pool_authority
+        associated_token::authority = pool_authority,
+        associated_token::token_program = token_program0
     )]
-    pub vault0: Box<Account<'info, TokenAccount>>,
+    pub vault0: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         associated_token::mint = mint1,
-        associated_token::authority = pool_authority
+        associated_token::authority = pool_authority,
+        associated_token::token_program = token_program1
     )]
-    pub vault1: Box<Account<'info, TokenAccount>>,
+    pub vault1: Box<InterfaceAccount<'info, TokenAccount>>,
 
     // Mint for the liquidity provider (LP) tokens, controlled by the pool's authority.
-    #[account(init, payer = owner, seeds = [b"lp_mint", pool.key().as_ref()], bump, mint::decimals = 6, mint::authority = pool_authority)]
-    pub lp_mint: Box<Account<'info, Mint>>,
+    #[account(init, payer = creator, seeds = [b"lp_mint", pool.key().as_ref()], bump, mint::decimals = 6, mint::authority = pool_authority)]
+    pub lp_mint: Box<Account<'info, token::Mint>>,
+
+    // LP vault owned by the pool's authority, permanently holding the minimum liquidity.
+    #[account(
+        init,
+        payer = creator,
+        associated_token::mint = lp_mint,
+        associated_token::authority = pool_authority
+    )]
+    pub vault_lp: Box<Account<'info, token::TokenAccount>>,
 
     // Required programs and system accounts.
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Program<'info, Token>,
+    pub token_program0: Interface<'info, TokenInterface>,
+    pub token_program1: Interface<'info, TokenInterface>,
     pub rent: Sysvar<'info, Rent>,
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct SetPoolCreation<'info> {
+    pub owner: Signer<'info>, // The owner of the contract
+    #[account(mut, seeds = [b"config"], bump = config.bump, has_one = owner)]
+    pub config: Account<'info, Config>, // The configuration account
+}
+
+#[event]
+pub struct PoolCreationSet {
+    pub permissionless: bool,
+    pub creation_fee: u64,
+}
+
+pub fn set_pool_creation(
+    ctx: Context<SetPoolCreation>,
+    permissionless: bool,
+    creation_fee: u64,
+) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+
+    config.set_pool_creation(permissionless, creation_fee)?;
+    emit!(PoolCreationSet {
+        permissionless,
+        creation_fee,
+    });
+
+    Ok(())
+}
+
 // Define the event for pool creation.
 #[event]
 pub struct PoolCreated {
     pub pool: Pubkey,
     pub mint0: Pubkey,
     pub mint1: Pubkey,
+    pub fee_tier: Pubkey,
+    pub fee: u64,
+    pub curve: PoolCurve,
+    pub creator: Pubkey,
 }
 
-pub fn create_pool(ctx: Context<CreatePool>) -> Result<()> {
+pub fn create_pool(ctx: Context<CreatePool>, curve: PoolCurve) -> Result<()> {
+    // Reject mints using extensions that are not on the allow-list.
+    require_allowed_mint(&ctx.accounts.config, &ctx.accounts.mint0)?;
+    require_allowed_mint(&ctx.accounts.config, &ctx.accounts.mint1)?;
+
+    // Charge the pool creation fee, if any.
+    let creation_fee = ctx.accounts.config.pool_creation_fee;
+    if creation_fee > 0 {
+        transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.creator.to_account_info(),
+                    to: ctx.accounts.fee_to.to_account_info(),
+                },
+            ),
+            creation_fee,
+        )?;
+    }
+
     let pool = &mut ctx.accounts.pool;
 
-    // Initialize the pool with the provided token mints.
-    pool.initialize(ctx.accounts.mint0.key(), ctx.accounts.mint1.key())?;
+    // Initialize the pool with its creator, the provided token mints, their vaults, the fee tier and its curve.
+    pool.initialize(
+        ctx.bumps.pool,
+        ctx.accounts.creator.key(),
+        ctx.accounts.mint0.key(),
+        ctx.accounts.mint1.key(),
+        ctx.accounts.vault0.key(),
+        ctx.accounts.vault1.key(),
+        ctx.accounts.mint0.decimals,
+        ctx.accounts.mint1.decimals,
+        &ctx.accounts.fee_tier,
+        curve,
+    )?;
+
+    // Start the oracle with a single observation.
+    ctx.accounts
+        .observations
+        .load_init()?
+        .initialize(pool.key(), Clock::get()?.unix_timestamp)?;
 
     // Emit the PoolCreated event.
     emit!(PoolCreated {
         pool: pool.key(),
         mint0: ctx.accounts.mint0.key(),
         mint1: ctx.accounts.mint1.key(),
+        fee_tier: ctx.accounts.fee_tier.key(),
+        fee: ctx.accounts.fee_tier.fee,
+        curve,
+        creator: ctx.accounts.creator.key(),
     });
 
     Ok(())
@@ -0,0 +1,31 @@
+use crate::state::config::Config;
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct ExtensionAllowListSet {
+    pub allowed_extensions: u64,
+    pub freeze_authority_allowed: bool,
+}
+
+#[derive(Accounts)]
+pub struct SetExtensionAllowList<'info> {
+    pub owner: Signer<'info>, // The owner of the contract
+    #[account(mut, seeds = [b"config"], bump = config.bump, has_one = owner)]
+    pub config: Account<'info, Config>, // The configuration account
+}
+
+pub fn set_extension_allow_list(
+    ctx: Context<SetExtensionAllowList>,
+    allowed_extensions: u64,
+    freeze_authority_allowed: bool,
+) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+
+    config.set_extension_allow_list(allowed_extensions, freeze_authority_allowed)?;
+    emit!(ExtensionAllowListSet {
+        allowed_extensions,
+        freeze_authority_allowed,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use crate::state::Config;
+use crate::state::FeeTier;
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+#[instruction(fee: u64)]
+pub struct CreateFeeTier<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>, // The owner of the contract
+    #[account(seeds = [b"config"], bump = config.bump, has_one = owner)]
+    pub config: Account<'info, Config>, // The configuration account
+    #[account(init, payer = owner, seeds = [b"fee_tier", fee.to_le_bytes().as_ref()], bump, space = 8 + FeeTier::INIT_SPACE)]
+    pub fee_tier: Account<'info, FeeTier>, // The fee tier being created
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DeleteFeeTier<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>, // The owner of the contract
+    #[account(seeds = [b"config"], bump = config.bump, has_one = owner)]
+    pub config: Account<'info, Config>, // The configuration account
+    #[account(mut, close = owner, seeds = [b"fee_tier", fee_tier.fee.to_le_bytes().as_ref()], bump = fee_tier.bump)]
+    pub fee_tier: Account<'info, FeeTier>, // The fee tier being deleted
+}
+
+#[event]
+pub struct FeeTierCreated {
+    pub fee_tier: Pubkey,
+    pub fee: u64,
+}
+
+#[event]
+pub struct FeeTierDeleted {
+    pub fee_tier: Pubkey,
+    pub fee: u64,
+}
+
+pub fn create_fee_tier(ctx: Context<CreateFeeTier>, fee: u64) -> Result<()> {
+    let fee_tier = &mut ctx.accounts.fee_tier;
+
+    fee_tier.initialize(ctx.bumps.fee_tier, fee)?;
+    emit!(FeeTierCreated {
+        fee_tier: fee_tier.key(),
+        fee,
+    });
+
+    Ok(())
+}
+
+/// Deletes a fee tier so no new pools can be created with it; existing pools keep their fee.
+pub fn delete_fee_tier(ctx: Context<DeleteFeeTier>) -> Result<()> {
+    emit!(FeeTierDeleted {
+        fee_tier: ctx.accounts.fee_tier.key(),
+        fee: ctx.accounts.fee_tier.fee,
+    });
+
+    Ok(())
+}
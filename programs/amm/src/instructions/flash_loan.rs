@@ -0,0 +1,194 @@
+use crate::constant::BASIS_POINTS;
+use crate::error::ErrorCode;
+use crate::state::Config;
+use crate::state::Observations;
+use crate::state::Pool;
+use crate::utils::lock_pool;
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar::instructions::{
+    load_current_index_checked, load_instruction_at_checked,
+};
+use anchor_lang::Discriminator;
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+
+#[derive(Accounts)]
+pub struct FlashLoanBegin<'info> {
+    // Config PDA
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    pub owner: Signer<'info>,
+
+    // Pool account, re-derived from its mint pair
+    #[account(mut, seeds = [b"pool", pool.token0.as_ref(), pool.token1.as_ref(), pool.fee_tier.as_ref()], bump = pool.bump)]
+    pub pool: Box<Account<'info, Pool>>,
+
+    // Pool authority PDA
+    /// CHECK: authority so 1 acc pass in can derive all other PDAs
+    #[account(seeds = [b"authority", pool.key().as_ref()], bump)]
+    pub pool_authority: AccountInfo<'info>,
+
+    // Vault lent from, must be one of the pool's vaults
+    #[account(mut, constraint = pool.is_vault(&vault.key()) @ ErrorCode::InvalidVault)]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // Mint of the vault, owned by its token program
+    #[account(address = vault.mint, mint::token_program = token_program)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+
+    // Token account receiving the loan, any owner
+    #[account(mut, constraint = recipient.mint == vault.mint)]
+    pub recipient: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: instructions sysvar, checked by address
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct FlashLoanEnd<'info> {
+    // Pool account, re-derived from its mint pair
+    #[account(mut, seeds = [b"pool", pool.token0.as_ref(), pool.token1.as_ref(), pool.fee_tier.as_ref()], bump = pool.bump)]
+    pub pool: Box<Account<'info, Pool>>,
+
+    // Price oracle of the pool
+    #[account(mut, seeds = [b"observations", pool.key().as_ref()], bump)]
+    pub observations: AccountLoader<'info, Observations>,
+
+    // Vaults bound to the pool
+    #[account(address = pool.vault0 @ ErrorCode::InvalidVault)]
+    pub vault0: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(address = pool.vault1 @ ErrorCode::InvalidVault)]
+    pub vault1: Box<InterfaceAccount<'info, TokenAccount>>,
+}
+
+#[event]
+pub struct FlashLoanBegun {
+    pub owner: Pubkey,
+    pub pool: Pubkey,
+    pub vault: Pubkey,
+    pub amount: u64,
+    pub fee: u64,
+}
+
+#[event]
+pub struct FlashLoanEnded {
+    pub pool: Pubkey,
+    pub vault: Pubkey,
+    pub reserve0: u64,
+    pub reserve1: u64,
+}
+
+/// Lends `amount` out of one vault, a later `flash_loan_end` in the transaction must find it repaid with the fee.
+pub fn flash_loan_begin(ctx: Context<FlashLoanBegin>, amount: u64) -> Result<()> {
+    let accounts = ctx.accounts;
+    let pool_key = accounts.pool.key();
+
+    // Ensure trading is not paused
+    accounts.pool.require_not_paused(&accounts.config)?;
+
+    // Ensure a valid amount, the pool must keep some of its reserve
+    let (reserve, _) = accounts.pool.swap_reserves(&accounts.vault.key());
+    require!(amount > 0, ErrorCode::InsufficientOutputAmount);
+    require!(amount < reserve, ErrorCode::InsufficientLiquidity);
+
+    // Ensure the loan is closed later in the same transaction
+    let current_index = load_current_index_checked(&accounts.instructions)? as usize;
+    let mut index = current_index + 1;
+    let mut ended = false;
+    while let Ok(instruction) = load_instruction_at_checked(index, &accounts.instructions) {
+        if instruction.program_id == crate::ID
+            && instruction
+                .data
+                .starts_with(&crate::instruction::FlashLoanEnd::DISCRIMINATOR)
+            && instruction.accounts.first().map(|meta| meta.pubkey) == Some(pool_key)
+        {
+            ended = true;
+            break;
+        }
+        index += 1;
+    }
+    require!(ended, ErrorCode::FlashLoanEndMissing);
+
+    // Lock the pool against re-entry through the token transfer, before anything else changes
+    lock_pool(&mut accounts.pool)?;
+
+    // The vault must hold its current balance plus the fee, rounded up, when the loan ends
+    let fee = (amount as u128 * accounts.config.flash_loan_fee as u128)
+        .div_ceil(BASIS_POINTS as u128) as u64;
+    let repayment = accounts
+        .vault
+        .amount
+        .checked_add(fee)
+        .ok_or(ErrorCode::MathOverflow)?;
+    accounts
+        .pool
+        .begin_flash_loan(accounts.vault.key(), repayment, fee)?;
+
+    let pool_sign = &[b"authority", pool_key.as_ref(), &[ctx.bumps.pool_authority]];
+    transfer_checked(
+        CpiContext::new(
+            accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: accounts.vault.to_account_info(),
+                mint: accounts.mint.to_account_info(),
+                to: accounts.recipient.to_account_info(),
+                authority: accounts.pool_authority.to_account_info(),
+            },
+        )
+        .with_signer(&[pool_sign]),
+        amount,
+        accounts.mint.decimals,
+    )?;
+    accounts.pool.unlock();
+
+    emit!(FlashLoanBegun {
+        owner: accounts.owner.key(),
+        pool: pool_key,
+        vault: accounts.vault.key(),
+        amount,
+        fee,
+    });
+
+    Ok(())
+}
+
+/// Closes the open flash loan, ensuring it was repaid, and credits the fee to the reserves.
+pub fn flash_loan_end(ctx: Context<FlashLoanEnd>) -> Result<()> {
+    let accounts = ctx.accounts;
+    accounts.pool.lock()?;
+    let (vault, repayment, fee) = accounts.pool.end_flash_loan()?;
+
+    let balance = if vault == accounts.vault0.key() {
+        accounts.vault0.amount
+    } else {
+        accounts.vault1.amount
+    };
+    require!(balance >= repayment, ErrorCode::FlashLoanNotRepaid);
+
+    // Credit the fee to the lent reserve for the LPs, anything paid above it is left for skim
+    let (mut reserve0, mut reserve1) = (accounts.pool.reserve0, accounts.pool.reserve1);
+    let reserve = if vault == accounts.vault0.key() {
+        &mut reserve0
+    } else {
+        &mut reserve1
+    };
+    *reserve = reserve.checked_add(fee).ok_or(ErrorCode::MathOverflow)?;
+    accounts
+        .pool
+        .update_reserves(reserve0, reserve1, &mut *accounts.observations.load_mut()?)?;
+    accounts.pool.unlock();
+
+    emit!(FlashLoanEnded {
+        pool: accounts.pool.key(),
+        vault,
+        reserve0,
+        reserve1,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,254 @@
+use crate::constant::BASIS_POINTS;
+use crate::error::ErrorCode;
+use crate::state::Config;
+use crate::state::Observations;
+use crate::state::Pool;
+use crate::utils::lock_pool;
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke;
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+
+#[derive(Accounts)]
+pub struct FlashSwap<'info> {
+    // Config PDA
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    // Borrower, passed on to the callback as a signer
+    pub owner: Signer<'info>,
+
+    // Pool account, re-derived from its mint pair
+    #[account(mut, seeds = [b"pool", pool.token0.as_ref(), pool.token1.as_ref(), pool.fee_tier.as_ref()], bump = pool.bump)]
+    pub pool: Box<Account<'info, Pool>>,
+
+    // Pool authority PDA
+    /// CHECK: authority so 1 acc pass in can derive all other PDAs
+    #[account(seeds = [b"authority", pool.key().as_ref()], bump)]
+    pub pool_authority: AccountInfo<'info>,
+
+    // Price oracle of the pool
+    #[account(mut, seeds = [b"observations", pool.key().as_ref()], bump)]
+    pub observations: AccountLoader<'info, Observations>,
+
+    // Vaults bound to the pool
+    #[account(mut, address = pool.vault0 @ ErrorCode::InvalidVault)]
+    pub vault0: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, address = pool.vault1 @ ErrorCode::InvalidVault)]
+    pub vault1: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // Token accounts receiving the borrowed amounts, any owner
+    #[account(mut, constraint = recipient0.mint == pool.token0)]
+    pub recipient0: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, constraint = recipient1.mint == pool.token1)]
+    pub recipient1: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // Mints of the pool, owned by their token programs
+    #[account(address = pool.token0, mint::token_program = token_program0)]
+    pub mint0: Box<InterfaceAccount<'info, Mint>>,
+    #[account(address = pool.token1, mint::token_program = token_program1)]
+    pub mint1: Box<InterfaceAccount<'info, Mint>>,
+
+    // Borrower's program, called with a FlashSwapCallback once the output is sent
+    /// CHECK: any program implementing the flash swap callback
+    #[account(executable, constraint = callback_program.key() != crate::ID @ ErrorCode::InvalidCallbackProgram)]
+    pub callback_program: AccountInfo<'info>,
+
+    pub token_program0: Interface<'info, TokenInterface>,
+    pub token_program1: Interface<'info, TokenInterface>,
+    // The remaining accounts are passed on to the callback after the owner and the pool
+}
+
+/// Instruction data of the callback, prefixed with the discriminator of `flash_swap_callback`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct FlashSwapCallback {
+    pub sender: Pubkey,
+    pub amount0: u64,
+    pub amount1: u64,
+    pub data: Vec<u8>,
+}
+
+#[event]
+pub struct FlashSwapEvent {
+    pub owner: Pubkey,
+    pub pool: Pubkey,
+    pub amount0_in: u64,
+    pub amount1_in: u64,
+    pub amount0_out: u64,
+    pub amount1_out: u64,
+    pub callback_program: Pubkey,
+}
+
+impl<'info> FlashSwap<'info> {
+    // Sends `amount` out of a vault, signed by the pool authority
+    fn transfer_out(
+        &self,
+        vault: &InterfaceAccount<'info, TokenAccount>,
+        mint: &InterfaceAccount<'info, Mint>,
+        recipient: &InterfaceAccount<'info, TokenAccount>,
+        token_program: &Interface<'info, TokenInterface>,
+        amount: u64,
+        pool_authority_bump: u8,
+    ) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+
+        let pool_key = self.pool.key();
+        let pool_sign = &[b"authority", pool_key.as_ref(), &[pool_authority_bump]];
+        transfer_checked(
+            CpiContext::new(
+                token_program.to_account_info(),
+                TransferChecked {
+                    from: vault.to_account_info(),
+                    mint: mint.to_account_info(),
+                    to: recipient.to_account_info(),
+                    authority: self.pool_authority.to_account_info(),
+                },
+            )
+            .with_signer(&[pool_sign]),
+            amount,
+            mint.decimals,
+        )
+    }
+
+    // Calls the borrower's program, which must pay the pool back before returning
+    fn callback(
+        &self,
+        remaining_accounts: &[AccountInfo<'info>],
+        amount0: u64,
+        amount1: u64,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let mut instruction_data = hash(b"global:flash_swap_callback").to_bytes()[..8].to_vec();
+        FlashSwapCallback {
+            sender: self.owner.key(),
+            amount0,
+            amount1,
+            data,
+        }
+        .serialize(&mut instruction_data)?;
+
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.owner.key(), true),
+            AccountMeta::new_readonly(self.pool.key(), false),
+        ];
+        let mut account_infos = vec![self.owner.to_account_info(), self.pool.to_account_info()];
+        for account in remaining_accounts {
+            accounts.push(match account.is_writable {
+                true => AccountMeta::new(account.key(), account.is_signer),
+                false => AccountMeta::new_readonly(account.key(), account.is_signer),
+            });
+            account_infos.push(account.clone());
+        }
+        account_infos.push(self.callback_program.clone());
+
+        invoke(
+            &Instruction {
+                program_id: self.callback_program.key(),
+                accounts,
+                data: instruction_data,
+            },
+            &account_infos,
+        )?;
+        Ok(())
+    }
+}
+
+pub fn flash_swap<'info>(
+    ctx: Context<'_, '_, '_, 'info, FlashSwap<'info>>,
+    amount0_out: u64,
+    amount1_out: u64,
+    data: Vec<u8>,
+) -> Result<()> {
+    let accounts = ctx.accounts;
+    let pool_authority_bump = ctx.bumps.pool_authority;
+
+    // Ensure trading is not paused and no flash loan is open
+    accounts.pool.require_not_paused(&accounts.config)?;
+    accounts.pool.require_no_flash_loan()?;
+
+    // Refresh the oracle price of an oracle-pegged pool if its price account was passed
+    accounts.pool.load_oracle_price(ctx.remaining_accounts)?;
+
+    // Ensure valid output amounts
+    require!(
+        amount0_out > 0 || amount1_out > 0,
+        ErrorCode::InsufficientOutputAmount,
+    );
+    let (reserve0, reserve1) = (accounts.pool.reserve0, accounts.pool.reserve1);
+    require!(
+        amount0_out < reserve0 && amount1_out < reserve1,
+        ErrorCode::InsufficientLiquidity,
+    );
+
+    // Lock the pool, the callback must not trade against it before paying back
+    lock_pool(&mut accounts.pool)?;
+
+    // Optimistically send the output before anything is paid in
+    accounts.transfer_out(
+        &accounts.vault0,
+        &accounts.mint0,
+        &accounts.recipient0,
+        &accounts.token_program0,
+        amount0_out,
+        pool_authority_bump,
+    )?;
+    accounts.transfer_out(
+        &accounts.vault1,
+        &accounts.mint1,
+        &accounts.recipient1,
+        &accounts.token_program1,
+        amount1_out,
+        pool_authority_bump,
+    )?;
+
+    accounts.callback(ctx.remaining_accounts, amount0_out, amount1_out, data)?;
+
+    // Anything the vaults hold above the reserves left after the output was paid in
+    accounts.vault0.reload()?;
+    accounts.vault1.reload()?;
+    let (balance0, balance1) = (accounts.vault0.amount, accounts.vault1.amount);
+    let amount0_in = balance0.saturating_sub(reserve0 - amount0_out);
+    let amount1_in = balance1.saturating_sub(reserve1 - amount1_out);
+    require!(
+        amount0_in > 0 || amount1_in > 0,
+        ErrorCode::InsufficientInputAmount,
+    );
+
+    // The invariant of the balances, net of the swap fee on what was paid in, must not decrease
+    let fee = accounts.pool.fee as u128;
+    let balance0_adjusted = balance0 as u128 * BASIS_POINTS as u128 - amount0_in as u128 * fee;
+    let balance1_adjusted = balance1 as u128 * BASIS_POINTS as u128 - amount1_in as u128 * fee;
+    let (reserve0_scaled, reserve1_scaled) = (
+        reserve0 as u128 * BASIS_POINTS as u128,
+        reserve1 as u128 * BASIS_POINTS as u128,
+    );
+    accounts.pool.swap_curve()?.require_invariant(
+        reserve0_scaled,
+        reserve1_scaled,
+        balance0_adjusted,
+        balance1_adjusted,
+    )?;
+
+    // Update pool reserves
+    accounts
+        .pool
+        .update_reserves(balance0, balance1, &mut *accounts.observations.load_mut()?)?;
+    accounts.pool.unlock();
+
+    emit!(FlashSwapEvent {
+        owner: accounts.owner.key(),
+        pool: accounts.pool.key(),
+        amount0_in,
+        amount1_in,
+        amount0_out,
+        amount1_out,
+        callback_program: accounts.callback_program.key(),
+    });
+
+    Ok(())
+}
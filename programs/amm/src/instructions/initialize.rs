@@ -22,20 +22,18 @@ pub struct Initialize<'info> {
 pub struct ConfigInitialized {
     pub owner: Pubkey,
     pub fee_to: Pubkey,
-    pub fee: u64,
 }
 
-pub fn initialize(ctx: Context<Initialize>, fee_to: Pubkey, fee: u64) -> Result<()> {
+pub fn initialize(ctx: Context<Initialize>, fee_to: Pubkey) -> Result<()> {
     let config = &mut ctx.accounts.config;
 
     // Initialize the config with the given parameters.
-    config.initialize(ctx.bumps.config, *ctx.accounts.owner.key, fee_to, fee)?;
+    config.initialize(ctx.bumps.config, *ctx.accounts.owner.key, fee_to)?;
 
     // Emit an event to log the initialization.
     emit!(ConfigInitialized {
         owner: *ctx.accounts.owner.key,
         fee_to,
-        fee,
     });
 
     Ok(())
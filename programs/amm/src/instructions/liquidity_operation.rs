@@ -1,14 +1,16 @@
+use crate::constant::MINIMUM_LIQUIDITY;
 use crate::error::ErrorCode;
 use crate::instructions::mint_fee;
 use crate::state::Config;
+use crate::state::Observations;
 use crate::state::Pool;
+use crate::utils::{get_transfer_fee, lock_pool, require_not_expired, unwrap_sol, wrap_sol};
 use anchor_lang::prelude::*;
 use anchor_spl::associated_token::AssociatedToken;
-use anchor_spl::token::{
-    burn, mint_to, transfer, Burn, Mint, MintTo, Token, TokenAccount, Transfer,
+use anchor_spl::token::{self, burn, mint_to, spl_token::native_mint, Burn, MintTo, Token};
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
 };
-use fixed::types::U128F0;
-use std::cmp::min;
 
 #[derive(Accounts)]
 pub struct LiquidityOperation<'info> {
@@ -19,38 +21,240 @@ pub struct LiquidityOperation<'info> {
     pub owner: Signer<'info>,
 
     #[account(mut, has_one = owner,)]
-    pub user_ata0: Box<Account<'info, TokenAccount>>,
+    pub user_ata0: Box<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(mut, has_one = owner,)]
-    pub user_ata1: Box<Account<'info, TokenAccount>>,
+    pub user_ata1: Box<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(init_if_needed, payer = owner, associated_token::mint = lp_mint, associated_token::authority = owner)]
-    pub user_lp_ata: Box<Account<'info, TokenAccount>>,
+    pub user_lp_ata: Box<Account<'info, token::TokenAccount>>,
+
+    #[account(mut, seeds = [b"pool", pool.token0.as_ref(), pool.token1.as_ref(), pool.fee_tier.as_ref()], bump = pool.bump)]
+    pub pool: Box<Account<'info, Pool>>,
+
+    /// CHECK: authority so one account can derive all other PDAs
+    #[account(seeds = [b"authority", pool.key().as_ref()], bump)]
+    pub pool_authority: AccountInfo<'info>,
+
+    #[account(mut, seeds = [b"observations", pool.key().as_ref()], bump)]
+    pub observations: AccountLoader<'info, Observations>,
+
+    #[account(mut, associated_token::mint = lp_mint, associated_token::authority = pool_authority)]
+    pub vault_lp: Box<Account<'info, token::TokenAccount>>,
+
+    /// CHECK: protocol fee recipient, checked against the config
+    #[account(address = config.fee_to)]
+    pub fee_to: AccountInfo<'info>,
+
+    #[account(init_if_needed, payer = owner, associated_token::mint = lp_mint, associated_token::authority = fee_to)]
+    pub fee_to_lp_ata: Box<Account<'info, token::TokenAccount>>,
+
+    #[account(mut, address = pool.vault0 @ ErrorCode::InvalidVault, constraint = vault0.mint == user_ata0.mint,)]
+    pub vault0: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(address = pool.token0, mint::token_program = token_program0)]
+    pub mint0: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut, address = pool.vault1 @ ErrorCode::InvalidVault, constraint = vault1.mint == user_ata1.mint,)]
+    pub vault1: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(address = pool.token1, mint::token_program = token_program1)]
+    pub mint1: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut, seeds = [b"lp_mint", pool.key().as_ref()], bump)]
+    pub lp_mint: Box<Account<'info, token::Mint>>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Program<'info, Token>,
+    pub token_program0: Interface<'info, TokenInterface>,
+    pub token_program1: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct LiquidityOperationSol<'info> {
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
 
     #[account(mut)]
+    pub owner: Signer<'info>,
+
+    // User's token account for the pool's other token
+    #[account(mut, has_one = owner,)]
+    pub user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: temporary wSOL account, created and closed within the instruction
+    #[account(mut, seeds = [b"wsol", owner.key().as_ref()], bump)]
+    pub user_wsol: AccountInfo<'info>,
+
+    #[account(init_if_needed, payer = owner, associated_token::mint = lp_mint, associated_token::authority = owner)]
+    pub user_lp_ata: Box<Account<'info, token::TokenAccount>>,
+
+    #[account(mut, seeds = [b"pool", pool.token0.as_ref(), pool.token1.as_ref(), pool.fee_tier.as_ref()], bump = pool.bump)]
     pub pool: Box<Account<'info, Pool>>,
 
     /// CHECK: authority so one account can derive all other PDAs
     #[account(seeds = [b"authority", pool.key().as_ref()], bump)]
     pub pool_authority: AccountInfo<'info>,
 
-    #[account(init_if_needed, payer = owner, associated_token::mint = lp_mint, associated_token::authority = pool_authority)]
-    pub vault_lp: Box<Account<'info, TokenAccount>>,
+    #[account(mut, seeds = [b"observations", pool.key().as_ref()], bump)]
+    pub observations: AccountLoader<'info, Observations>,
 
-    #[account(mut, constraint = vault0.mint == user_ata0.mint,)]
-    pub vault0: Box<Account<'info, TokenAccount>>,
+    #[account(mut, associated_token::mint = lp_mint, associated_token::authority = pool_authority)]
+    pub vault_lp: Box<Account<'info, token::TokenAccount>>,
 
-    #[account(mut, constraint = vault1.mint == user_ata1.mint,)]
-    pub vault1: Box<Account<'info, TokenAccount>>,
+    /// CHECK: protocol fee recipient, checked against the config
+    #[account(address = config.fee_to)]
+    pub fee_to: AccountInfo<'info>,
+
+    #[account(init_if_needed, payer = owner, associated_token::mint = lp_mint, associated_token::authority = fee_to)]
+    pub fee_to_lp_ata: Box<Account<'info, token::TokenAccount>>,
+
+    #[account(mut, address = pool.vault0 @ ErrorCode::InvalidVault)]
+    pub vault0: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(address = pool.token0, mint::token_program = token_program0)]
+    pub mint0: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut, address = pool.vault1 @ ErrorCode::InvalidVault)]
+    pub vault1: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(address = pool.token1, mint::token_program = token_program1)]
+    pub mint1: Box<InterfaceAccount<'info, Mint>>,
 
     #[account(mut, seeds = [b"lp_mint", pool.key().as_ref()], bump)]
-    pub lp_mint: Box<Account<'info, Mint>>,
+    pub lp_mint: Box<Account<'info, token::Mint>>,
 
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Program<'info, Token>,
+    pub token_program0: Interface<'info, TokenInterface>,
+    pub token_program1: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
+// Accounts liquidity moves through, shared by the token and native SOL instructions
+pub struct LiquidityAccounts<'a, 'info> {
+    pub config: &'a Config,
+    pub owner: AccountInfo<'info>,
+    pub user_ata0: AccountInfo<'info>,
+    pub user_ata1: AccountInfo<'info>,
+    pub user_lp_ata: AccountInfo<'info>,
+    pub pool: &'a mut Account<'info, Pool>,
+    pub pool_authority: AccountInfo<'info>,
+    pub pool_authority_bump: u8,
+    pub observations: &'a AccountLoader<'info, Observations>,
+    pub vault_lp: AccountInfo<'info>,
+    pub fee_to_lp_ata: AccountInfo<'info>,
+    pub vault0: &'a mut InterfaceAccount<'info, TokenAccount>,
+    pub vault1: &'a mut InterfaceAccount<'info, TokenAccount>,
+    pub mint0: &'a InterfaceAccount<'info, Mint>,
+    pub mint1: &'a InterfaceAccount<'info, Mint>,
+    pub lp_mint: &'a mut Account<'info, token::Mint>,
+    pub token_program: AccountInfo<'info>,
+    pub token_program0: AccountInfo<'info>,
+    pub token_program1: AccountInfo<'info>,
+}
+
+impl<'info> LiquidityOperation<'info> {
+    fn liquidity_accounts(&mut self, pool_authority_bump: u8) -> LiquidityAccounts<'_, 'info> {
+        LiquidityAccounts {
+            config: &self.config,
+            owner: self.owner.to_account_info(),
+            user_ata0: self.user_ata0.to_account_info(),
+            user_ata1: self.user_ata1.to_account_info(),
+            user_lp_ata: self.user_lp_ata.to_account_info(),
+            pool: &mut self.pool,
+            pool_authority: self.pool_authority.to_account_info(),
+            pool_authority_bump,
+            observations: &self.observations,
+            vault_lp: self.vault_lp.to_account_info(),
+            fee_to_lp_ata: self.fee_to_lp_ata.to_account_info(),
+            vault0: &mut self.vault0,
+            vault1: &mut self.vault1,
+            mint0: &self.mint0,
+            mint1: &self.mint1,
+            lp_mint: &mut self.lp_mint,
+            token_program: self.token_program.to_account_info(),
+            token_program0: self.token_program0.to_account_info(),
+            token_program1: self.token_program1.to_account_info(),
+        }
+    }
+}
+
+impl<'info> LiquidityOperationSol<'info> {
+    // Which side of the pool is SOL, the other side must be the user's token
+    fn sol_side(&self) -> Result<u8> {
+        let (side, native, other) = if self.mint0.key() == native_mint::ID {
+            (0, &self.mint0, &self.mint1)
+        } else {
+            (1, &self.mint1, &self.mint0)
+        };
+        require!(
+            native.key() == native_mint::ID && other.key() == self.user_ata.mint,
+            ErrorCode::InvalidNativeMint
+        );
+
+        Ok(side)
+    }
+
+    fn wrap_sol(&self, amount: u64, wsol_bump: u8) -> Result<()> {
+        let native = if self.mint0.key() == native_mint::ID {
+            &self.mint0
+        } else {
+            &self.mint1
+        };
+
+        wrap_sol(
+            &self.owner.to_account_info(),
+            &self.user_wsol,
+            wsol_bump,
+            &native.to_account_info(),
+            amount,
+            &self.system_program.to_account_info(),
+            &self.token_program.to_account_info(),
+        )
+    }
+
+    fn unwrap_sol(&self) -> Result<()> {
+        unwrap_sol(
+            &self.owner.to_account_info(),
+            &self.user_wsol,
+            &self.token_program.to_account_info(),
+        )
+    }
+
+    fn liquidity_accounts(&mut self, pool_authority_bump: u8) -> LiquidityAccounts<'_, 'info> {
+        let user_ata = self.user_ata.to_account_info();
+        let (user_ata0, user_ata1) = if self.mint0.key() == native_mint::ID {
+            (self.user_wsol.clone(), user_ata)
+        } else {
+            (user_ata, self.user_wsol.clone())
+        };
+
+        LiquidityAccounts {
+            config: &self.config,
+            owner: self.owner.to_account_info(),
+            user_ata0,
+            user_ata1,
+            user_lp_ata: self.user_lp_ata.to_account_info(),
+            pool: &mut self.pool,
+            pool_authority: self.pool_authority.to_account_info(),
+            pool_authority_bump,
+            observations: &self.observations,
+            vault_lp: self.vault_lp.to_account_info(),
+            fee_to_lp_ata: self.fee_to_lp_ata.to_account_info(),
+            vault0: &mut self.vault0,
+            vault1: &mut self.vault1,
+            mint0: &self.mint0,
+            mint1: &self.mint1,
+            lp_mint: &mut self.lp_mint,
+            token_program: self.token_program.to_account_info(),
+            token_program0: self.token_program0.to_account_info(),
+            token_program1: self.token_program1.to_account_info(),
+        }
+    }
+}
+
 #[event]
 pub struct LiquidityAdded {
     pub user: Pubkey,
@@ -74,11 +278,118 @@ pub fn add_liquidity(
     amount0_min: u64,
     amount1_min: u64,
 ) -> Result<()> {
-    let pool: &Box<Account<Pool>> = &ctx.accounts.pool;
-    let (reserve0, reserve1) = (ctx.accounts.vault0.amount, ctx.accounts.vault1.amount);
+    let mut accounts = ctx.accounts.liquidity_accounts(ctx.bumps.pool_authority);
+    deposit(
+        &mut accounts,
+        amount0_desired,
+        amount1_desired,
+        amount0_min,
+        amount1_min,
+    )
+}
+
+pub fn remove_liquidity(
+    ctx: Context<LiquidityOperation>,
+    liquidity: u64,
+    amount0_min: u64,
+    amount1_min: u64,
+) -> Result<()> {
+    let mut accounts = ctx.accounts.liquidity_accounts(ctx.bumps.pool_authority);
+    withdraw(&mut accounts, liquidity, amount0_min, amount1_min)
+}
+
+pub fn add_liquidity_with_deadline(
+    ctx: Context<LiquidityOperation>,
+    amount0_desired: u64,
+    amount1_desired: u64,
+    amount0_min: u64,
+    amount1_min: u64,
+    deadline: i64,
+) -> Result<()> {
+    require_not_expired(deadline)?;
+    add_liquidity(
+        ctx,
+        amount0_desired,
+        amount1_desired,
+        amount0_min,
+        amount1_min,
+    )
+}
+
+pub fn remove_liquidity_with_deadline(
+    ctx: Context<LiquidityOperation>,
+    liquidity: u64,
+    amount0_min: u64,
+    amount1_min: u64,
+    deadline: i64,
+) -> Result<()> {
+    require_not_expired(deadline)?;
+    remove_liquidity(ctx, liquidity, amount0_min, amount1_min)
+}
+
+pub fn add_liquidity_sol(
+    ctx: Context<LiquidityOperationSol>,
+    amount0_desired: u64,
+    amount1_desired: u64,
+    amount0_min: u64,
+    amount1_min: u64,
+) -> Result<()> {
+    // Wrap the desired SOL amount, whatever the deposit does not use is returned on unwrap
+    let accounts = ctx.accounts;
+    let wrapped = match accounts.sol_side()? {
+        0 => amount0_desired,
+        _ => amount1_desired,
+    };
+    accounts.wrap_sol(wrapped, ctx.bumps.user_wsol)?;
+
+    let mut liquidity_accounts = accounts.liquidity_accounts(ctx.bumps.pool_authority);
+    deposit(
+        &mut liquidity_accounts,
+        amount0_desired,
+        amount1_desired,
+        amount0_min,
+        amount1_min,
+    )?;
+
+    accounts.unwrap_sol()
+}
+
+pub fn remove_liquidity_sol(
+    ctx: Context<LiquidityOperationSol>,
+    liquidity: u64,
+    amount0_min: u64,
+    amount1_min: u64,
+) -> Result<()> {
+    // Withdraw into an empty wSOL account, then unwrap it back to lamports
+    let accounts = ctx.accounts;
+    accounts.sol_side()?;
+    accounts.wrap_sol(0, ctx.bumps.user_wsol)?;
+
+    let mut liquidity_accounts = accounts.liquidity_accounts(ctx.bumps.pool_authority);
+    withdraw(&mut liquidity_accounts, liquidity, amount0_min, amount1_min)?;
+
+    accounts.unwrap_sol()
+}
+
+fn deposit(
+    accounts: &mut LiquidityAccounts,
+    amount0_desired: u64,
+    amount1_desired: u64,
+    amount0_min: u64,
+    amount1_min: u64,
+) -> Result<()> {
+    // Ensure deposits are not paused, withdrawals stay open so LPs can always exit
+    accounts.pool.require_not_paused(accounts.config)?;
+    accounts.pool.require_no_flash_loan()?;
+
+    // Lock the pool against re-entry through the token transfers
+    lock_pool(accounts.pool)?;
+
+    let (reserve0, reserve1) = (accounts.pool.reserve0, accounts.pool.reserve1);
+    let curve = accounts.pool.curve()?;
 
     // Calculate the optimal amounts of tokens to add
-    let (amount0, amount1) = calculate_liquidity_amounts(
+    let (amount0, amount1) = curve.deposit_amounts(
         reserve0,
         reserve1,
         amount0_desired,
@@ -87,91 +398,127 @@ pub fn add_liquidity(
         amount1_min,
     )?;
 
-    // Derive the pool authority signature
-    let pool_key = pool.key();
-    let pool_sign = &[b"authority", pool_key.as_ref(), &[ctx.bumps.pool_authority]];
+    // Ensure the amounts received by the vaults, net of transfer fees, meet the minimums
+    require!(
+        amount0 - get_transfer_fee(accounts.mint0, amount0)? >= amount0_min
+            && amount1 - get_transfer_fee(accounts.mint1, amount1)? >= amount1_min,
+        ErrorCode::InsufficientAmount
+    );
+
+    // Transfer the user's tokens to the vaults, crediting what the vaults actually received
+    let (vault0_before, vault1_before) = (accounts.vault0.amount, accounts.vault1.amount);
 
-    // Mint fee tokens to the vault
+    transfer_checked(
+        CpiContext::new(
+            accounts.token_program0.to_account_info(),
+            TransferChecked {
+                from: accounts.user_ata0.to_account_info(),
+                mint: accounts.mint0.to_account_info(),
+                to: accounts.vault0.to_account_info(),
+                authority: accounts.owner.to_account_info(),
+            },
+        ),
+        amount0,
+        accounts.mint0.decimals,
+    )?;
+
+    transfer_checked(
+        CpiContext::new(
+            accounts.token_program1.to_account_info(),
+            TransferChecked {
+                from: accounts.user_ata1.to_account_info(),
+                mint: accounts.mint1.to_account_info(),
+                to: accounts.vault1.to_account_info(),
+                authority: accounts.owner.to_account_info(),
+            },
+        ),
+        amount1,
+        accounts.mint1.decimals,
+    )?;
+
+    accounts.vault0.reload()?;
+    accounts.vault1.reload()?;
+    let amount0 = accounts.vault0.amount - vault0_before;
+    let amount1 = accounts.vault1.amount - vault1_before;
+
+    // Derive the pool authority signature
+    let pool_key = accounts.pool.key();
+    let pool_sign = &[
+        b"authority",
+        pool_key.as_ref(),
+        &[accounts.pool_authority_bump],
+    ];
+
+    // Mint protocol fee tokens to the fee recipient
     let mint_fee_ctx = CpiContext::new(
-        ctx.accounts.token_program.to_account_info(),
+        accounts.token_program.to_account_info(),
         MintTo {
-            to: ctx.accounts.vault_lp.to_account_info(),
-            mint: ctx.accounts.lp_mint.to_account_info(),
-            authority: ctx.accounts.pool_authority.to_account_info(),
+            to: accounts.fee_to_lp_ata.to_account_info(),
+            mint: accounts.lp_mint.to_account_info(),
+            authority: accounts.pool_authority.to_account_info(),
         },
     );
-    mint_fee(
-        &ctx.accounts.config,
-        &ctx.accounts.pool,
+    let fee_on = mint_fee(
+        accounts.config,
+        accounts.pool,
         reserve0,
         reserve1,
-        ctx.accounts.lp_mint.supply,
+        accounts.lp_mint.supply,
         mint_fee_ctx.with_signer(&[pool_sign]),
     )?;
+    accounts.lp_mint.reload()?;
 
     // Calculate the amount of liquidity to mint
-    let lp_mint = &ctx.accounts.lp_mint;
-    let liquidity: u64;
-    if lp_mint.supply == 0 {
-        liquidity = U128F0::from_num((amount0 as u128) * (amount1 as u128))
-            .sqrt()
-            .to_num::<u64>();
-    } else {
-        liquidity = min(
-            amount0 as u128 * lp_mint.supply as u128 / reserve0 as u128,
-            amount1 as u128 * lp_mint.supply as u128 / reserve1 as u128,
-        ) as u64
+    let lp_supply = accounts.lp_mint.supply;
+    let mut liquidity = curve.deposit(amount0, amount1, reserve0, reserve1, lp_supply)?;
+    if lp_supply == 0 {
+        liquidity = liquidity
+            .checked_sub(MINIMUM_LIQUIDITY)
+            .ok_or(ErrorCode::InsufficientLiquidityMinted)?;
+
+        // Permanently lock the minimum liquidity in the pool's LP vault
+        let lock_ctx = CpiContext::new(
+            accounts.token_program.to_account_info(),
+            MintTo {
+                to: accounts.vault_lp.to_account_info(),
+                mint: accounts.lp_mint.to_account_info(),
+                authority: accounts.pool_authority.to_account_info(),
+            },
+        );
+        mint_to(lock_ctx.with_signer(&[pool_sign]), MINIMUM_LIQUIDITY)?;
     }
 
     require!(liquidity > 0, ErrorCode::InsufficientLiquidityMinted);
 
     // Mint liquidity tokens to the user
     let mint_ctx = CpiContext::new(
-        ctx.accounts.token_program.to_account_info(),
+        accounts.token_program.to_account_info(),
         MintTo {
-            to: ctx.accounts.user_lp_ata.to_account_info(),
-            mint: ctx.accounts.lp_mint.to_account_info(),
-            authority: ctx.accounts.pool_authority.to_account_info(),
+            to: accounts.user_lp_ata.to_account_info(),
+            mint: accounts.lp_mint.to_account_info(),
+            authority: accounts.pool_authority.to_account_info(),
         },
     );
     mint_to(mint_ctx.with_signer(&[pool_sign]), liquidity)?;
 
-    // Transfer the user's tokens to the vault
-    transfer(
-        CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.user_ata0.to_account_info(),
-                to: ctx.accounts.vault0.to_account_info(),
-                authority: ctx.accounts.owner.to_account_info(),
-            },
-        ),
-        amount0,
-    )?;
-
-    transfer(
-        CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.user_ata1.to_account_info(),
-                to: ctx.accounts.vault1.to_account_info(),
-                authority: ctx.accounts.owner.to_account_info(),
-            },
-        ),
-        amount1,
-    )?;
-
     // Update pool reserves
-    let pool: &mut Box<Account<Pool>> = &mut ctx.accounts.pool;
-    ctx.accounts.vault0.reload()?;
-    ctx.accounts.vault1.reload()?;
-    let (reserve0, reserve1) = (ctx.accounts.vault0.amount, ctx.accounts.vault1.amount);
-
-    pool.update_k_last(reserve0, reserve1);
+    let pool = &mut accounts.pool;
+    let reserve0 = reserve0
+        .checked_add(amount0)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let reserve1 = reserve1
+        .checked_add(amount1)
+        .ok_or(ErrorCode::MathOverflow)?;
+    pool.update_reserves(reserve0, reserve1, &mut *accounts.observations.load_mut()?)?;
+
+    if fee_on {
+        pool.update_k_last(reserve0, reserve1)?;
+    }
+    pool.unlock();
 
     // Emit event
     emit!(LiquidityAdded {
-        user: ctx.accounts.owner.key(),
+        user: accounts.owner.key(),
         amount0,
         amount1,
         liquidity,
@@ -180,145 +527,120 @@ pub fn add_liquidity(
     Ok(())
 }
 
-// Calculate the optimal amounts of tokens to add based on the reserves
-fn calculate_liquidity_amounts(
-    reserve0: u64,
-    reserve1: u64,
-    amount0_desired: u64,
-    amount1_desired: u64,
-    amount0_min: u64,
-    amount1_min: u64,
-) -> Result<(u64, u64)> {
-    let amount0: u64;
-    let amount1: u64;
-    if reserve0 == 0 && reserve1 == 0 {
-        (amount0, amount1) = (amount0_desired, amount1_desired);
-    } else {
-        let amount1_optimal = quote(amount0_desired, reserve0, reserve1)?;
-        if amount1_optimal <= amount1_desired {
-            require!(
-                amount1_optimal >= amount1_min,
-                ErrorCode::InsufficientAmount
-            );
-            (amount0, amount1) = (amount0_desired, amount1_optimal);
-        } else {
-            let amount0_optimal = quote(amount1_desired, reserve1, reserve0)?;
-            require!(
-                amount0_optimal <= amount0_desired,
-                ErrorCode::InsufficientAmount
-            );
-            require!(
-                amount0_optimal >= amount0_min,
-                ErrorCode::InsufficientAmount
-            );
-            (amount0, amount1) = (amount0_optimal, amount1_desired);
-        }
-    }
-
-    Ok((amount0, amount1))
-}
-
-// Given an amount of an asset and pair reserves, returns an equivalent amount of the other asset
-fn quote(amount0: u64, reserve0: u64, reserve1: u64) -> Result<u64> {
-    require!(amount0 > 0, ErrorCode::InsufficientAmount);
-    require!(
-        reserve0 > 0 && reserve1 > 0,
-        ErrorCode::InsufficientReserves
-    );
-
-    Ok((amount0 as u128 * reserve1 as u128 / reserve0 as u128) as u64)
-}
-
-pub fn remove_liquidity(
-    ctx: Context<LiquidityOperation>,
+fn withdraw(
+    accounts: &mut LiquidityAccounts,
     liquidity: u64,
     amount0_min: u64,
     amount1_min: u64,
 ) -> Result<()> {
-    let pool: &Box<Account<Pool>> = &ctx.accounts.pool;
-    let (reserve0, reserve1) = (ctx.accounts.vault0.amount, ctx.accounts.vault1.amount);
+    accounts.pool.require_no_flash_loan()?;
 
-    // Derive the pool authority signature
-    let pool_key = pool.key();
-    let pool_sign = &[b"authority", pool_key.as_ref(), &[ctx.bumps.pool_authority]];
+    // Lock the pool against re-entry through the token transfers
+    lock_pool(accounts.pool)?;
 
-    // Mint fee tokens to the vault
+    let (reserve0, reserve1) = (accounts.pool.reserve0, accounts.pool.reserve1);
+
+    // Derive the pool authority signature
+    let pool_key = accounts.pool.key();
+    let pool_sign = &[
+        b"authority",
+        pool_key.as_ref(),
+        &[accounts.pool_authority_bump],
+    ];
+
+    // Mint protocol fee tokens to the fee recipient
     let mint_fee_ctx = CpiContext::new(
-        ctx.accounts.token_program.to_account_info(),
+        accounts.token_program.to_account_info(),
         MintTo {
-            to: ctx.accounts.vault_lp.to_account_info(),
-            mint: ctx.accounts.lp_mint.to_account_info(),
-            authority: ctx.accounts.pool_authority.to_account_info(),
+            to: accounts.fee_to_lp_ata.to_account_info(),
+            mint: accounts.lp_mint.to_account_info(),
+            authority: accounts.pool_authority.to_account_info(),
         },
     );
-    mint_fee(
-        &ctx.accounts.config,
-        &ctx.accounts.pool,
+    let fee_on = mint_fee(
+        accounts.config,
+        accounts.pool,
         reserve0,
         reserve1,
-        ctx.accounts.lp_mint.supply,
+        accounts.lp_mint.supply,
         mint_fee_ctx.with_signer(&[pool_sign]),
     )?;
+    accounts.lp_mint.reload()?;
 
     // Calculate the amount of tokens to return to the user
-    let (amount0, amount1) = calculate_removed_amounts(
-        liquidity,
-        ctx.accounts.lp_mint.supply,
-        reserve0,
-        reserve1,
-        amount0_min,
-        amount1_min,
-    )?;
+    let (amount0, amount1) =
+        accounts
+            .pool
+            .curve()?
+            .withdraw(liquidity, reserve0, reserve1, accounts.lp_mint.supply)?;
+
+    // Ensure the amounts received by the user, net of transfer fees, meet the minimums
+    require!(
+        amount0 - get_transfer_fee(accounts.mint0, amount0)? >= amount0_min
+            && amount1 - get_transfer_fee(accounts.mint1, amount1)? >= amount1_min,
+        ErrorCode::InsufficientAmount
+    );
 
     // Burn liquidity tokens
     let burn_ctx = CpiContext::new(
-        ctx.accounts.token_program.to_account_info(),
+        accounts.token_program.to_account_info(),
         Burn {
-            from: ctx.accounts.user_lp_ata.to_account_info(),
-            mint: ctx.accounts.lp_mint.to_account_info(),
-            authority: ctx.accounts.owner.to_account_info(),
+            from: accounts.user_lp_ata.to_account_info(),
+            mint: accounts.lp_mint.to_account_info(),
+            authority: accounts.owner.to_account_info(),
         },
     );
     burn(burn_ctx.with_signer(&[pool_sign]), liquidity)?;
 
     // Transfer tokens from vault to user
-    transfer(
+    transfer_checked(
         CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.vault0.to_account_info(),
-                to: ctx.accounts.user_ata0.to_account_info(),
-                authority: ctx.accounts.pool_authority.to_account_info(),
+            accounts.token_program0.to_account_info(),
+            TransferChecked {
+                from: accounts.vault0.to_account_info(),
+                mint: accounts.mint0.to_account_info(),
+                to: accounts.user_ata0.to_account_info(),
+                authority: accounts.pool_authority.to_account_info(),
             },
         )
         .with_signer(&[pool_sign]),
         amount0,
+        accounts.mint0.decimals,
     )?;
 
-    transfer(
+    transfer_checked(
         CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.vault1.to_account_info(),
-                to: ctx.accounts.user_ata1.to_account_info(),
-                authority: ctx.accounts.pool_authority.to_account_info(),
+            accounts.token_program1.to_account_info(),
+            TransferChecked {
+                from: accounts.vault1.to_account_info(),
+                mint: accounts.mint1.to_account_info(),
+                to: accounts.user_ata1.to_account_info(),
+                authority: accounts.pool_authority.to_account_info(),
             },
         )
         .with_signer(&[pool_sign]),
         amount1,
+        accounts.mint1.decimals,
     )?;
 
     // Update pool reserves
-    let pool: &mut Box<Account<Pool>> = &mut ctx.accounts.pool;
-    ctx.accounts.vault0.reload()?;
-    ctx.accounts.vault1.reload()?;
-    let (reserve0, reserve1) = (ctx.accounts.vault0.amount, ctx.accounts.vault1.amount);
-
-    pool.update_k_last(reserve0, reserve1);
+    let pool = &mut accounts.pool;
+    let reserve0 = reserve0
+        .checked_sub(amount0)
+        .ok_or(ErrorCode::InsufficientLiquidity)?;
+    let reserve1 = reserve1
+        .checked_sub(amount1)
+        .ok_or(ErrorCode::InsufficientLiquidity)?;
+    pool.update_reserves(reserve0, reserve1, &mut *accounts.observations.load_mut()?)?;
+
+    if fee_on {
+        pool.update_k_last(reserve0, reserve1)?;
+    }
+    pool.unlock();
 
     // Emit event
     emit!(LiquidityRemoved {
-        user: ctx.accounts.owner.key(),
+        user: accounts.owner.key(),
         amount0,
         amount1,
         liquidity,
@@ -326,28 +648,3 @@ pub fn remove_liquidity(
 
     Ok(())
 }
-
-// Calculate the amounts of tokens to return when removing liquidity
-fn calculate_removed_amounts(
-    liquidity: u64,
-    lp_supply: u64,
-    reserve0: u64,
-    reserve1: u64,
-    amount0_min: u64,
-    amount1_min: u64,
-) -> Result<(u64, u64)> {
-    let amount0: u64 = (liquidity as u128 * reserve0 as u128 / lp_supply as u128) as u64;
-    let amount1: u64 = (liquidity as u128 * reserve1 as u128 / lp_supply as u128) as u64;
-
-    require!(
-        amount0 >= amount0_min && amount1 >= amount1_min,
-        ErrorCode::InsufficientAmount
-    );
-
-    require!(
-        amount0 != 0 && amount1 != 0,
-        ErrorCode::InsufficientLiquidityBurned
-    );
-
-    Ok((amount0, amount1))
-}
@@ -1,11 +1,39 @@
+pub mod amp;
+pub mod concentrated_pool;
+pub mod concentrated_swap;
 pub mod create_pool;
+pub mod extensions;
+pub mod fee_tier;
+pub mod flash_loan;
+pub mod flash_swap;
 pub mod initialize;
 pub mod liquidity_operation;
+pub mod observations;
+pub mod oracle;
+pub mod pause;
+pub mod position;
+pub mod reserves;
+pub mod route;
 pub mod set_fee;
+pub mod set_owner;
 pub mod swap;
 
+pub use amp::*;
+pub use concentrated_pool::*;
+pub use concentrated_swap::*;
 pub use create_pool::*;
+pub use extensions::*;
+pub use fee_tier::*;
+pub use flash_loan::*;
+pub use flash_swap::*;
 pub use initialize::*;
 pub use liquidity_operation::*;
+pub use observations::*;
+pub use oracle::*;
+pub use pause::*;
+pub use position::*;
+pub use reserves::*;
+pub use route::*;
 pub use set_fee::*;
+pub use set_owner::*;
 pub use swap::*;
@@ -0,0 +1,66 @@
+use crate::constant::MAX_OBSERVE_QUERIES;
+use crate::error::ErrorCode;
+use crate::state::{Cumulatives, Observations, Pool};
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct ObservationCardinalityIncreased {
+    pub pool: Pubkey,
+    pub cardinality_next_old: u16,
+    pub cardinality_next_new: u16,
+}
+
+#[derive(Accounts)]
+pub struct IncreaseObservationCardinality<'info> {
+    #[account(seeds = [b"pool", pool.token0.as_ref(), pool.token1.as_ref(), pool.fee_tier.as_ref()], bump = pool.bump)]
+    pub pool: Account<'info, Pool>, // The pool whose oracle is extended
+    #[account(mut, seeds = [b"observations", pool.key().as_ref()], bump)]
+    pub observations: AccountLoader<'info, Observations>, // The oracle of the pool
+}
+
+/// Lets anyone reserve more oracle slots, the new slots are used once the ring wraps around.
+pub fn increase_observation_cardinality(
+    ctx: Context<IncreaseObservationCardinality>,
+    cardinality_next: u16,
+) -> Result<()> {
+    let observations = &mut ctx.accounts.observations.load_mut()?;
+    let cardinality_next_old = observations.cardinality_next;
+
+    observations.increase_cardinality(cardinality_next)?;
+    emit!(ObservationCardinalityIncreased {
+        pool: ctx.accounts.pool.key(),
+        cardinality_next_old,
+        cardinality_next_new: observations.cardinality_next,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct Observe<'info> {
+    #[account(seeds = [b"pool", pool.token0.as_ref(), pool.token1.as_ref(), pool.fee_tier.as_ref()], bump = pool.bump)]
+    pub pool: Account<'info, Pool>, // The pool being queried
+    #[account(seeds = [b"observations", pool.key().as_ref()], bump)]
+    pub observations: AccountLoader<'info, Observations>, // The oracle of the pool
+}
+
+/// Returns the cumulatives `seconds_ago` before now for each entry, TWAPs are the difference of two divided by the interval.
+pub fn observe(ctx: Context<Observe>, seconds_ago: Vec<u32>) -> Result<Vec<Cumulatives>> {
+    require!(
+        seconds_ago.len() <= MAX_OBSERVE_QUERIES,
+        ErrorCode::TooManyObservations
+    );
+
+    let pool = &ctx.accounts.pool;
+    let observations = ctx.accounts.observations.load()?;
+    let block_timestamp = Clock::get()?.unix_timestamp;
+    let (price0, price1) = pool.prices()?;
+    let liquidity = pool.liquidity();
+
+    seconds_ago
+        .iter()
+        .map(|&seconds_ago| {
+            observations.observe(block_timestamp, seconds_ago, price0, price1, liquidity)
+        })
+        .collect()
+}
@@ -0,0 +1,34 @@
+use crate::state::Pool;
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct UpdateOraclePrice<'info> {
+    // Oracle-pegged pool, re-derived from its mint pair
+    #[account(mut, seeds = [b"pool", pool.token0.as_ref(), pool.token1.as_ref(), pool.fee_tier.as_ref()], bump = pool.bump)]
+    pub pool: Account<'info, Pool>,
+
+    // Pyth price account of the pool
+    /// CHECK: checked against the pool and parsed by the pool
+    #[account(address = pool.oracle)]
+    pub oracle: AccountInfo<'info>,
+}
+
+#[event]
+pub struct OraclePriceUpdated {
+    pub pool: Pubkey,
+    pub price: u128,
+    pub publish_slot: u64,
+}
+
+pub fn update_oracle_price(ctx: Context<UpdateOraclePrice>) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+
+    pool.update_oracle_price(&ctx.accounts.oracle)?;
+    emit!(OraclePriceUpdated {
+        pool: pool.key(),
+        price: pool.oracle_price,
+        publish_slot: pool.oracle_publish_slot,
+    });
+
+    Ok(())
+}
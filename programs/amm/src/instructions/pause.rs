@@ -0,0 +1,104 @@
+use crate::state::config::Config;
+use crate::state::{ConcentratedPool, Pool};
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct PauseAuthoritySet {
+    pub old_pause_authority: Pubkey,
+    pub new_pause_authority: Pubkey,
+}
+
+#[event]
+pub struct PausedSet {
+    pub paused: bool,
+}
+
+#[event]
+pub struct PoolPausedSet {
+    pub pool: Pubkey,
+    pub paused: bool,
+}
+
+#[derive(Accounts)]
+pub struct SetPauseAuthority<'info> {
+    pub owner: Signer<'info>, // The owner of the contract
+    #[account(mut, seeds = [b"config"], bump = config.bump, has_one = owner)]
+    pub config: Account<'info, Config>, // The configuration account
+}
+
+pub fn set_pause_authority(
+    ctx: Context<SetPauseAuthority>,
+    new_pause_authority: Pubkey,
+) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+    let old_pause_authority = config.pause_authority;
+
+    config.set_pause_authority(new_pause_authority)?;
+    emit!(PauseAuthoritySet {
+        old_pause_authority,
+        new_pause_authority,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    pub pause_authority: Signer<'info>, // The account allowed to pause trading
+    #[account(mut, seeds = [b"config"], bump = config.bump, has_one = pause_authority)]
+    pub config: Account<'info, Config>, // The configuration account
+}
+
+pub fn set_paused(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+
+    config.set_paused(paused)?;
+    emit!(PausedSet { paused });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetPoolPaused<'info> {
+    pub pause_authority: Signer<'info>, // The account allowed to pause trading
+    #[account(seeds = [b"config"], bump = config.bump, has_one = pause_authority)]
+    pub config: Account<'info, Config>, // The configuration account
+    #[account(mut, seeds = [b"pool", pool.token0.as_ref(), pool.token1.as_ref(), pool.fee_tier.as_ref()], bump = pool.bump)]
+    pub pool: Account<'info, Pool>, // The pool being paused
+}
+
+pub fn set_pool_paused(ctx: Context<SetPoolPaused>, paused: bool) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+
+    pool.set_paused(paused)?;
+    emit!(PoolPausedSet {
+        pool: pool.key(),
+        paused,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetConcentratedPoolPaused<'info> {
+    pub pause_authority: Signer<'info>, // The account allowed to pause trading
+    #[account(seeds = [b"config"], bump = config.bump, has_one = pause_authority)]
+    pub config: Account<'info, Config>, // The configuration account
+    #[account(mut, seeds = [b"concentrated_pool", pool.token0.as_ref(), pool.token1.as_ref(), pool.fee_tier.as_ref()], bump = pool.bump)]
+    pub pool: Account<'info, ConcentratedPool>, // The concentrated pool being paused
+}
+
+pub fn set_concentrated_pool_paused(
+    ctx: Context<SetConcentratedPoolPaused>,
+    paused: bool,
+) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+
+    pool.set_paused(paused)?;
+    emit!(PoolPausedSet {
+        pool: pool.key(),
+        paused,
+    });
+
+    Ok(())
+}
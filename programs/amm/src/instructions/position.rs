@@ -0,0 +1,335 @@
+use crate::error::ErrorCode;
+use crate::state::ConcentratedPool;
+use crate::state::Config;
+use crate::state::Position;
+use crate::state::{Tick, TickArray};
+use crate::utils::{lock_concentrated_pool, transfer_tokens};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+#[derive(Accounts)]
+#[instruction(tick_lower: i32, tick_upper: i32)]
+pub struct OpenPosition<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(seeds = [b"concentrated_pool", pool.token0.as_ref(), pool.token1.as_ref(), pool.fee_tier.as_ref()], bump = pool.bump)]
+    pub pool: Box<Account<'info, ConcentratedPool>>,
+
+    // Position being opened, one per owner and range
+    #[account(
+        init,
+        payer = owner,
+        seeds = [b"position", pool.key().as_ref(), owner.key().as_ref(), tick_lower.to_le_bytes().as_ref(), tick_upper.to_le_bytes().as_ref()],
+        bump,
+        space = 8 + Position::INIT_SPACE
+    )]
+    pub position: Box<Account<'info, Position>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ModifyPosition<'info> {
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    pub owner: Signer<'info>,
+
+    #[account(mut, has_one = owner,)]
+    pub user_ata0: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut, has_one = owner,)]
+    pub user_ata1: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut, seeds = [b"concentrated_pool", pool.token0.as_ref(), pool.token1.as_ref(), pool.fee_tier.as_ref()], bump = pool.bump)]
+    pub pool: Box<Account<'info, ConcentratedPool>>,
+
+    /// CHECK: authority so one account can derive all other PDAs
+    #[account(seeds = [b"authority", pool.key().as_ref()], bump)]
+    pub pool_authority: AccountInfo<'info>,
+
+    #[account(mut, has_one = owner, has_one = pool)]
+    pub position: Box<Account<'info, Position>>,
+
+    // Tick arrays holding the position's lower and upper ticks, the same account if both are in one array
+    #[account(mut, constraint = tick_array_lower.load()?.pool == pool.key() @ ErrorCode::InvalidTickArray)]
+    pub tick_array_lower: AccountLoader<'info, TickArray>,
+    #[account(mut, constraint = tick_array_upper.load()?.pool == pool.key() @ ErrorCode::InvalidTickArray)]
+    pub tick_array_upper: AccountLoader<'info, TickArray>,
+
+    #[account(mut, address = pool.vault0 @ ErrorCode::InvalidVault, constraint = vault0.mint == user_ata0.mint,)]
+    pub vault0: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(address = pool.token0, mint::token_program = token_program0)]
+    pub mint0: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut, address = pool.vault1 @ ErrorCode::InvalidVault, constraint = vault1.mint == user_ata1.mint,)]
+    pub vault1: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(address = pool.token1, mint::token_program = token_program1)]
+    pub mint1: Box<InterfaceAccount<'info, Mint>>,
+
+    pub token_program0: Interface<'info, TokenInterface>,
+    pub token_program1: Interface<'info, TokenInterface>,
+}
+
+impl<'info> ModifyPosition<'info> {
+    // Updates the position's ticks and fees and the pool's liquidity, returning the token amounts moved
+    fn modify_position(&mut self, liquidity_delta: i128) -> Result<(u64, u64)> {
+        let pool = &mut self.pool;
+        let position = &mut self.position;
+        let (tick_lower, tick_upper) = (position.tick_lower, position.tick_upper);
+        let (global0, global1) = (pool.fee_growth_global0, pool.fee_growth_global1);
+        let (tick_current, tick_spacing) = (pool.tick_current, pool.tick_spacing);
+
+        let mut lower_array = self.tick_array_lower.load_mut()?;
+        let mut upper_array = if self.tick_array_upper.key() == self.tick_array_lower.key() {
+            None
+        } else {
+            Some(self.tick_array_upper.load_mut()?)
+        };
+
+        // Update both ticks, then read the fee growth between them
+        lower_array.tick_mut(tick_lower, tick_spacing)?.update(
+            tick_lower,
+            tick_current,
+            liquidity_delta,
+            global0,
+            global1,
+            false,
+        )?;
+        let lower = *lower_array.tick(tick_lower, tick_spacing)?;
+        let upper_array = match upper_array.as_mut() {
+            Some(upper_array) => &mut **upper_array,
+            None => &mut *lower_array,
+        };
+        let upper = upper_array.tick_mut(tick_upper, tick_spacing)?;
+        upper.update(
+            tick_upper,
+            tick_current,
+            liquidity_delta,
+            global0,
+            global1,
+            true,
+        )?;
+        let upper = *upper;
+
+        let (inside0, inside1) = pool.fee_growth_inside(tick_lower, &lower, tick_upper, &upper);
+        position.update(liquidity_delta, inside0, inside1)?;
+
+        // Clear ticks no position is bounded by any more, so they are not crossed
+        if !upper.is_initialized() {
+            *upper_array.tick_mut(tick_upper, tick_spacing)? = Tick::default();
+        }
+        if !lower.is_initialized() {
+            *lower_array.tick_mut(tick_lower, tick_spacing)? = Tick::default();
+        }
+
+        pool.modify_liquidity(tick_lower, tick_upper, liquidity_delta)
+    }
+
+    // Transfers amounts of both tokens from the user to the vaults
+    fn transfer_in(&self, amount0: u64, amount1: u64) -> Result<()> {
+        transfer_tokens(
+            &self.token_program0.to_account_info(),
+            &self.user_ata0.to_account_info(),
+            &self.mint0,
+            &self.vault0.to_account_info(),
+            &self.owner.to_account_info(),
+            &[],
+            amount0,
+        )?;
+        transfer_tokens(
+            &self.token_program1.to_account_info(),
+            &self.user_ata1.to_account_info(),
+            &self.mint1,
+            &self.vault1.to_account_info(),
+            &self.owner.to_account_info(),
+            &[],
+            amount1,
+        )
+    }
+
+    // Transfers amounts of both tokens from the vaults to the user
+    fn transfer_out(&self, amount0: u64, amount1: u64, pool_authority_bump: u8) -> Result<()> {
+        let pool_key = self.pool.key();
+        let pool_sign: &[&[u8]] = &[b"authority", pool_key.as_ref(), &[pool_authority_bump]];
+
+        transfer_tokens(
+            &self.token_program0.to_account_info(),
+            &self.vault0.to_account_info(),
+            &self.mint0,
+            &self.user_ata0.to_account_info(),
+            &self.pool_authority,
+            &[pool_sign],
+            amount0,
+        )?;
+        transfer_tokens(
+            &self.token_program1.to_account_info(),
+            &self.vault1.to_account_info(),
+            &self.mint1,
+            &self.user_ata1.to_account_info(),
+            &self.pool_authority,
+            &[pool_sign],
+            amount1,
+        )
+    }
+}
+
+#[event]
+pub struct PositionOpened {
+    pub owner: Pubkey,
+    pub pool: Pubkey,
+    pub position: Pubkey,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+}
+
+#[event]
+pub struct PositionLiquidityIncreased {
+    pub owner: Pubkey,
+    pub position: Pubkey,
+    pub amount0: u64,
+    pub amount1: u64,
+    pub liquidity: u128,
+}
+
+#[event]
+pub struct PositionLiquidityDecreased {
+    pub owner: Pubkey,
+    pub position: Pubkey,
+    pub amount0: u64,
+    pub amount1: u64,
+    pub liquidity: u128,
+}
+
+#[event]
+pub struct FeesCollected {
+    pub owner: Pubkey,
+    pub position: Pubkey,
+    pub amount0: u64,
+    pub amount1: u64,
+}
+
+pub fn open_position(ctx: Context<OpenPosition>, tick_lower: i32, tick_upper: i32) -> Result<()> {
+    let pool = &ctx.accounts.pool;
+    pool.require_valid_tick(tick_lower)?;
+    pool.require_valid_tick(tick_upper)?;
+
+    ctx.accounts.position.initialize(
+        ctx.bumps.position,
+        pool.key(),
+        ctx.accounts.owner.key(),
+        tick_lower,
+        tick_upper,
+    )?;
+
+    emit!(PositionOpened {
+        owner: ctx.accounts.owner.key(),
+        pool: pool.key(),
+        position: ctx.accounts.position.key(),
+        tick_lower,
+        tick_upper,
+    });
+
+    Ok(())
+}
+
+pub fn increase_liquidity(
+    ctx: Context<ModifyPosition>,
+    liquidity: u128,
+    amount0_max: u64,
+    amount1_max: u64,
+) -> Result<()> {
+    let accounts = ctx.accounts;
+
+    // Lock the pool against re-entry through the token transfers
+    accounts.pool.require_not_paused(&accounts.config)?;
+    lock_concentrated_pool(&mut accounts.pool)?;
+
+    let liquidity_delta = i128::try_from(liquidity).map_err(|_| ErrorCode::CalculationFailure)?;
+    require!(liquidity_delta > 0, ErrorCode::InsufficientAmount);
+
+    // Ensure the amounts the liquidity needs, rounded up, are within the user's limits
+    let (amount0, amount1) = accounts.modify_position(liquidity_delta)?;
+    require!(
+        amount0 <= amount0_max && amount1 <= amount1_max,
+        ErrorCode::InsufficientInputAmount
+    );
+
+    accounts.transfer_in(amount0, amount1)?;
+
+    emit!(PositionLiquidityIncreased {
+        owner: accounts.owner.key(),
+        position: accounts.position.key(),
+        amount0,
+        amount1,
+        liquidity,
+    });
+
+    accounts.pool.unlock();
+    Ok(())
+}
+
+pub fn decrease_liquidity(
+    ctx: Context<ModifyPosition>,
+    liquidity: u128,
+    amount0_min: u64,
+    amount1_min: u64,
+) -> Result<()> {
+    let accounts = ctx.accounts;
+
+    // Lock the pool against re-entry through the token transfers
+    lock_concentrated_pool(&mut accounts.pool)?;
+
+    require!(
+        liquidity > 0 && liquidity <= accounts.position.liquidity,
+        ErrorCode::InsufficientLiquidityBurned
+    );
+
+    // Ensure the amounts the liquidity is worth, rounded down, meet the user's limits
+    let (amount0, amount1) = accounts.modify_position(-(liquidity as i128))?;
+    require!(
+        amount0 >= amount0_min && amount1 >= amount1_min,
+        ErrorCode::InsufficientOutputAmount
+    );
+
+    accounts.transfer_out(amount0, amount1, ctx.bumps.pool_authority)?;
+
+    emit!(PositionLiquidityDecreased {
+        owner: accounts.owner.key(),
+        position: accounts.position.key(),
+        amount0,
+        amount1,
+        liquidity,
+    });
+
+    accounts.pool.unlock();
+    Ok(())
+}
+
+pub fn collect_fees(ctx: Context<ModifyPosition>) -> Result<()> {
+    let accounts = ctx.accounts;
+
+    // Lock the pool against re-entry through the token transfers
+    lock_concentrated_pool(&mut accounts.pool)?;
+
+    // Credit the fees earned since the position was last updated
+    if accounts.position.liquidity > 0 {
+        accounts.modify_position(0)?;
+    }
+
+    let (amount0, amount1) = accounts.position.take_fees();
+    accounts.transfer_out(amount0, amount1, ctx.bumps.pool_authority)?;
+
+    emit!(FeesCollected {
+        owner: accounts.owner.key(),
+        position: accounts.position.key(),
+        amount0,
+        amount1,
+    });
+
+    accounts.pool.unlock();
+    Ok(())
+}
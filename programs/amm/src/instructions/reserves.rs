@@ -0,0 +1,162 @@
+use crate::error::ErrorCode;
+use crate::state::Observations;
+use crate::state::Pool;
+use crate::utils::lock_pool;
+use anchor_lang::prelude::*;
+use anchor_spl::token;
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+
+#[derive(Accounts)]
+pub struct SyncReserves<'info> {
+    // Pool account, re-derived from its mint pair
+    #[account(mut, seeds = [b"pool", pool.token0.as_ref(), pool.token1.as_ref(), pool.fee_tier.as_ref()], bump = pool.bump)]
+    pub pool: Box<Account<'info, Pool>>,
+
+    // Price oracle of the pool
+    #[account(mut, seeds = [b"observations", pool.key().as_ref()], bump)]
+    pub observations: AccountLoader<'info, Observations>,
+
+    // Vaults bound to the pool
+    #[account(address = pool.vault0 @ ErrorCode::InvalidVault)]
+    pub vault0: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(address = pool.vault1 @ ErrorCode::InvalidVault)]
+    pub vault1: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // LP mint of the pool, its supply tells whether the pool holds liquidity
+    #[account(seeds = [b"lp_mint", pool.key().as_ref()], bump)]
+    pub lp_mint: Box<Account<'info, token::Mint>>,
+}
+
+#[derive(Accounts)]
+pub struct SkimReserves<'info> {
+    // Pool account, re-derived from its mint pair
+    #[account(mut, seeds = [b"pool", pool.token0.as_ref(), pool.token1.as_ref(), pool.fee_tier.as_ref()], bump = pool.bump)]
+    pub pool: Box<Account<'info, Pool>>,
+
+    // Pool authority PDA
+    /// CHECK: authority so 1 acc pass in can derive all other PDAs
+    #[account(seeds = [b"authority", pool.key().as_ref()], bump)]
+    pub pool_authority: AccountInfo<'info>,
+
+    // Vaults bound to the pool
+    #[account(mut, address = pool.vault0 @ ErrorCode::InvalidVault)]
+    pub vault0: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, address = pool.vault1 @ ErrorCode::InvalidVault)]
+    pub vault1: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // Recipients of the excess balances, must match the mints of the vaults
+    #[account(mut, constraint = to0.mint == vault0.mint)]
+    pub to0: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, constraint = to1.mint == vault1.mint)]
+    pub to1: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // Mints of the pool, owned by their token programs
+    #[account(address = pool.token0, mint::token_program = token_program0)]
+    pub mint0: Box<InterfaceAccount<'info, Mint>>,
+    #[account(address = pool.token1, mint::token_program = token_program1)]
+    pub mint1: Box<InterfaceAccount<'info, Mint>>,
+
+    pub token_program0: Interface<'info, TokenInterface>,
+    pub token_program1: Interface<'info, TokenInterface>,
+}
+
+#[event]
+pub struct ReservesSynced {
+    pub pool: Pubkey,
+    pub reserve0: u64,
+    pub reserve1: u64,
+}
+
+#[event]
+pub struct ReservesSkimmed {
+    pub pool: Pubkey,
+    pub amount0: u64,
+    pub amount1: u64,
+}
+
+/// Forces the tracked reserves to match the vault balances, once the pool holds liquidity.
+pub fn sync(ctx: Context<SyncReserves>) -> Result<()> {
+    ctx.accounts.pool.require_no_flash_loan()?;
+
+    // Syncing donations into an empty pool would set the first deposit's ratio, or leave one
+    // reserve empty and reject every deposit
+    require!(ctx.accounts.lp_mint.supply > 0, ErrorCode::NoLiquidity);
+    let (reserve0, reserve1) = (ctx.accounts.vault0.amount, ctx.accounts.vault1.amount);
+
+    let pool = &mut ctx.accounts.pool;
+    pool.lock()?;
+    pool.update_reserves(
+        reserve0,
+        reserve1,
+        &mut *ctx.accounts.observations.load_mut()?,
+    )?;
+    pool.unlock();
+
+    emit!(ReservesSynced {
+        pool: pool.key(),
+        reserve0,
+        reserve1,
+    });
+
+    Ok(())
+}
+
+/// Sends any vault balance in excess of the tracked reserves to the given recipients.
+pub fn skim(ctx: Context<SkimReserves>) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+    pool.require_no_flash_loan()?;
+
+    // Lock the pool against re-entry through the token transfers
+    lock_pool(pool)?;
+    // A vault holding less than its reserve has nothing to skim
+    let amount0 = ctx.accounts.vault0.amount.saturating_sub(pool.reserve0);
+    let amount1 = ctx.accounts.vault1.amount.saturating_sub(pool.reserve1);
+
+    let pool_key = pool.key();
+    let pool_sign = &[b"authority", pool_key.as_ref(), &[ctx.bumps.pool_authority]];
+
+    if amount0 > 0 {
+        transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program0.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.vault0.to_account_info(),
+                    mint: ctx.accounts.mint0.to_account_info(),
+                    to: ctx.accounts.to0.to_account_info(),
+                    authority: ctx.accounts.pool_authority.to_account_info(),
+                },
+            )
+            .with_signer(&[pool_sign]),
+            amount0,
+            ctx.accounts.mint0.decimals,
+        )?;
+    }
+
+    if amount1 > 0 {
+        transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program1.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.vault1.to_account_info(),
+                    mint: ctx.accounts.mint1.to_account_info(),
+                    to: ctx.accounts.to1.to_account_info(),
+                    authority: ctx.accounts.pool_authority.to_account_info(),
+                },
+            )
+            .with_signer(&[pool_sign]),
+            amount1,
+            ctx.accounts.mint1.decimals,
+        )?;
+    }
+
+    ctx.accounts.pool.unlock();
+    emit!(ReservesSkimmed {
+        pool: pool_key,
+        amount0,
+        amount1,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,347 @@
+use crate::constant::{MAX_ROUTE_HOPS, ROUTE_HOP_ACCOUNTS};
+use crate::error::ErrorCode;
+use crate::instructions::{get_amount_in, get_amount_out, swap, transfer_input, SwapAccounts};
+use crate::state::{Config, CurveType, Observations, Pool};
+use crate::utils::{get_transfer_fee, get_transfer_inverse_fee, lock_pool};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+#[derive(Accounts)]
+pub struct SwapRoute<'info> {
+    // Config PDA
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    pub owner: Signer<'info>,
+
+    // User's token account for the first mint of the route, must be owned by the user
+    #[account(mut, has_one = owner)]
+    pub user_ata_src: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // User's token account for the last mint of the route, must be owned by the user
+    #[account(mut, has_one = owner)]
+    pub user_ata_des: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // Mint the route starts from, owned by its token program
+    #[account(address = user_ata_src.mint, mint::token_program = token_program_src)]
+    pub mint_src: Box<InterfaceAccount<'info, Mint>>,
+
+    pub token_program_src: Interface<'info, TokenInterface>,
+    // Each hop follows in the remaining accounts as: pool, pool authority, observations,
+    // source vault, destination vault, destination mint and its token program. The Pyth
+    // price accounts of oracle-pegged hops to refresh may follow the last hop.
+}
+
+// Accounts of one hop of a route, read from the remaining accounts
+pub struct RouteHop<'info> {
+    pub pool: Account<'info, Pool>,
+    pub pool_authority: AccountInfo<'info>,
+    pub pool_authority_bump: u8,
+    pub observations: AccountLoader<'info, Observations>,
+    pub vault_src: InterfaceAccount<'info, TokenAccount>,
+    pub vault_des: InterfaceAccount<'info, TokenAccount>,
+    pub mint_src: InterfaceAccount<'info, Mint>,
+    pub mint_des: InterfaceAccount<'info, Mint>,
+    pub token_program_src: AccountInfo<'info>,
+    pub token_program_des: AccountInfo<'info>,
+    pub recipient: AccountInfo<'info>, // The next hop's source vault, or the user's destination account
+}
+
+impl<'info> RouteHop<'info> {
+    /// Reads and validates a hop swapping `mint_src` through the pool given in `accounts`.
+    fn try_from_accounts(
+        config: &Config,
+        accounts: &'info [AccountInfo<'info>],
+        mint_src: InterfaceAccount<'info, Mint>,
+        token_program_src: AccountInfo<'info>,
+        recipient: AccountInfo<'info>,
+    ) -> Result<Self> {
+        // Re-derive the pool from its mint pair and fee tier, like the pool of a direct swap
+        let pool = Account::<Pool>::try_from(&accounts[0])?;
+        let pool_key = Pubkey::create_program_address(
+            &[
+                b"pool",
+                pool.token0.as_ref(),
+                pool.token1.as_ref(),
+                pool.fee_tier.as_ref(),
+                &[pool.bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| ErrorCode::InvalidRoute)?;
+        require_keys_eq!(pool.key(), pool_key, ErrorCode::InvalidRoute);
+        pool.require_not_paused(config)?;
+
+        let (pool_authority, pool_authority_bump) =
+            Pubkey::find_program_address(&[b"authority", pool.key().as_ref()], &crate::ID);
+        require_keys_eq!(accounts[1].key(), pool_authority, ErrorCode::InvalidRoute);
+
+        let observations = AccountLoader::<Observations>::try_from(&accounts[2])?;
+        require_keys_eq!(
+            observations.load()?.pool,
+            pool.key(),
+            ErrorCode::InvalidRoute
+        );
+
+        // The vaults must be the pool's two vaults, entered from the previous hop's mint
+        let vault_src = InterfaceAccount::<TokenAccount>::try_from(&accounts[3])?;
+        let vault_des = InterfaceAccount::<TokenAccount>::try_from(&accounts[4])?;
+        require!(
+            pool.is_vault(&vault_src.key())
+                && pool.is_vault(&vault_des.key())
+                && vault_src.key() != vault_des.key(),
+            ErrorCode::InvalidVault
+        );
+        require_keys_eq!(vault_src.mint, mint_src.key(), ErrorCode::InvalidRoute);
+
+        let mint_des = InterfaceAccount::<Mint>::try_from(&accounts[5])?;
+        let token_program_des = Interface::<TokenInterface>::try_from(&accounts[6])?;
+        require_keys_eq!(mint_des.key(), vault_des.mint, ErrorCode::InvalidRoute);
+        require_keys_eq!(
+            *mint_des.to_account_info().owner,
+            token_program_des.key(),
+            ErrorCode::InvalidRoute
+        );
+
+        Ok(RouteHop {
+            pool,
+            pool_authority: accounts[1].clone(),
+            pool_authority_bump,
+            observations,
+            vault_src,
+            vault_des,
+            mint_src,
+            mint_des,
+            token_program_src,
+            token_program_des: token_program_des.to_account_info(),
+            recipient,
+        })
+    }
+
+    // Reloads the source vault, returning the amount it received from the previous hop
+    fn receive(&mut self) -> Result<u64> {
+        let vault_src_before = self.vault_src.amount;
+        self.vault_src.reload()?;
+        Ok(self.vault_src.amount - vault_src_before)
+    }
+}
+
+impl<'info> SwapRoute<'info> {
+    /// Reads the hops of the route from the remaining accounts.
+    fn route_hops(
+        &self,
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<Vec<RouteHop<'info>>> {
+        // Fewer oracles than accounts per hop can follow, as there are fewer hops than that
+        let hop_count = remaining_accounts.len() / ROUTE_HOP_ACCOUNTS;
+        require!(
+            hop_count > 0 && hop_count <= MAX_ROUTE_HOPS,
+            ErrorCode::InvalidRoute
+        );
+        let (hop_accounts, oracles) = remaining_accounts.split_at(hop_count * ROUTE_HOP_ACCOUNTS);
+
+        let mut hops: Vec<RouteHop<'info>> = Vec::new();
+        for accounts in hop_accounts.chunks(ROUTE_HOP_ACCOUNTS) {
+            // Each hop starts from the mint the previous one ends with
+            let (mint_src, token_program_src) = match hops.last() {
+                Some(prev) => (prev.mint_des.clone(), prev.token_program_des.clone()),
+                None => (
+                    (*self.mint_src).clone(),
+                    self.token_program_src.to_account_info(),
+                ),
+            };
+            let hop = RouteHop::try_from_accounts(
+                &self.config,
+                accounts,
+                mint_src,
+                token_program_src,
+                self.user_ata_des.to_account_info(),
+            )?;
+
+            // A pool can only be used once, its reserves would otherwise be stale
+            require!(
+                hops.iter().all(|prev| prev.pool.key() != hop.pool.key()),
+                ErrorCode::InvalidRoute
+            );
+
+            // The previous hop pays straight into this hop's source vault
+            if let Some(prev) = hops.last_mut() {
+                prev.recipient = hop.vault_src.to_account_info();
+            }
+            hops.push(hop);
+        }
+
+        let last = hops.last().ok_or(ErrorCode::InvalidRoute)?;
+        require_keys_eq!(
+            last.mint_des.key(),
+            self.user_ata_des.mint,
+            ErrorCode::InvalidRoute
+        );
+
+        // Refresh the oracle prices of oracle-pegged hops, each trailing account must be one
+        let is_hop_oracle = |oracle: &AccountInfo| {
+            hops.iter().any(|hop| {
+                hop.pool.curve_type == CurveType::OraclePegged && hop.pool.oracle == oracle.key()
+            })
+        };
+        require!(oracles.iter().all(is_hop_oracle), ErrorCode::InvalidRoute);
+        for hop in hops.iter_mut() {
+            hop.pool.load_oracle_price(oracles)?;
+        }
+
+        Ok(hops)
+    }
+
+    fn hop_accounts<'a>(&'a self, hop: &'a mut RouteHop<'info>) -> SwapAccounts<'a, 'info> {
+        SwapAccounts {
+            config: &self.config,
+            owner: self.owner.to_account_info(),
+            user_src: self.user_ata_src.to_account_info(),
+            user_src_amount: self.user_ata_src.amount,
+            user_des: hop.recipient.clone(),
+            pool: &mut hop.pool,
+            pool_authority: hop.pool_authority.clone(),
+            pool_authority_bump: hop.pool_authority_bump,
+            observations: &hop.observations,
+            vault_src: &mut hop.vault_src,
+            vault_des: &hop.vault_des,
+            mint_src: &hop.mint_src,
+            mint_des: &hop.mint_des,
+            token_program_src: hop.token_program_src.clone(),
+            token_program_des: hop.token_program_des.clone(),
+        }
+    }
+}
+
+#[event]
+pub struct RouteSwapEvent {
+    pub owner: Pubkey,
+    pub input_amount: u64,
+    pub output_amount: u64,
+    pub mint_src: Pubkey,
+    pub mint_des: Pubkey,
+    pub hops: u8,
+}
+
+pub fn swap_route_exact_input<'info>(
+    ctx: Context<'_, '_, 'info, 'info, SwapRoute<'info>>,
+    input_amount: u64,
+    min_output_amount: u64,
+) -> Result<()> {
+    let route = &ctx.accounts;
+    let mut hops = route.route_hops(ctx.remaining_accounts)?;
+
+    // Each hop swaps whatever its source vault actually received
+    let output_amount = execute(route, &mut hops, input_amount, |hop, _, amount_in| {
+        get_amount_out(&hop.pool, &hop.vault_src.key(), amount_in)
+    })?;
+
+    // Ensure the output amount received by the user, net of transfer fees, meets the minimum required
+    let last = hops.last().ok_or(ErrorCode::InvalidRoute)?;
+    let amount_received = output_amount - get_transfer_fee(&last.mint_des, output_amount)?;
+    require!(
+        amount_received >= min_output_amount,
+        ErrorCode::InsufficientOutputAmount,
+    );
+
+    emit_route(route, &hops, input_amount, output_amount)
+}
+
+pub fn swap_route_exact_output<'info>(
+    ctx: Context<'_, '_, 'info, 'info, SwapRoute<'info>>,
+    output_amount: u64,
+    max_input_amount: u64,
+) -> Result<()> {
+    let route = &ctx.accounts;
+    let mut hops = route.route_hops(ctx.remaining_accounts)?;
+
+    // Work back from the output, each hop must deliver what the next needs net of transfer fees
+    let last = hops.last().ok_or(ErrorCode::InvalidRoute)?;
+    let mut amount_out = output_amount
+        .checked_add(get_transfer_inverse_fee(&last.mint_des, output_amount)?)
+        .ok_or(ErrorCode::CalculationFailure)?;
+    let mut amounts = vec![(0, 0); hops.len()];
+    for (i, hop) in hops.iter().enumerate().rev() {
+        let (_, reserve_out) = hop.pool.swap_reserves(&hop.vault_src.key());
+        require!(amount_out < reserve_out, ErrorCode::InsufficientLiquidity,);
+
+        let amount_in = get_amount_in(&hop.pool, &hop.vault_src.key(), amount_out)?;
+        amounts[i] = (amount_in, amount_out);
+        amount_out = amount_in
+            .checked_add(get_transfer_inverse_fee(&hop.mint_src, amount_in)?)
+            .ok_or(ErrorCode::CalculationFailure)?;
+    }
+
+    // Ensure the input amount does not exceed the maximum allowed
+    let input_amount = amount_out;
+    require!(
+        input_amount <= max_input_amount,
+        ErrorCode::InsufficientInputAmount,
+    );
+
+    // Ensure each vault received what its hop requires
+    let output_amount = execute(route, &mut hops, input_amount, |_, i, amount_received| {
+        let (amount_in, amount_out) = amounts[i];
+        require!(
+            amount_received >= amount_in,
+            ErrorCode::InsufficientInputAmount,
+        );
+        Ok(amount_out)
+    })?;
+
+    emit_route(route, &hops, input_amount, output_amount)
+}
+
+// Transfers the input into the first pool and runs each hop, returning the last hop's output
+fn execute<'info>(
+    route: &SwapRoute<'info>,
+    hops: &mut [RouteHop<'info>],
+    input_amount: u64,
+    mut amount_out: impl FnMut(&RouteHop<'info>, usize, u64) -> Result<u64>,
+) -> Result<u64> {
+    // Lock every pool of the route against re-entry through the token transfers
+    for hop in hops.iter_mut() {
+        hop.pool.require_no_flash_loan()?;
+        lock_pool(&mut hop.pool)?;
+    }
+
+    let mut amount_in = transfer_input(&mut route.hop_accounts(&mut hops[0]), input_amount)?;
+    let mut output_amount = 0;
+
+    for i in 0..hops.len() {
+        output_amount = amount_out(&hops[i], i, amount_in)?;
+        swap(
+            &mut route.hop_accounts(&mut hops[i]),
+            amount_in,
+            output_amount,
+        )?;
+
+        // Pools read from the remaining accounts are not persisted by Anchor
+        hops[i].pool.unlock();
+        hops[i].pool.exit(&crate::ID)?;
+
+        if let Some(next) = hops.get_mut(i + 1) {
+            amount_in = next.receive()?;
+        }
+    }
+
+    Ok(output_amount)
+}
+
+fn emit_route(
+    route: &SwapRoute,
+    hops: &[RouteHop],
+    input_amount: u64,
+    output_amount: u64,
+) -> Result<()> {
+    emit!(RouteSwapEvent {
+        owner: route.owner.key(),
+        input_amount,
+        output_amount,
+        mint_src: route.mint_src.key(),
+        mint_des: route.user_ata_des.mint,
+        hops: hops.len() as u8,
+    });
+
+    Ok(())
+}
@@ -1,8 +1,7 @@
 use crate::state::config::Config;
-use crate::state::Pool;
+use crate::state::{CurveType, FeeTier, Pool};
 use anchor_lang::prelude::*;
 use anchor_spl::token::{mint_to, MintTo};
-use fixed::types::U128F0;
 
 #[event]
 pub struct FeeToSet {
@@ -11,11 +10,23 @@ pub struct FeeToSet {
 }
 
 #[event]
-pub struct FeeSet {
+pub struct PoolFeeSet {
+    pub pool: Pubkey,
     pub old_fee: u64,
     pub new_fee: u64,
 }
 
+#[event]
+pub struct FeeOnSet {
+    pub fee_on: bool,
+}
+
+#[event]
+pub struct FlashLoanFeeSet {
+    pub old_flash_loan_fee: u64,
+    pub new_flash_loan_fee: u64,
+}
+
 #[event]
 pub struct LiquidityMinted {
     pub liquidity: u64,
@@ -44,7 +55,35 @@ pub fn set_fee_to(ctx: Context<SetFeeTo>, new_fee_to: Pubkey) -> Result<()> {
 }
 
 #[derive(Accounts)]
-pub struct SetFee<'info> {
+pub struct SetPoolFee<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>, // The owner of the contract
+    #[account(seeds = [b"config"], bump = config.bump, has_one = owner)]
+    pub config: Account<'info, Config>, // The configuration account
+    #[account(mut, seeds = [b"pool", pool.token0.as_ref(), pool.token1.as_ref(), pool.fee_tier.as_ref()], bump = pool.bump)]
+    pub pool: Account<'info, Pool>, // The pool whose fee is changed
+    #[account(seeds = [b"fee_tier", fee_tier.fee.to_le_bytes().as_ref()], bump = fee_tier.bump)]
+    pub fee_tier: Account<'info, FeeTier>, // The fee tier providing the new fee
+}
+
+/// Sets the pool's fee to that of any existing fee tier, the pool keeps the tier in its address.
+pub fn set_pool_fee(ctx: Context<SetPoolFee>) -> Result<()> {
+    let new_fee = ctx.accounts.fee_tier.fee;
+    let pool = &mut ctx.accounts.pool;
+    let old_fee = pool.fee;
+
+    pool.set_fee(new_fee)?;
+    emit!(PoolFeeSet {
+        pool: pool.key(),
+        old_fee,
+        new_fee,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetFeeOn<'info> {
     #[account(mut)]
     pub owner: Signer<'info>, // The owner of the contract
     #[account(mut, seeds = [b"config"], bump = config.bump, has_one = owner)]
@@ -52,41 +91,61 @@ pub struct SetFee<'info> {
     pub system_program: Program<'info, System>,
 }
 
-pub fn set_fee(ctx: Context<SetFee>, new_fee: u64) -> Result<()> {
+pub fn set_fee_on(ctx: Context<SetFeeOn>, fee_on: bool) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+
+    config.set_fee_on(fee_on)?;
+    emit!(FeeOnSet { fee_on });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetFlashLoanFee<'info> {
+    pub owner: Signer<'info>, // The owner of the contract
+    #[account(mut, seeds = [b"config"], bump = config.bump, has_one = owner)]
+    pub config: Account<'info, Config>, // The configuration account
+}
+
+pub fn set_flash_loan_fee(ctx: Context<SetFlashLoanFee>, new_flash_loan_fee: u64) -> Result<()> {
     let config = &mut ctx.accounts.config;
-    let old_fee = config.fee;
+    let old_flash_loan_fee = config.flash_loan_fee;
 
-    config.set_fee(new_fee)?;
-    emit!(FeeSet { old_fee, new_fee });
+    config.set_flash_loan_fee(new_flash_loan_fee)?;
+    emit!(FlashLoanFeeSet {
+        old_flash_loan_fee,
+        new_flash_loan_fee,
+    });
 
     Ok(())
 }
 
+/// Mints the protocol's share of the accrued swap fees to the fee recipient's LP account,
+/// returning whether the protocol fee is on (mirrors Uniswap V2's `_mintFee`).
 pub fn mint_fee<'info>(
-    _config: &Config,
-    pool: &Pool,    // The liquidity pool
-    reserve0: u64,  // Reserve of token0
-    reserve1: u64,  // Reserve of token1
-    lp_supply: u64, // Total supply of liquidity tokens
+    config: &Config,
+    pool: &mut Pool, // The liquidity pool
+    reserve0: u64,   // Reserve of token0
+    reserve1: u64,   // Reserve of token1
+    lp_supply: u64,  // Total supply of liquidity tokens
     mint_ctx: CpiContext<'_, '_, '_, 'info, MintTo<'info>>,
-) -> Result<()> {
+) -> Result<bool> {
+    // The invariant of oracle-pegged pools also grows with the oracle price, not only with fees
+    let fee_on = config.fee_on && pool.curve_type != CurveType::OraclePegged;
     let k_last = pool.k_last;
 
-    if k_last != 0 {
-        let root_k: u128 = U128F0::from_num((reserve0 as u128) * (reserve1 as u128))
-            .sqrt()
-            .to_num::<u128>();
-        let root_k_last = U128F0::from_num(k_last).sqrt().to_num::<u128>();
-        if root_k > root_k_last {
-            let numerator: u128 = (lp_supply as u128) * (root_k - root_k_last);
-            let denominator: u128 = root_k * 5 + root_k_last;
-            let liquidity: u64 = (numerator / denominator) as u64;
-            if liquidity > 0 {
-                mint_to(mint_ctx, liquidity)?; // Mint new liquidity tokens
-                emit!(LiquidityMinted { liquidity });
-            }
+    if !fee_on {
+        // Reset k_last so fees are not charged retroactively once the fee is turned back on
+        if k_last != 0 {
+            pool.k_last = 0;
+        }
+    } else if k_last != 0 {
+        let liquidity = pool.protocol_fee_liquidity(reserve0, reserve1, lp_supply)?;
+        if liquidity > 0 {
+            mint_to(mint_ctx, liquidity)?; // Mint new liquidity tokens
+            emit!(LiquidityMinted { liquidity });
         }
     }
 
-    Ok(())
+    Ok(fee_on)
 }
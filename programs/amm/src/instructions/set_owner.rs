@@ -0,0 +1,80 @@
+use crate::error::ErrorCode;
+use crate::state::config::Config;
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct OwnerProposed {
+    pub owner: Pubkey,
+    pub pending_owner: Pubkey,
+}
+
+#[event]
+pub struct OwnerAccepted {
+    pub old_owner: Pubkey,
+    pub new_owner: Pubkey,
+}
+
+#[event]
+pub struct OwnerTransferCancelled {
+    pub owner: Pubkey,
+    pub pending_owner: Pubkey,
+}
+
+#[derive(Accounts)]
+pub struct ProposeOwner<'info> {
+    pub owner: Signer<'info>, // The owner of the contract
+    #[account(mut, seeds = [b"config"], bump = config.bump, has_one = owner)]
+    pub config: Account<'info, Config>, // The configuration account
+}
+
+pub fn propose_owner(ctx: Context<ProposeOwner>, new_owner: Pubkey) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+
+    config.propose_owner(new_owner)?;
+    emit!(OwnerProposed {
+        owner: config.owner,
+        pending_owner: new_owner,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AcceptOwner<'info> {
+    pub pending_owner: Signer<'info>, // The proposed owner
+    #[account(mut, seeds = [b"config"], bump = config.bump, has_one = pending_owner @ ErrorCode::NotPendingOwner)]
+    pub config: Account<'info, Config>, // The configuration account
+}
+
+pub fn accept_owner(ctx: Context<AcceptOwner>) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+    let old_owner = config.owner;
+
+    config.accept_owner()?;
+    emit!(OwnerAccepted {
+        old_owner,
+        new_owner: config.owner,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CancelOwnerTransfer<'info> {
+    pub owner: Signer<'info>, // The owner of the contract
+    #[account(mut, seeds = [b"config"], bump = config.bump, has_one = owner)]
+    pub config: Account<'info, Config>, // The configuration account
+}
+
+pub fn cancel_owner_transfer(ctx: Context<CancelOwnerTransfer>) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+    let pending_owner = config.pending_owner;
+
+    config.cancel_owner_transfer()?;
+    emit!(OwnerTransferCancelled {
+        owner: config.owner,
+        pending_owner,
+    });
+
+    Ok(())
+}
@@ -1,10 +1,17 @@
-use crate::constant::BASIS_POINTS;
 use crate::error::ErrorCode;
 use crate::state::Config;
+use crate::state::Observations;
 use crate::state::Pool;
+use crate::utils::{
+    get_transfer_fee, get_transfer_inverse_fee, lock_pool, require_not_expired, unwrap_sol,
+    wrap_sol,
+};
 use anchor_lang::prelude::*;
 use anchor_spl::associated_token::AssociatedToken;
-use anchor_spl::token::{transfer, Mint, Token, TokenAccount, Transfer};
+use anchor_spl::token::{self, spl_token::native_mint, Token};
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
 
 #[derive(Accounts)]
 pub struct Swap<'info> {
@@ -17,14 +24,14 @@ pub struct Swap<'info> {
 
     // User's source token account, must be owned by the user
     #[account(mut, has_one = owner)]
-    pub user_ata_src: Box<Account<'info, TokenAccount>>,
+    pub user_ata_src: Box<InterfaceAccount<'info, TokenAccount>>,
 
     // User's destination token account, must be owned by the user
     #[account(mut, has_one = owner)]
-    pub user_ata_des: Box<Account<'info, TokenAccount>>,
+    pub user_ata_des: Box<InterfaceAccount<'info, TokenAccount>>,
 
-    // Pool account
-    #[account(mut)]
+    // Pool account, re-derived from its mint pair
+    #[account(mut, seeds = [b"pool", pool.token0.as_ref(), pool.token1.as_ref(), pool.fee_tier.as_ref()], bump = pool.bump)]
     pub pool: Box<Account<'info, Pool>>,
 
     // Pool authority PDA
@@ -32,23 +39,214 @@ pub struct Swap<'info> {
     #[account(seeds=[b"authority", pool.key().as_ref()], bump)]
     pub pool_authority: AccountInfo<'info>,
 
-    // Source vault for the swap, must match the mint of the user's source token account
-    #[account(mut, constraint = user_ata_src.mint == vault_src.mint)]
-    pub vault_src: Box<Account<'info, TokenAccount>>,
+    // Price oracle of the pool
+    #[account(mut, seeds = [b"observations", pool.key().as_ref()], bump)]
+    pub observations: AccountLoader<'info, Observations>,
+
+    // Source vault for the swap, must be one of the pool's vaults and match the mint of the user's source token account
+    #[account(
+        mut,
+        constraint = pool.is_vault(&vault_src.key()) @ ErrorCode::InvalidVault,
+        constraint = user_ata_src.mint == vault_src.mint
+    )]
+    pub vault_src: Box<InterfaceAccount<'info, TokenAccount>>,
 
-    // Destination vault for the swap, must match the mint of the user's destination token account
-    #[account(mut, constraint = user_ata_des.mint == vault_des.mint)]
-    pub vault_des: Box<Account<'info, TokenAccount>>,
+    // Destination vault for the swap, must be the pool's other vault and match the mint of the user's destination token account
+    #[account(
+        mut,
+        constraint = pool.is_vault(&vault_des.key()) @ ErrorCode::InvalidVault,
+        constraint = vault_des.key() != vault_src.key() @ ErrorCode::InvalidVault,
+        constraint = user_ata_des.mint == vault_des.mint
+    )]
+    pub vault_des: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // Mints of the source and destination vaults, owned by their token programs
+    #[account(address = vault_src.mint, mint::token_program = token_program_src)]
+    pub mint_src: Box<InterfaceAccount<'info, Mint>>,
+    #[account(address = vault_des.mint, mint::token_program = token_program_des)]
+    pub mint_des: Box<InterfaceAccount<'info, Mint>>,
 
     // LP mint PDA
     #[account(mut, seeds = [b"lp_mint", pool.key().as_ref()], bump)]
-    pub lp_mint: Box<Account<'info, Mint>>,
+    pub lp_mint: Box<Account<'info, token::Mint>>,
 
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Program<'info, Token>,
+    pub token_program_src: Interface<'info, TokenInterface>,
+    pub token_program_des: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SwapSol<'info> {
+    // Config PDA
+    #[account( seeds = [b"config"], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    // User's token account for the pool's other token, must be owned by the user
+    #[account(mut, has_one = owner)]
+    pub user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // Temporary wSOL account, created and closed within the instruction
+    /// CHECK: created by the instruction and closed before it returns
+    #[account(mut, seeds = [b"wsol", owner.key().as_ref()], bump)]
+    pub user_wsol: AccountInfo<'info>,
+
+    // Pool account, re-derived from its mint pair
+    #[account(mut, seeds = [b"pool", pool.token0.as_ref(), pool.token1.as_ref(), pool.fee_tier.as_ref()], bump = pool.bump)]
+    pub pool: Box<Account<'info, Pool>>,
+
+    // Pool authority PDA
+    /// CHECK: authority so 1 acc pass in can derive all other PDAs
+    #[account(seeds=[b"authority", pool.key().as_ref()], bump)]
+    pub pool_authority: AccountInfo<'info>,
+
+    // Price oracle of the pool
+    #[account(mut, seeds = [b"observations", pool.key().as_ref()], bump)]
+    pub observations: AccountLoader<'info, Observations>,
+
+    // Source vault for the swap, one of the two vaults must hold wSOL and the other the user's token
+    #[account(
+        mut,
+        constraint = pool.is_vault(&vault_src.key()) @ ErrorCode::InvalidVault
+    )]
+    pub vault_src: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // Destination vault for the swap, must be the pool's other vault
+    #[account(
+        mut,
+        constraint = pool.is_vault(&vault_des.key()) @ ErrorCode::InvalidVault,
+        constraint = vault_des.key() != vault_src.key() @ ErrorCode::InvalidVault
+    )]
+    pub vault_des: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // Mints of the source and destination vaults, owned by their token programs
+    #[account(address = vault_src.mint, mint::token_program = token_program_src)]
+    pub mint_src: Box<InterfaceAccount<'info, Mint>>,
+    #[account(address = vault_des.mint, mint::token_program = token_program_des)]
+    pub mint_des: Box<InterfaceAccount<'info, Mint>>,
+
+    pub token_program: Program<'info, Token>,
+    pub token_program_src: Interface<'info, TokenInterface>,
+    pub token_program_des: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
+// Accounts a swap moves tokens through, shared by the token and native SOL instructions
+pub struct SwapAccounts<'a, 'info> {
+    pub config: &'a Config,
+    pub owner: AccountInfo<'info>,
+    pub user_src: AccountInfo<'info>,
+    pub user_src_amount: u64,
+    pub user_des: AccountInfo<'info>,
+    pub pool: &'a mut Account<'info, Pool>,
+    pub pool_authority: AccountInfo<'info>,
+    pub pool_authority_bump: u8,
+    pub observations: &'a AccountLoader<'info, Observations>,
+    pub vault_src: &'a mut InterfaceAccount<'info, TokenAccount>,
+    pub vault_des: &'a InterfaceAccount<'info, TokenAccount>,
+    pub mint_src: &'a InterfaceAccount<'info, Mint>,
+    pub mint_des: &'a InterfaceAccount<'info, Mint>,
+    pub token_program_src: AccountInfo<'info>,
+    pub token_program_des: AccountInfo<'info>,
+}
+
+impl<'info> Swap<'info> {
+    fn swap_accounts(&mut self, pool_authority_bump: u8) -> SwapAccounts<'_, 'info> {
+        SwapAccounts {
+            config: &self.config,
+            owner: self.owner.to_account_info(),
+            user_src: self.user_ata_src.to_account_info(),
+            user_src_amount: self.user_ata_src.amount,
+            user_des: self.user_ata_des.to_account_info(),
+            pool: &mut self.pool,
+            pool_authority: self.pool_authority.to_account_info(),
+            pool_authority_bump,
+            observations: &self.observations,
+            vault_src: &mut self.vault_src,
+            vault_des: &self.vault_des,
+            mint_src: &self.mint_src,
+            mint_des: &self.mint_des,
+            token_program_src: self.token_program_src.to_account_info(),
+            token_program_des: self.token_program_des.to_account_info(),
+        }
+    }
+}
+
+impl<'info> SwapSol<'info> {
+    // Whether SOL is swapped in rather than out, the other side must be the user's token
+    fn sol_in(&self) -> Result<bool> {
+        let sol_in = self.mint_src.key() == native_mint::ID;
+        let (native, other) = if sol_in {
+            (&self.mint_src, &self.mint_des)
+        } else {
+            (&self.mint_des, &self.mint_src)
+        };
+        require!(
+            native.key() == native_mint::ID && other.key() == self.user_ata.mint,
+            ErrorCode::InvalidNativeMint
+        );
+
+        Ok(sol_in)
+    }
+
+    fn wrap_sol(&self, amount: u64, wsol_bump: u8) -> Result<()> {
+        let native = if self.mint_src.key() == native_mint::ID {
+            &self.mint_src
+        } else {
+            &self.mint_des
+        };
+
+        wrap_sol(
+            &self.owner.to_account_info(),
+            &self.user_wsol,
+            wsol_bump,
+            &native.to_account_info(),
+            amount,
+            &self.system_program.to_account_info(),
+            &self.token_program.to_account_info(),
+        )
+    }
+
+    fn unwrap_sol(&self) -> Result<()> {
+        unwrap_sol(
+            &self.owner.to_account_info(),
+            &self.user_wsol,
+            &self.token_program.to_account_info(),
+        )
+    }
+
+    fn swap_accounts(&mut self, wrapped: u64, pool_authority_bump: u8) -> SwapAccounts<'_, 'info> {
+        let user_ata = self.user_ata.to_account_info();
+        let (user_src, user_src_amount, user_des) = if self.mint_src.key() == native_mint::ID {
+            (self.user_wsol.clone(), wrapped, user_ata)
+        } else {
+            (user_ata, self.user_ata.amount, self.user_wsol.clone())
+        };
+
+        SwapAccounts {
+            config: &self.config,
+            owner: self.owner.to_account_info(),
+            user_src,
+            user_src_amount,
+            user_des,
+            pool: &mut self.pool,
+            pool_authority: self.pool_authority.to_account_info(),
+            pool_authority_bump,
+            observations: &self.observations,
+            vault_src: &mut self.vault_src,
+            vault_des: &self.vault_des,
+            mint_src: &self.mint_src,
+            mint_des: &self.mint_des,
+            token_program_src: self.token_program_src.to_account_info(),
+            token_program_des: self.token_program_des.to_account_info(),
+        }
+    }
+}
+
 #[event]
 pub struct SwapEvent {
     pub owner: Pubkey,
@@ -63,142 +261,281 @@ pub fn swap_exact_input(
     input_amount: u64,
     min_output_amount: u64,
 ) -> Result<()> {
+    // Refresh the oracle price of an oracle-pegged pool if its price account was passed
+    ctx.accounts
+        .pool
+        .load_oracle_price(ctx.remaining_accounts)?;
+
+    let mut accounts = ctx.accounts.swap_accounts(ctx.bumps.pool_authority);
+    exact_input(&mut accounts, input_amount, min_output_amount)
+}
+
+pub fn swap_exact_output(
+    ctx: Context<Swap>,
+    output_amount: u64,
+    max_input_amount: u64,
+) -> Result<()> {
+    // Refresh the oracle price of an oracle-pegged pool if its price account was passed
+    ctx.accounts
+        .pool
+        .load_oracle_price(ctx.remaining_accounts)?;
+
+    let mut accounts = ctx.accounts.swap_accounts(ctx.bumps.pool_authority);
+    exact_output(&mut accounts, output_amount, max_input_amount)
+}
+
+pub fn swap_exact_input_with_deadline(
+    ctx: Context<Swap>,
+    input_amount: u64,
+    min_output_amount: u64,
+    deadline: i64,
+) -> Result<()> {
+    require_not_expired(deadline)?;
+    swap_exact_input(ctx, input_amount, min_output_amount)
+}
+
+pub fn swap_exact_output_with_deadline(
+    ctx: Context<Swap>,
+    output_amount: u64,
+    max_input_amount: u64,
+    deadline: i64,
+) -> Result<()> {
+    require_not_expired(deadline)?;
+    swap_exact_output(ctx, output_amount, max_input_amount)
+}
+
+pub fn swap_exact_input_sol(
+    ctx: Context<SwapSol>,
+    input_amount: u64,
+    min_output_amount: u64,
+) -> Result<()> {
+    let accounts = ctx.accounts;
+    // Refresh the oracle price of an oracle-pegged pool if its price account was passed
+    accounts.pool.load_oracle_price(ctx.remaining_accounts)?;
+
+    // Wrap the input if it is SOL, otherwise create an empty account to receive the output
+    let wrapped = if accounts.sol_in()? { input_amount } else { 0 };
+    accounts.wrap_sol(wrapped, ctx.bumps.user_wsol)?;
+
+    let mut swap_accounts = accounts.swap_accounts(wrapped, ctx.bumps.pool_authority);
+    exact_input(&mut swap_accounts, input_amount, min_output_amount)?;
+
+    accounts.unwrap_sol()
+}
+
+pub fn swap_exact_output_sol(
+    ctx: Context<SwapSol>,
+    output_amount: u64,
+    max_input_amount: u64,
+) -> Result<()> {
+    let accounts = ctx.accounts;
+    // Refresh the oracle price of an oracle-pegged pool if its price account was passed
+    accounts.pool.load_oracle_price(ctx.remaining_accounts)?;
+
+    // Wrap the most the swap may take if the input is SOL, the remainder is returned on unwrap
+    let wrapped = if accounts.sol_in()? {
+        max_input_amount
+    } else {
+        0
+    };
+    accounts.wrap_sol(wrapped, ctx.bumps.user_wsol)?;
+
+    let mut swap_accounts = accounts.swap_accounts(wrapped, ctx.bumps.pool_authority);
+    exact_output(&mut swap_accounts, output_amount, max_input_amount)?;
+
+    accounts.unwrap_sol()
+}
+
+fn exact_input(
+    accounts: &mut SwapAccounts,
+    input_amount: u64,
+    min_output_amount: u64,
+) -> Result<()> {
+    // Lock the pool against re-entry through the token transfers
+    lock_pool(accounts.pool)?;
+
+    // Transfer the input first, pricing the swap on the amount the vault actually received
+    let amount_in = transfer_input(accounts, input_amount)?;
+
     // Calculate the output amount based on the input
-    let amount_out = get_amount_out(
-        &ctx.accounts.config,
-        input_amount,
-        ctx.accounts.vault_src.amount,
-        ctx.accounts.vault_des.amount,
-    )?;
+    let amount_out = get_amount_out(accounts.pool, &accounts.vault_src.key(), amount_in)?;
 
-    // Ensure the output amount meets the minimum required
+    // Ensure the output amount received by the user, net of transfer fees, meets the minimum required
+    let amount_received = amount_out - get_transfer_fee(accounts.mint_des, amount_out)?;
     require!(
-        amount_out >= min_output_amount,
+        amount_received >= min_output_amount,
         ErrorCode::InsufficientOutputAmount,
     );
 
     // Execute the swap
-    swap(ctx, input_amount, amount_out)?;
+    swap(accounts, amount_in, amount_out)?;
 
+    accounts.pool.unlock();
     Ok(())
 }
 
-pub fn swap_exact_output(
-    ctx: Context<Swap>,
+fn exact_output(
+    accounts: &mut SwapAccounts,
     output_amount: u64,
     max_input_amount: u64,
 ) -> Result<()> {
+    // Lock the pool against re-entry through the token transfers
+    lock_pool(accounts.pool)?;
+
+    // Gross up the output so the user receives it net of transfer fees
+    let amount_out = output_amount
+        .checked_add(get_transfer_inverse_fee(accounts.mint_des, output_amount)?)
+        .ok_or(ErrorCode::CalculationFailure)?;
+
     // Calculate the required input amount to get the desired output
-    let amount_in = get_amount_in(
-        &ctx.accounts.config,
-        output_amount,
-        ctx.accounts.vault_src.amount,
-        ctx.accounts.vault_des.amount,
-    )?;
+    let amount_in = get_amount_in(accounts.pool, &accounts.vault_src.key(), amount_out)?;
+
+    // Gross up the input so the vault receives it net of transfer fees
+    let input_amount = amount_in
+        .checked_add(get_transfer_inverse_fee(accounts.mint_src, amount_in)?)
+        .ok_or(ErrorCode::CalculationFailure)?;
 
     // Ensure the input amount does not exceed the maximum allowed
     require!(
-        amount_in <= max_input_amount,
+        input_amount <= max_input_amount,
+        ErrorCode::InsufficientInputAmount,
+    );
+
+    // Transfer the input and ensure the vault received what the swap requires
+    let amount_received = transfer_input(accounts, input_amount)?;
+    require!(
+        amount_received >= amount_in,
         ErrorCode::InsufficientInputAmount,
     );
 
     // Execute the swap
-    swap(ctx, amount_in, output_amount)?;
+    swap(accounts, amount_received, amount_out)?;
 
+    accounts.pool.unlock();
     Ok(())
 }
 
-fn get_amount_out(
-    config: &Config,
-    amount_in: u64,
-    reserve_in: u64,
-    reserve_out: u64,
-) -> Result<u64> {
+/// Returns the output of swapping `amount_in` out of the given source vault along the pool's curve, net of the swap fee.
+pub fn get_amount_out(pool: &Pool, vault_src: &Pubkey, amount_in: u64) -> Result<u64> {
+    let (reserve_in, reserve_out) = pool.swap_reserves(vault_src);
+
     // Ensure there is sufficient liquidity in both reserves
     require!(
         reserve_in > 0 && reserve_out > 0,
         ErrorCode::InsufficientLiquidity,
     );
 
-    // Calculate output amount with fee applied
-    let amount_in_with_fee = amount_in as u128 * (BASIS_POINTS - config.fee) as u128;
-    let numerator = amount_in_with_fee * reserve_out as u128;
-    let denominator = reserve_in as u128 * BASIS_POINTS as u128 + amount_in_with_fee;
-
-    Ok((numerator / denominator) as u64)
+    pool.swap_curve()?.swap_exact_in(
+        amount_in,
+        reserve_in,
+        reserve_out,
+        *vault_src == pool.vault0,
+    )
 }
 
-fn get_amount_in(
-    config: &Config,
-    amount_out: u64,
-    reserve_in: u64,
-    reserve_out: u64,
-) -> Result<u64> {
+/// Returns the input into the given source vault, including the swap fee, required to take `amount_out` along the pool's curve.
+pub fn get_amount_in(pool: &Pool, vault_src: &Pubkey, amount_out: u64) -> Result<u64> {
+    let (reserve_in, reserve_out) = pool.swap_reserves(vault_src);
+
     // Ensure there is sufficient liquidity in both reserves
     require!(
         reserve_in > 0 && reserve_out > 0,
         ErrorCode::InsufficientLiquidity,
     );
 
-    // Calculate input amount required to get the desired output
-    let numerator = reserve_in as u128 * amount_out as u128 * BASIS_POINTS as u128;
-    let denominator =
-        (reserve_out as u128 - amount_out as u128) * (BASIS_POINTS - config.fee) as u128;
-
-    Ok((numerator / denominator + 1) as u64)
+    pool.swap_curve()?.swap_exact_out(
+        amount_out,
+        reserve_in,
+        reserve_out,
+        *vault_src == pool.vault0,
+    )
 }
 
-fn swap(ctx: Context<Swap>, input_amount: u64, output_amount: u64) -> Result<()> {
-    // Ensure valid amounts for swap
-    require!(output_amount > 0, ErrorCode::InsufficientOutputAmount,);
+// Transfers the input from the user to the vault, returning the amount the vault received
+pub fn transfer_input(accounts: &mut SwapAccounts, input_amount: u64) -> Result<u64> {
+    // Ensure trading is not paused and no flash loan is open
+    accounts.pool.require_not_paused(accounts.config)?;
+    accounts.pool.require_no_flash_loan()?;
+
+    // Ensure valid input amount for swap
     require!(input_amount > 0, ErrorCode::InsufficientInputAmount,);
     require!(
-        output_amount < ctx.accounts.vault_des.amount,
-        ErrorCode::InsufficientLiquidity,
-    );
-    require!(
-        input_amount < ctx.accounts.user_ata_src.amount,
+        input_amount <= accounts.user_src_amount,
         ErrorCode::InsufficientUserBalance,
     );
 
-    let pool: &Box<Account<Pool>> = &ctx.accounts.pool;
-    let pool_key = pool.key();
-    let pool_sign = &[b"authority", pool_key.as_ref(), &[ctx.bumps.pool_authority]];
+    let vault_src_before = accounts.vault_src.amount;
 
     // Transfer tokens from the user's source account to the vault
-    transfer(
+    transfer_checked(
         CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.user_ata_src.to_account_info(),
-                to: ctx.accounts.vault_src.to_account_info(),
-                authority: ctx.accounts.owner.to_account_info(),
+            accounts.token_program_src.clone(),
+            TransferChecked {
+                from: accounts.user_src.clone(),
+                mint: accounts.mint_src.to_account_info(),
+                to: accounts.vault_src.to_account_info(),
+                authority: accounts.owner.clone(),
             },
         ),
         input_amount,
+        accounts.mint_src.decimals,
     )?;
 
+    accounts.vault_src.reload()?;
+    Ok(accounts.vault_src.amount - vault_src_before)
+}
+
+// Transfers the output from the vault to the user and records the swap
+pub fn swap(accounts: &mut SwapAccounts, input_amount: u64, output_amount: u64) -> Result<()> {
+    let (_, reserve_out) = accounts.pool.swap_reserves(&accounts.vault_src.key());
+
+    // Ensure valid amounts for swap
+    require!(output_amount > 0, ErrorCode::InsufficientOutputAmount,);
+    require!(input_amount > 0, ErrorCode::InsufficientInputAmount,);
+    require!(
+        output_amount < reserve_out,
+        ErrorCode::InsufficientLiquidity,
+    );
+
+    let pool_key = accounts.pool.key();
+    let pool_sign = &[
+        b"authority",
+        pool_key.as_ref(),
+        &[accounts.pool_authority_bump],
+    ];
+
     // Transfer tokens from the vault to the user's destination account
-    transfer(
+    transfer_checked(
         CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.vault_des.to_account_info(),
-                to: ctx.accounts.user_ata_des.to_account_info(),
-                authority: ctx.accounts.pool_authority.to_account_info(),
+            accounts.token_program_des.clone(),
+            TransferChecked {
+                from: accounts.vault_des.to_account_info(),
+                mint: accounts.mint_des.to_account_info(),
+                to: accounts.user_des.clone(),
+                authority: accounts.pool_authority.clone(),
             },
         )
         .with_signer(&[pool_sign]),
         output_amount,
+        accounts.mint_des.decimals,
+    )?;
+
+    // Update pool reserves
+    let vault_src = accounts.vault_src.key();
+    accounts.pool.update_swap_reserves(
+        &vault_src,
+        input_amount,
+        output_amount,
+        &mut *accounts.observations.load_mut()?,
     )?;
 
     // Emit event after successful swap
     emit!(SwapEvent {
-        owner: ctx.accounts.owner.key(),
+        owner: accounts.owner.key(),
         input_amount,
         output_amount,
-        vault_src: ctx.accounts.vault_src.key(),
-        vault_des: ctx.accounts.vault_des.key(),
+        vault_src: accounts.vault_src.key(),
+        vault_des: accounts.vault_des.key(),
     });
 
     Ok(())
@@ -1,10 +1,14 @@
 use anchor_lang::prelude::*;
 use instructions::*;
 
+pub mod concentrated;
 pub mod constant;
+pub mod curve;
 pub mod error;
 pub mod instructions;
+pub mod oracle;
 pub mod state;
+pub mod utils;
 
 declare_id!("4sRbFuajHVG181psKiK7G2JBSzbcvVD9RBVbo72DE9TQ");
 
@@ -12,9 +16,24 @@ declare_id!("4sRbFuajHVG181psKiK7G2JBSzbcvVD9RBVbo72DE9TQ");
 pub mod amm {
     use super::*;
 
-    /// Initializes the AMM with the provided fee recipient and fee amount.
-    pub fn initialize(ctx: Context<Initialize>, fee_to: Pubkey, fee: u64) -> Result<()> {
-        instructions::initialize(ctx, fee_to, fee)
+    /// Initializes the AMM with the provided fee recipient.
+    pub fn initialize(ctx: Context<Initialize>, fee_to: Pubkey) -> Result<()> {
+        instructions::initialize(ctx, fee_to)
+    }
+
+    /// Proposes a new owner, who must accept the transfer.
+    pub fn propose_owner(ctx: Context<ProposeOwner>, new_owner: Pubkey) -> Result<()> {
+        instructions::propose_owner(ctx, new_owner)
+    }
+
+    /// Accepts a proposed ownership transfer, signed by the pending owner.
+    pub fn accept_owner(ctx: Context<AcceptOwner>) -> Result<()> {
+        instructions::accept_owner(ctx)
+    }
+
+    /// Cancels a proposed ownership transfer.
+    pub fn cancel_owner_transfer(ctx: Context<CancelOwnerTransfer>) -> Result<()> {
+        instructions::cancel_owner_transfer(ctx)
     }
 
     /// Updates the fee recipient address.
@@ -22,14 +41,96 @@ pub mod amm {
         instructions::set_fee_to(ctx, new_fee_to)
     }
 
-    /// Updates the fee amount.
-    pub fn set_fee(ctx: Context<SetFee>, new_fee: u64) -> Result<()> {
-        instructions::set_fee(ctx, new_fee)
+    /// Creates a fee tier pools can be created with.
+    pub fn create_fee_tier(ctx: Context<CreateFeeTier>, fee: u64) -> Result<()> {
+        instructions::create_fee_tier(ctx, fee)
+    }
+
+    /// Deletes a fee tier, existing pools keep their fee.
+    pub fn delete_fee_tier(ctx: Context<DeleteFeeTier>) -> Result<()> {
+        instructions::delete_fee_tier(ctx)
+    }
+
+    /// Updates the swap fee of a single pool to that of the given fee tier.
+    pub fn set_pool_fee(ctx: Context<SetPoolFee>) -> Result<()> {
+        instructions::set_pool_fee(ctx)
+    }
+
+    /// Turns the protocol fee on or off.
+    pub fn set_fee_on(ctx: Context<SetFeeOn>, fee_on: bool) -> Result<()> {
+        instructions::set_fee_on(ctx, fee_on)
     }
 
-    /// Creates a new liquidity pool.
-    pub fn create_pool(ctx: Context<CreatePool>) -> Result<()> {
-        instructions::create_pool(ctx)
+    /// Updates the fee charged on flash loans, in basis points.
+    pub fn set_flash_loan_fee(
+        ctx: Context<SetFlashLoanFee>,
+        new_flash_loan_fee: u64,
+    ) -> Result<()> {
+        instructions::set_flash_loan_fee(ctx, new_flash_loan_fee)
+    }
+
+    /// Updates the account allowed to pause trading.
+    pub fn set_pause_authority(
+        ctx: Context<SetPauseAuthority>,
+        new_pause_authority: Pubkey,
+    ) -> Result<()> {
+        instructions::set_pause_authority(ctx, new_pause_authority)
+    }
+
+    /// Pauses or unpauses swaps and deposits on every pool.
+    pub fn set_paused(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+        instructions::set_paused(ctx, paused)
+    }
+
+    /// Pauses or unpauses swaps and deposits on a single pool.
+    pub fn set_pool_paused(ctx: Context<SetPoolPaused>, paused: bool) -> Result<()> {
+        instructions::set_pool_paused(ctx, paused)
+    }
+
+    /// Pauses or unpauses swaps and deposits on a single concentrated pool.
+    pub fn set_concentrated_pool_paused(
+        ctx: Context<SetConcentratedPoolPaused>,
+        paused: bool,
+    ) -> Result<()> {
+        instructions::set_concentrated_pool_paused(ctx, paused)
+    }
+
+    /// Sets whether anyone may create pools, and the lamports charged for creating one.
+    pub fn set_pool_creation(
+        ctx: Context<SetPoolCreation>,
+        permissionless: bool,
+        creation_fee: u64,
+    ) -> Result<()> {
+        instructions::set_pool_creation(ctx, permissionless, creation_fee)
+    }
+
+    /// Replaces the Token-2022 extension allow-list checked when creating pools.
+    pub fn set_extension_allow_list(
+        ctx: Context<SetExtensionAllowList>,
+        allowed_extensions: u64,
+        freeze_authority_allowed: bool,
+    ) -> Result<()> {
+        instructions::set_extension_allow_list(ctx, allowed_extensions, freeze_authority_allowed)
+    }
+
+    /// Creates a new liquidity pool for a mint pair and fee tier, pricing swaps along the given curve.
+    pub fn create_pool(ctx: Context<CreatePool>, curve: state::PoolCurve) -> Result<()> {
+        instructions::create_pool(ctx, curve)
+    }
+
+    /// Moves a StableSwap or oracle-pegged pool's amplification coefficient linearly to `amp_target` by `amp_ramp_end`.
+    pub fn ramp_amp(ctx: Context<RampAmp>, amp_target: u64, amp_ramp_end: i64) -> Result<()> {
+        instructions::ramp_amp(ctx, amp_target, amp_ramp_end)
+    }
+
+    /// Stops a StableSwap or oracle-pegged pool's amplification ramp at its current coefficient.
+    pub fn stop_ramp_amp(ctx: Context<RampAmp>) -> Result<()> {
+        instructions::stop_ramp_amp(ctx)
+    }
+
+    /// Refreshes an oracle-pegged pool's price from its Pyth price account.
+    pub fn update_oracle_price(ctx: Context<UpdateOraclePrice>) -> Result<()> {
+        instructions::update_oracle_price(ctx)
     }
 
     /// Adds liquidity to the pool, specifying desired and minimum amounts.
@@ -59,6 +160,95 @@ pub mod amm {
         instructions::remove_liquidity(ctx, liquidity, amount0_min, amount1_min)
     }
 
+    /// Adds liquidity to the pool, failing once the unix timestamp `deadline` has passed.
+    pub fn add_liquidity_with_deadline(
+        ctx: Context<LiquidityOperation>,
+        amount0_desired: u64,
+        amount1_desired: u64,
+        amount0_min: u64,
+        amount1_min: u64,
+        deadline: i64,
+    ) -> Result<()> {
+        instructions::add_liquidity_with_deadline(
+            ctx,
+            amount0_desired,
+            amount1_desired,
+            amount0_min,
+            amount1_min,
+            deadline,
+        )
+    }
+
+    /// Removes liquidity from the pool, failing once the unix timestamp `deadline` has passed.
+    pub fn remove_liquidity_with_deadline(
+        ctx: Context<LiquidityOperation>,
+        liquidity: u64,
+        amount0_min: u64,
+        amount1_min: u64,
+        deadline: i64,
+    ) -> Result<()> {
+        instructions::remove_liquidity_with_deadline(
+            ctx,
+            liquidity,
+            amount0_min,
+            amount1_min,
+            deadline,
+        )
+    }
+
+    /// Adds liquidity to a pool with a native SOL side, wrapping the SOL deposited.
+    pub fn add_liquidity_sol(
+        ctx: Context<LiquidityOperationSol>,
+        amount0_desired: u64,
+        amount1_desired: u64,
+        amount0_min: u64,
+        amount1_min: u64,
+    ) -> Result<()> {
+        instructions::add_liquidity_sol(
+            ctx,
+            amount0_desired,
+            amount1_desired,
+            amount0_min,
+            amount1_min,
+        )
+    }
+
+    /// Removes liquidity from a pool with a native SOL side, unwrapping the SOL withdrawn.
+    pub fn remove_liquidity_sol(
+        ctx: Context<LiquidityOperationSol>,
+        liquidity: u64,
+        amount0_min: u64,
+        amount1_min: u64,
+    ) -> Result<()> {
+        instructions::remove_liquidity_sol(ctx, liquidity, amount0_min, amount1_min)
+    }
+
+    /// Forces the pool's tracked reserves to match its vault balances.
+    pub fn sync(ctx: Context<SyncReserves>) -> Result<()> {
+        instructions::sync(ctx)
+    }
+
+    /// Sends vault balances in excess of the pool's tracked reserves to the given accounts.
+    pub fn skim(ctx: Context<SkimReserves>) -> Result<()> {
+        instructions::skim(ctx)
+    }
+
+    /// Grows the number of observations the pool's oracle keeps.
+    pub fn increase_observation_cardinality(
+        ctx: Context<IncreaseObservationCardinality>,
+        cardinality_next: u16,
+    ) -> Result<()> {
+        instructions::increase_observation_cardinality(ctx, cardinality_next)
+    }
+
+    /// Returns the pool's cumulative prices and liquidity at each of the given ages.
+    pub fn observe(
+        ctx: Context<Observe>,
+        seconds_ago: Vec<u32>,
+    ) -> Result<Vec<state::Cumulatives>> {
+        instructions::observe(ctx, seconds_ago)
+    }
+
     /// Swaps an exact input amount for a minimum output amount.
     pub fn swap_exact_input(
         ctx: Context<Swap>,
@@ -76,4 +266,197 @@ pub mod amm {
     ) -> Result<()> {
         instructions::swap_exact_output(ctx, output_amount, max_input_amount)
     }
+
+    /// Swaps an exact input amount, failing once the unix timestamp `deadline` has passed.
+    pub fn swap_exact_input_with_deadline(
+        ctx: Context<Swap>,
+        input_amount: u64,
+        min_output_amount: u64,
+        deadline: i64,
+    ) -> Result<()> {
+        instructions::swap_exact_input_with_deadline(ctx, input_amount, min_output_amount, deadline)
+    }
+
+    /// Swaps for an exact output amount, failing once the unix timestamp `deadline` has passed.
+    pub fn swap_exact_output_with_deadline(
+        ctx: Context<Swap>,
+        output_amount: u64,
+        max_input_amount: u64,
+        deadline: i64,
+    ) -> Result<()> {
+        instructions::swap_exact_output_with_deadline(
+            ctx,
+            output_amount,
+            max_input_amount,
+            deadline,
+        )
+    }
+
+    /// Sends the requested output first and calls the borrower's program, which must pay the pool back.
+    pub fn flash_swap<'info>(
+        ctx: Context<'_, '_, '_, 'info, FlashSwap<'info>>,
+        amount0_out: u64,
+        amount1_out: u64,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::flash_swap(ctx, amount0_out, amount1_out, data)
+    }
+
+    /// Lends tokens out of one of the pool's vaults, to be repaid with the fee by `flash_loan_end`.
+    pub fn flash_loan_begin(ctx: Context<FlashLoanBegin>, amount: u64) -> Result<()> {
+        instructions::flash_loan_begin(ctx, amount)
+    }
+
+    /// Ensures the open flash loan was repaid and closes it.
+    pub fn flash_loan_end(ctx: Context<FlashLoanEnd>) -> Result<()> {
+        instructions::flash_loan_end(ctx)
+    }
+
+    /// Swaps an exact input amount along a path of pools given in the remaining accounts.
+    pub fn swap_route_exact_input<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SwapRoute<'info>>,
+        input_amount: u64,
+        min_output_amount: u64,
+    ) -> Result<()> {
+        instructions::swap_route_exact_input(ctx, input_amount, min_output_amount)
+    }
+
+    /// Swaps for an exact output amount along a path of pools given in the remaining accounts.
+    pub fn swap_route_exact_output<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SwapRoute<'info>>,
+        output_amount: u64,
+        max_input_amount: u64,
+    ) -> Result<()> {
+        instructions::swap_route_exact_output(ctx, output_amount, max_input_amount)
+    }
+
+    /// Swaps an exact input amount for a minimum output amount, wrapping or unwrapping native SOL.
+    pub fn swap_exact_input_sol(
+        ctx: Context<SwapSol>,
+        input_amount: u64,
+        min_output_amount: u64,
+    ) -> Result<()> {
+        instructions::swap_exact_input_sol(ctx, input_amount, min_output_amount)
+    }
+
+    /// Swaps to obtain an exact output amount, wrapping or unwrapping native SOL.
+    pub fn swap_exact_output_sol(
+        ctx: Context<SwapSol>,
+        output_amount: u64,
+        max_input_amount: u64,
+    ) -> Result<()> {
+        instructions::swap_exact_output_sol(ctx, output_amount, max_input_amount)
+    }
+
+    /// Creates a concentrated liquidity pool for a mint pair and fee tier, starting at a Q64.64 sqrt price.
+    pub fn create_concentrated_pool(
+        ctx: Context<CreateConcentratedPool>,
+        tick_spacing: u16,
+        sqrt_price: u128,
+    ) -> Result<()> {
+        instructions::create_concentrated_pool(ctx, tick_spacing, sqrt_price)
+    }
+
+    /// Creates the tick array of a concentrated pool starting at `start_tick`.
+    pub fn initialize_tick_array(ctx: Context<InitializeTickArray>, start_tick: i32) -> Result<()> {
+        instructions::initialize_tick_array(ctx, start_tick)
+    }
+
+    /// Opens an empty position between two ticks of a concentrated pool.
+    pub fn open_position(
+        ctx: Context<OpenPosition>,
+        tick_lower: i32,
+        tick_upper: i32,
+    ) -> Result<()> {
+        instructions::open_position(ctx, tick_lower, tick_upper)
+    }
+
+    /// Adds liquidity to a position, paying at most the given amounts.
+    pub fn increase_liquidity(
+        ctx: Context<ModifyPosition>,
+        liquidity: u128,
+        amount0_max: u64,
+        amount1_max: u64,
+    ) -> Result<()> {
+        instructions::increase_liquidity(ctx, liquidity, amount0_max, amount1_max)
+    }
+
+    /// Removes liquidity from a position, receiving at least the given amounts.
+    pub fn decrease_liquidity(
+        ctx: Context<ModifyPosition>,
+        liquidity: u128,
+        amount0_min: u64,
+        amount1_min: u64,
+    ) -> Result<()> {
+        instructions::decrease_liquidity(ctx, liquidity, amount0_min, amount1_min)
+    }
+
+    /// Sends a position the fees it has earned.
+    pub fn collect_fees(ctx: Context<ModifyPosition>) -> Result<()> {
+        instructions::collect_fees(ctx)
+    }
+
+    /// Swaps an exact input amount on a concentrated pool, stopping at the sqrt price limit if one is given.
+    pub fn swap_concentrated_exact_input<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SwapConcentrated<'info>>,
+        input_amount: u64,
+        min_output_amount: u64,
+        sqrt_price_limit: u128,
+    ) -> Result<()> {
+        instructions::swap_concentrated_exact_input(
+            ctx,
+            input_amount,
+            min_output_amount,
+            sqrt_price_limit,
+        )
+    }
+
+    /// Swaps for an exact output amount on a concentrated pool, stopping at the sqrt price limit if one is given.
+    pub fn swap_concentrated_exact_output<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SwapConcentrated<'info>>,
+        output_amount: u64,
+        max_input_amount: u64,
+        sqrt_price_limit: u128,
+    ) -> Result<()> {
+        instructions::swap_concentrated_exact_output(
+            ctx,
+            output_amount,
+            max_input_amount,
+            sqrt_price_limit,
+        )
+    }
+
+    /// Swaps an exact input amount on a concentrated pool, failing once the unix timestamp `deadline` has passed.
+    pub fn swap_concentrated_exact_input_with_deadline<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SwapConcentrated<'info>>,
+        input_amount: u64,
+        min_output_amount: u64,
+        sqrt_price_limit: u128,
+        deadline: i64,
+    ) -> Result<()> {
+        instructions::swap_concentrated_exact_input_with_deadline(
+            ctx,
+            input_amount,
+            min_output_amount,
+            sqrt_price_limit,
+            deadline,
+        )
+    }
+
+    /// Swaps for an exact output amount on a concentrated pool, failing once the unix timestamp `deadline` has passed.
+    pub fn swap_concentrated_exact_output_with_deadline<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SwapConcentrated<'info>>,
+        output_amount: u64,
+        max_input_amount: u64,
+        sqrt_price_limit: u128,
+        deadline: i64,
+    ) -> Result<()> {
+        instructions::swap_concentrated_exact_output_with_deadline(
+            ctx,
+            output_amount,
+            max_input_amount,
+            sqrt_price_limit,
+            deadline,
+        )
+    }
 }
@@ -0,0 +1,61 @@
+use crate::constant::PYTH_PROGRAM_ID;
+use crate::error::ErrorCode;
+use anchor_lang::prelude::*;
+
+// Header of a Pyth price account: magic number, version and account type
+const PYTH_MAGIC: u32 = 0xa1b2c3d4;
+const PYTH_VERSION: u32 = 2;
+const PYTH_PRICE_ACCOUNT: u32 = 3;
+
+// Status of an aggregate price that is safe to use
+const PYTH_STATUS_TRADING: u32 = 1;
+
+// Byte offsets of the fields read from a Pyth price account
+const MAGIC_OFFSET: usize = 0;
+const VERSION_OFFSET: usize = 4;
+const ACCOUNT_TYPE_OFFSET: usize = 8;
+const EXPONENT_OFFSET: usize = 20;
+const PRICE_OFFSET: usize = 208;
+const CONFIDENCE_OFFSET: usize = 216;
+const STATUS_OFFSET: usize = 224;
+const PUBLISH_SLOT_OFFSET: usize = 232;
+const PRICE_ACCOUNT_LEN: usize = 240;
+
+/// Aggregate price of a Pyth price account, `price * 10^exponent` plus or minus `confidence * 10^exponent`.
+#[derive(Clone, Copy, Debug)]
+pub struct PythPrice {
+    pub price: i64,
+    pub confidence: u64,
+    pub exponent: i32,
+    pub publish_slot: u64,
+}
+
+impl PythPrice {
+    /// Reads the aggregate price from a Pyth price account, which must be currently trading.
+    pub fn load(account: &AccountInfo) -> Result<Self> {
+        require_keys_eq!(*account.owner, PYTH_PROGRAM_ID, ErrorCode::InvalidOracle);
+        let data = account.try_borrow_data()?;
+        require!(data.len() >= PRICE_ACCOUNT_LEN, ErrorCode::InvalidOracle);
+
+        let read = |offset: usize, len: usize| &data[offset..offset + len];
+        let read_u32 = |offset| u32::from_le_bytes(read(offset, 4).try_into().unwrap());
+        let read_u64 = |offset| u64::from_le_bytes(read(offset, 8).try_into().unwrap());
+        require!(
+            read_u32(MAGIC_OFFSET) == PYTH_MAGIC
+                && read_u32(VERSION_OFFSET) == PYTH_VERSION
+                && read_u32(ACCOUNT_TYPE_OFFSET) == PYTH_PRICE_ACCOUNT,
+            ErrorCode::InvalidOracle
+        );
+        require!(
+            read_u32(STATUS_OFFSET) == PYTH_STATUS_TRADING,
+            ErrorCode::OracleUnavailable
+        );
+
+        Ok(PythPrice {
+            price: read_u64(PRICE_OFFSET) as i64,
+            confidence: read_u64(CONFIDENCE_OFFSET),
+            exponent: read_u32(EXPONENT_OFFSET) as i32,
+            publish_slot: read_u64(PUBLISH_SLOT_OFFSET),
+        })
+    }
+}
@@ -0,0 +1,347 @@
+use crate::concentrated::{
+    amount0_delta, amount1_delta, compute_swap_step, sqrt_price_at_tick, tick_at_sqrt_price,
+};
+use crate::constant::{MAX_SQRT_PRICE, MAX_TICK, MIN_SQRT_PRICE, MIN_TICK, TICK_ARRAY_SIZE};
+use crate::error::ErrorCode;
+use crate::state::{Config, FeeTier, Tick, TickArray};
+use crate::utils::div_wide;
+use anchor_lang::prelude::*;
+use fixed::types::U64F64;
+
+#[account]
+#[derive(InitSpace)] // Automatically calculates the space required for the struct.
+pub struct ConcentratedPool {
+    pub bump: u8,
+    pub creator: Pubkey,
+    pub token0: Pubkey,
+    pub token1: Pubkey,
+    pub vault0: Pubkey,
+    pub vault1: Pubkey,
+    pub fee_tier: Pubkey,
+    pub fee: u64,
+    pub tick_spacing: u16, // Positions may only start and end on multiples of the spacing
+    pub sqrt_price: u128,  // Square root of the price of token0 in token1 as Q64.64
+    pub tick_current: i32, // Greatest tick at or below the sqrt price
+    pub liquidity: u128,   // Liquidity of the positions in range of the current price
+    pub fee_growth_global0: u128, // Fees in token0 earned per unit of liquidity as Q64.64, wraps on overflow
+    pub fee_growth_global1: u128, // Fees in token1 earned per unit of liquidity as Q64.64, wraps on overflow
+    pub paused: bool,
+    pub unlocked: bool, // Cleared while an instruction is using the pool, rejects nested calls
+}
+
+impl ConcentratedPool {
+    /// Initializes the pool with its creator, two tokens, their vaults, its fee tier, tick spacing and starting price.
+    #[allow(clippy::too_many_arguments)]
+    pub fn initialize(
+        &mut self,
+        bump: u8,
+        creator: Pubkey,
+        token0: Pubkey,
+        token1: Pubkey,
+        vault0: Pubkey,
+        vault1: Pubkey,
+        fee_tier: &Account<FeeTier>,
+        tick_spacing: u16,
+        sqrt_price: u128,
+    ) -> Result<()> {
+        // Ensure token0 is less than token1 to maintain consistent ordering.
+        require!(
+            token0.to_string() < token1.to_string(),
+            ErrorCode::InvalidMintOrder
+        );
+        require!(tick_spacing > 0, ErrorCode::InvalidTickSpacing);
+        require!(
+            (MIN_SQRT_PRICE..MAX_SQRT_PRICE).contains(&sqrt_price),
+            ErrorCode::InvalidSqrtPrice
+        );
+        self.bump = bump;
+        self.creator = creator;
+        self.token0 = token0;
+        self.token1 = token1;
+        self.vault0 = vault0;
+        self.vault1 = vault1;
+        self.fee_tier = fee_tier.key();
+        self.fee = fee_tier.fee;
+        self.tick_spacing = tick_spacing;
+        self.sqrt_price = sqrt_price;
+        self.tick_current = tick_at_sqrt_price(U64F64::from_bits(sqrt_price))?;
+        self.liquidity = 0;
+        self.fee_growth_global0 = 0;
+        self.fee_growth_global1 = 0;
+        self.paused = false;
+        self.unlocked = true;
+        Ok(())
+    }
+
+    /// Returns true if the given account is one of the pool's vaults.
+    pub fn is_vault(&self, vault: &Pubkey) -> bool {
+        *vault == self.vault0 || *vault == self.vault1
+    }
+
+    /// Pauses or unpauses trading on the pool.
+    pub fn set_paused(&mut self, paused: bool) -> Result<()> {
+        self.paused = paused;
+        Ok(())
+    }
+
+    /// Ensures neither the pool nor the whole AMM is paused.
+    pub fn require_not_paused(&self, config: &Config) -> Result<()> {
+        require!(!config.paused && !self.paused, ErrorCode::Paused);
+        Ok(())
+    }
+
+    /// Locks the pool, failing if an instruction further up the call stack already holds it.
+    pub fn lock(&mut self) -> Result<()> {
+        require!(self.unlocked, ErrorCode::Locked);
+        self.unlocked = false;
+        Ok(())
+    }
+
+    /// Unlocks the pool at the end of an instruction.
+    pub fn unlock(&mut self) {
+        self.unlocked = true;
+    }
+
+    /// Ensures `tick` is a valid boundary for a position in this pool.
+    pub fn require_valid_tick(&self, tick: i32) -> Result<()> {
+        sqrt_price_at_tick(tick)?;
+        require!(
+            tick % self.tick_spacing as i32 == 0,
+            ErrorCode::InvalidTickSpacing
+        );
+        Ok(())
+    }
+
+    /// Returns the start of the tick array holding `tick`.
+    pub fn tick_array_start(&self, tick: i32) -> i32 {
+        let ticks_in_array = self.tick_spacing as i32 * TICK_ARRAY_SIZE;
+        tick.div_euclid(ticks_in_array) * ticks_in_array
+    }
+
+    /// Returns the fees earned per unit of liquidity between two initialized ticks, in token0 and token1.
+    pub fn fee_growth_inside(
+        &self,
+        tick_lower: i32,
+        lower: &Tick,
+        tick_upper: i32,
+        upper: &Tick,
+    ) -> (u128, u128) {
+        // Growth outside a tick is on the side away from the current price, flip it when the price is on the other side
+        let below = |global: u128, outside: u128| {
+            if self.tick_current >= tick_lower {
+                outside
+            } else {
+                global.wrapping_sub(outside)
+            }
+        };
+        let above = |global: u128, outside: u128| {
+            if self.tick_current < tick_upper {
+                outside
+            } else {
+                global.wrapping_sub(outside)
+            }
+        };
+        let inside = |global: u128, outside_lower: u128, outside_upper: u128| {
+            global
+                .wrapping_sub(below(global, outside_lower))
+                .wrapping_sub(above(global, outside_upper))
+        };
+
+        (
+            inside(
+                self.fee_growth_global0,
+                lower.fee_growth_outside0,
+                upper.fee_growth_outside0,
+            ),
+            inside(
+                self.fee_growth_global1,
+                lower.fee_growth_outside1,
+                upper.fee_growth_outside1,
+            ),
+        )
+    }
+
+    /// Changes the liquidity of a position between two ticks by `liquidity_delta`, returning the token amounts it
+    /// moves. Amounts are rounded up when adding liquidity and down when removing it.
+    pub fn modify_liquidity(
+        &mut self,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity_delta: i128,
+    ) -> Result<(u64, u64)> {
+        let round_up = liquidity_delta > 0;
+        let liquidity = liquidity_delta.unsigned_abs();
+        let sqrt_price_lower = sqrt_price_at_tick(tick_lower)?;
+        let sqrt_price_upper = sqrt_price_at_tick(tick_upper)?;
+        let sqrt_price = U64F64::from_bits(self.sqrt_price);
+
+        // Below the range the position is all token0, above it all token1, in range it is active
+        let (amount0, amount1) = if self.tick_current < tick_lower {
+            (
+                amount0_delta(sqrt_price_lower, sqrt_price_upper, liquidity, round_up)?,
+                0,
+            )
+        } else if self.tick_current < tick_upper {
+            self.liquidity = self
+                .liquidity
+                .checked_add_signed(liquidity_delta)
+                .ok_or(ErrorCode::CalculationFailure)?;
+            (
+                amount0_delta(sqrt_price, sqrt_price_upper, liquidity, round_up)?,
+                amount1_delta(sqrt_price_lower, sqrt_price, liquidity, round_up)?,
+            )
+        } else {
+            (
+                0,
+                amount1_delta(sqrt_price_lower, sqrt_price_upper, liquidity, round_up)?,
+            )
+        };
+
+        Ok((
+            u64::try_from(amount0).map_err(|_| ErrorCode::CalculationFailure)?,
+            u64::try_from(amount1).map_err(|_| ErrorCode::CalculationFailure)?,
+        ))
+    }
+
+    /// Swaps along the pool's liquidity until `amount` is used up or the price reaches `sqrt_price_limit`, zero
+    /// for the furthest price, crossing initialized ticks on the way. Returns the input, including fees, and the
+    /// output. Every tick array of the pool the swap reaches must be in `tick_arrays`.
+    pub fn swap(
+        &mut self,
+        tick_arrays: &[AccountLoader<TickArray>],
+        amount: u64,
+        exact_input: bool,
+        zero_for_one: bool,
+        sqrt_price_limit: u128,
+    ) -> Result<(u64, u64)> {
+        let sqrt_price_limit = match (sqrt_price_limit, zero_for_one) {
+            (0, true) => MIN_SQRT_PRICE + 1,
+            (0, false) => MAX_SQRT_PRICE - 1,
+            (limit, _) => limit,
+        };
+        require!(
+            if zero_for_one {
+                sqrt_price_limit < self.sqrt_price && sqrt_price_limit > MIN_SQRT_PRICE
+            } else {
+                sqrt_price_limit > self.sqrt_price && sqrt_price_limit < MAX_SQRT_PRICE
+            },
+            ErrorCode::InvalidSqrtPriceLimit
+        );
+        let sqrt_price_limit = U64F64::from_bits(sqrt_price_limit);
+        let tick_spacing = self.tick_spacing as i32;
+
+        let mut amount_remaining = amount;
+        let mut amount_calculated = 0u64;
+        let mut sqrt_price = U64F64::from_bits(self.sqrt_price);
+        let mut fee_growth_global = if zero_for_one {
+            self.fee_growth_global0
+        } else {
+            self.fee_growth_global1
+        };
+
+        while amount_remaining > 0 && sqrt_price != sqrt_price_limit {
+            // Search the tick array from the current tick, or the next one up when moving up
+            let compressed = self.tick_current.div_euclid(tick_spacing);
+            let search_tick = if zero_for_one {
+                compressed * tick_spacing
+            } else {
+                (compressed + 1) * tick_spacing
+            };
+            let start_tick = self.tick_array_start(search_tick);
+            let tick_array = tick_arrays
+                .iter()
+                .find(|tick_array| {
+                    tick_array
+                        .load()
+                        .is_ok_and(|tick_array| tick_array.start_tick == start_tick)
+                })
+                .ok_or(ErrorCode::InvalidTickArray)?;
+            let mut tick_array = tick_array.load_mut()?;
+
+            let (tick_next, initialized) =
+                tick_array.next_initialized_tick(search_tick, self.tick_spacing, zero_for_one)?;
+            let tick_next = tick_next.clamp(MIN_TICK, MAX_TICK);
+            let sqrt_price_next_tick = sqrt_price_at_tick(tick_next)?;
+
+            // Swap up to the next tick or the limit, whichever comes first
+            let sqrt_price_target = if zero_for_one {
+                sqrt_price_next_tick.max(sqrt_price_limit)
+            } else {
+                sqrt_price_next_tick.min(sqrt_price_limit)
+            };
+            let step = compute_swap_step(
+                sqrt_price,
+                sqrt_price_target,
+                self.liquidity,
+                amount_remaining,
+                self.fee,
+                exact_input,
+            )?;
+
+            let amount_in = step.amount_in + step.fee_amount;
+            if exact_input {
+                amount_remaining -= amount_in;
+                amount_calculated = amount_calculated
+                    .checked_add(step.amount_out)
+                    .ok_or(ErrorCode::CalculationFailure)?;
+            } else {
+                amount_remaining -= step.amount_out;
+                amount_calculated = amount_calculated
+                    .checked_add(amount_in)
+                    .ok_or(ErrorCode::CalculationFailure)?;
+            }
+
+            // Share the fee among the liquidity in range
+            if self.liquidity > 0 {
+                let fee_growth = div_wide(0, (step.fee_amount as u128) << 64, self.liquidity)
+                    .ok_or(ErrorCode::CalculationFailure)?;
+                fee_growth_global = fee_growth_global.wrapping_add(fee_growth);
+            }
+
+            if step.sqrt_price_next == sqrt_price_next_tick {
+                // Cross the tick, moving its positions' liquidity in or out of range
+                if initialized {
+                    let (global0, global1) = if zero_for_one {
+                        (fee_growth_global, self.fee_growth_global1)
+                    } else {
+                        (self.fee_growth_global0, fee_growth_global)
+                    };
+                    let liquidity_net = tick_array
+                        .tick_mut(tick_next, self.tick_spacing)?
+                        .cross(global0, global1);
+                    let liquidity_net = if zero_for_one {
+                        -liquidity_net
+                    } else {
+                        liquidity_net
+                    };
+                    self.liquidity = self
+                        .liquidity
+                        .checked_add_signed(liquidity_net)
+                        .ok_or(ErrorCode::CalculationFailure)?;
+                }
+                self.tick_current = if zero_for_one {
+                    tick_next - 1
+                } else {
+                    tick_next
+                };
+            } else if step.sqrt_price_next != sqrt_price {
+                self.tick_current = tick_at_sqrt_price(step.sqrt_price_next)?;
+            }
+            sqrt_price = step.sqrt_price_next;
+        }
+
+        self.sqrt_price = sqrt_price.to_bits();
+        if zero_for_one {
+            self.fee_growth_global0 = fee_growth_global;
+        } else {
+            self.fee_growth_global1 = fee_growth_global;
+        }
+
+        let amount_used = amount - amount_remaining;
+        Ok(if exact_input {
+            (amount_used, amount_calculated)
+        } else {
+            (amount_calculated, amount_used)
+        })
+    }
+}
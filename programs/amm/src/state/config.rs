@@ -1,4 +1,4 @@
-use crate::constant::BASIS_POINTS;
+use crate::constant::{BASIS_POINTS, DEFAULT_ALLOWED_EXTENSIONS};
 use crate::error::ErrorCode;
 use anchor_lang::prelude::*;
 
@@ -7,30 +7,56 @@ use anchor_lang::prelude::*;
 pub struct Config {
     pub bump: u8,
     pub owner: Pubkey,
+    pub pending_owner: Pubkey,
     pub fee_to: Pubkey,
-    pub fee: u64,
+    pub fee_on: bool,
+    pub pause_authority: Pubkey,
+    pub paused: bool,
+    pub permissionless_pool_creation: bool,
+    pub pool_creation_fee: u64,
+    pub allowed_extensions: u64, // Bitmask of allowed Token-2022 mint extension types
+    pub freeze_authority_allowed: bool,
+    pub flash_loan_fee: u64, // Fee on flash loans in basis points, paid to the pool's LPs
 }
 
 impl Config {
-    /// Initializes the config with the specified parameters, ensuring the fee is valid.
-    pub fn initialize(&mut self, bump: u8, owner: Pubkey, fee_to: Pubkey, fee: u64) -> Result<()> {
-        // Ensure the fee is less than the maximum allowed value.
-        require!(fee < BASIS_POINTS, ErrorCode::InvalidFee);
-
+    /// Initializes the config with the specified parameters.
+    pub fn initialize(&mut self, bump: u8, owner: Pubkey, fee_to: Pubkey) -> Result<()> {
         self.bump = bump;
         self.owner = owner;
+        self.pending_owner = Pubkey::default();
         self.fee_to = fee_to;
-        self.fee = fee;
+        self.fee_on = true;
+        self.pause_authority = owner;
+        self.paused = false;
+        self.permissionless_pool_creation = false;
+        self.pool_creation_fee = 0;
+        self.allowed_extensions = DEFAULT_ALLOWED_EXTENSIONS;
+        self.freeze_authority_allowed = true;
+        self.flash_loan_fee = 0;
+
+        Ok(())
+    }
 
+    /// Proposes a new owner, who must accept before the transfer takes effect.
+    pub fn propose_owner(&mut self, pending_owner: Pubkey) -> Result<()> {
+        self.pending_owner = pending_owner;
         Ok(())
     }
 
-    /// Sets a new fee, ensuring it is valid.
-    pub fn set_fee(&mut self, fee: u64) -> Result<()> {
-        // Ensure the fee is less than the maximum allowed value.
-        require!(fee < BASIS_POINTS, ErrorCode::InvalidFee);
+    /// Makes the pending owner the owner, along with the pause authority if the old owner still held it.
+    pub fn accept_owner(&mut self) -> Result<()> {
+        if self.pause_authority == self.owner {
+            self.pause_authority = self.pending_owner;
+        }
+        self.owner = self.pending_owner;
+        self.pending_owner = Pubkey::default();
+        Ok(())
+    }
 
-        self.fee = fee;
+    /// Clears the pending owner.
+    pub fn cancel_owner_transfer(&mut self) -> Result<()> {
+        self.pending_owner = Pubkey::default();
         Ok(())
     }
 
@@ -39,4 +65,47 @@ impl Config {
         self.fee_to = fee_to;
         Ok(())
     }
+
+    /// Turns the protocol fee on or off.
+    pub fn set_fee_on(&mut self, fee_on: bool) -> Result<()> {
+        self.fee_on = fee_on;
+        Ok(())
+    }
+
+    /// Updates the fee charged on flash loans.
+    pub fn set_flash_loan_fee(&mut self, flash_loan_fee: u64) -> Result<()> {
+        require!(flash_loan_fee < BASIS_POINTS, ErrorCode::InvalidFee);
+        self.flash_loan_fee = flash_loan_fee;
+        Ok(())
+    }
+
+    /// Sets whether anyone may create pools, and the lamports charged for creating one.
+    pub fn set_pool_creation(&mut self, permissionless: bool, creation_fee: u64) -> Result<()> {
+        self.permissionless_pool_creation = permissionless;
+        self.pool_creation_fee = creation_fee;
+        Ok(())
+    }
+
+    /// Updates the account allowed to pause trading.
+    pub fn set_pause_authority(&mut self, pause_authority: Pubkey) -> Result<()> {
+        self.pause_authority = pause_authority;
+        Ok(())
+    }
+
+    /// Pauses or unpauses trading on every pool.
+    pub fn set_paused(&mut self, paused: bool) -> Result<()> {
+        self.paused = paused;
+        Ok(())
+    }
+
+    /// Replaces the Token-2022 extension allow-list checked at pool creation.
+    pub fn set_extension_allow_list(
+        &mut self,
+        allowed_extensions: u64,
+        freeze_authority_allowed: bool,
+    ) -> Result<()> {
+        self.allowed_extensions = allowed_extensions;
+        self.freeze_authority_allowed = freeze_authority_allowed;
+        Ok(())
+    }
 }
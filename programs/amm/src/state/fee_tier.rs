@@ -0,0 +1,22 @@
+use crate::constant::BASIS_POINTS;
+use crate::error::ErrorCode;
+use anchor_lang::prelude::*;
+
+#[account]
+#[derive(InitSpace)] // Automatically calculates the space required for the struct.
+pub struct FeeTier {
+    pub bump: u8,
+    pub fee: u64,
+}
+
+impl FeeTier {
+    /// Initializes the fee tier, ensuring the fee is valid.
+    pub fn initialize(&mut self, bump: u8, fee: u64) -> Result<()> {
+        // Ensure the fee is less than the maximum allowed value.
+        require!(fee < BASIS_POINTS, ErrorCode::InvalidFee);
+
+        self.bump = bump;
+        self.fee = fee;
+        Ok(())
+    }
+}
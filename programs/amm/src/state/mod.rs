@@ -1,5 +1,15 @@
+pub mod concentrated_pool;
 pub mod config;
+pub mod fee_tier;
+pub mod observations;
 pub mod pool;
+pub mod position;
+pub mod tick_array;
 
+pub use concentrated_pool::*;
 pub use config::*;
+pub use fee_tier::*;
+pub use observations::*;
 pub use pool::*;
+pub use position::*;
+pub use tick_array::*;
@@ -0,0 +1,218 @@
+use crate::constant::OBSERVATION_CAPACITY;
+use crate::error::ErrorCode;
+use crate::utils::mul_div;
+use anchor_lang::prelude::*;
+
+#[zero_copy]
+#[derive(Default)]
+pub struct Observation {
+    pub price0_cumulative: u128, // Pool's price0_cumulative_last at the observation
+    pub price1_cumulative: u128, // Pool's price1_cumulative_last at the observation
+    pub liquidity_cumulative: u128, // Time-weighted sum of sqrt(reserve0 * reserve1), wraps on overflow
+    pub block_timestamp: i64,
+    pub initialized: u8,
+    pub padding: [u8; 7],
+}
+
+impl Observation {
+    /// Returns the observation extrapolated to `block_timestamp` at the given prices and liquidity.
+    fn transform(&self, block_timestamp: i64, price0: u128, price1: u128, liquidity: u64) -> Self {
+        let time_elapsed = (block_timestamp - self.block_timestamp) as u128;
+        Observation {
+            price0_cumulative: self
+                .price0_cumulative
+                .wrapping_add(price0.wrapping_mul(time_elapsed)),
+            price1_cumulative: self
+                .price1_cumulative
+                .wrapping_add(price1.wrapping_mul(time_elapsed)),
+            liquidity_cumulative: self
+                .liquidity_cumulative
+                .wrapping_add((liquidity as u128).wrapping_mul(time_elapsed)),
+            block_timestamp,
+            initialized: 1,
+            padding: [0; 7],
+        }
+    }
+
+    /// Returns the observation interpolated to `block_timestamp`, between this one and a later one.
+    fn interpolate(&self, later: &Observation, block_timestamp: i64) -> Result<Self> {
+        let observation_elapsed = (later.block_timestamp - self.block_timestamp) as u128;
+        let target_elapsed = (block_timestamp - self.block_timestamp) as u128;
+        // Multiply before dividing, the differences of UQ64.64 cumulatives lose their fraction otherwise
+        let delta = |before: u128, after: u128| {
+            mul_div(
+                after.wrapping_sub(before),
+                target_elapsed,
+                observation_elapsed,
+            )
+            .ok_or(ErrorCode::MathOverflow)
+        };
+
+        Ok(Observation {
+            price0_cumulative: self
+                .price0_cumulative
+                .wrapping_add(delta(self.price0_cumulative, later.price0_cumulative)?),
+            price1_cumulative: self
+                .price1_cumulative
+                .wrapping_add(delta(self.price1_cumulative, later.price1_cumulative)?),
+            liquidity_cumulative: self.liquidity_cumulative.wrapping_add(delta(
+                self.liquidity_cumulative,
+                later.liquidity_cumulative,
+            )?),
+            block_timestamp,
+            initialized: 1,
+            padding: [0; 7],
+        })
+    }
+}
+
+/// Cumulative values at a point in time, differences over an interval give its TWAPs.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct Cumulatives {
+    pub price0_cumulative: u128,
+    pub price1_cumulative: u128,
+    pub liquidity_cumulative: u128,
+}
+
+#[account(zero_copy)]
+pub struct Observations {
+    pub pool: Pubkey,
+    pub index: u16,            // Index of the most recent observation
+    pub cardinality: u16,      // Number of observations in use
+    pub cardinality_next: u16, // Number of observations in use once the ring wraps
+    pub padding: [u8; 10],
+    pub observations: [Observation; OBSERVATION_CAPACITY],
+}
+
+impl Observations {
+    /// Initializes the ring buffer with a single observation at the given time.
+    pub fn initialize(&mut self, pool: Pubkey, block_timestamp: i64) -> Result<()> {
+        self.pool = pool;
+        self.index = 0;
+        self.cardinality = 1;
+        self.cardinality_next = 1;
+        self.observations[0] = Observation {
+            block_timestamp,
+            initialized: 1,
+            ..Default::default()
+        };
+        Ok(())
+    }
+
+    /// Grows the number of observations kept once the ring next wraps.
+    pub fn increase_cardinality(&mut self, cardinality_next: u16) -> Result<()> {
+        require!(
+            cardinality_next as usize <= OBSERVATION_CAPACITY,
+            ErrorCode::ObservationCardinalityTooLarge
+        );
+
+        self.cardinality_next = self.cardinality_next.max(cardinality_next);
+        Ok(())
+    }
+
+    /// Records the pool's cumulative prices at most once per timestamp.
+    pub fn write(
+        &mut self,
+        block_timestamp: i64,
+        price0_cumulative: u128,
+        price1_cumulative: u128,
+        liquidity: u64,
+    ) {
+        let last = self.observations[self.index as usize];
+        if last.block_timestamp == block_timestamp {
+            return;
+        }
+
+        // Grow into the next slots only once the ring reaches its current end
+        if self.cardinality_next > self.cardinality && self.index == self.cardinality - 1 {
+            self.cardinality = self.cardinality_next;
+        }
+        self.index = (self.index + 1) % self.cardinality;
+
+        let time_elapsed = (block_timestamp - last.block_timestamp) as u128;
+        self.observations[self.index as usize] = Observation {
+            price0_cumulative,
+            price1_cumulative,
+            liquidity_cumulative: last
+                .liquidity_cumulative
+                .wrapping_add((liquidity as u128).wrapping_mul(time_elapsed)),
+            block_timestamp,
+            initialized: 1,
+            padding: [0; 7],
+        };
+    }
+
+    /// Returns the cumulatives `seconds_ago` before `block_timestamp`, extrapolating past the last observation with the given prices and liquidity.
+    pub fn observe(
+        &self,
+        block_timestamp: i64,
+        seconds_ago: u32,
+        price0: u128,
+        price1: u128,
+        liquidity: u64,
+    ) -> Result<Cumulatives> {
+        let target = block_timestamp - seconds_ago as i64;
+        let newest = self.observations[self.index as usize];
+
+        let observation = if newest.block_timestamp <= target {
+            newest.transform(target, price0, price1, liquidity)
+        } else {
+            let (before, after) = self.surrounding(target)?;
+            if target == before.block_timestamp {
+                before
+            } else {
+                before.interpolate(&after, target)?
+            }
+        };
+
+        Ok(Cumulatives {
+            price0_cumulative: observation.price0_cumulative,
+            price1_cumulative: observation.price1_cumulative,
+            liquidity_cumulative: observation.liquidity_cumulative,
+        })
+    }
+
+    // Finds the observations at or before and after the target, which must precede the newest observation
+    fn surrounding(&self, target: i64) -> Result<(Observation, Observation)> {
+        let cardinality = self.cardinality as usize;
+        let index = self.index as usize;
+        // Until the ring fills after growing, the slots past the newest observation are unwritten
+        // and the oldest observation is the first slot
+        let (mut l, mut r) = if self.observations[(index + 1) % cardinality].initialized == 0 {
+            (0, index)
+        } else {
+            (index + 1, index + cardinality)
+        };
+        require!(
+            self.observations[l % cardinality].block_timestamp <= target,
+            ErrorCode::ObservationTooOld
+        );
+
+        // Binary search over the ring, from the oldest to the newest observation, every step
+        // narrows the range so it ends within `cardinality` steps
+        for _ in 0..cardinality {
+            if l > r {
+                break;
+            }
+
+            let i = (l + r) / 2;
+            let before = self.observations[i % cardinality];
+            if before.initialized == 0 {
+                l = i + 1;
+                continue;
+            }
+
+            let after = self.observations[(i + 1) % cardinality];
+            if before.block_timestamp <= target {
+                if target < after.block_timestamp {
+                    return Ok((before, after));
+                }
+                l = i + 1;
+            } else {
+                r = i.checked_sub(1).ok_or(ErrorCode::ObservationTooOld)?;
+            }
+        }
+
+        err!(ErrorCode::ObservationTooOld)
+    }
+}
@@ -1,30 +1,584 @@
+use crate::constant::{
+    BASIS_POINTS, MAX_AMP, MAX_AMP_CHANGE, MIN_AMP, MIN_AMP_RAMP_DURATION, MIN_WEIGHT,
+};
+use crate::curve::{ConstantProduct, Curve, OraclePegged, StableSwap, Weighted};
 use crate::error::ErrorCode;
+use crate::oracle::PythPrice;
+use crate::state::{Config, FeeTier, Observations};
+use crate::utils::mul_div;
 use anchor_lang::prelude::*;
+use fixed::types::U128F0;
+
+/// Invariant a pool prices its swaps along.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum CurveType {
+    ConstantProduct, // x * y = k
+    StableSwap,      // Curve's StableSwap invariant, for correlated assets
+    Weighted,        // Balancer's weighted product x^w0 * y^w1 = k
+    OraclePegged,    // StableSwap invariant over the reserves valued at an oracle price
+}
+
+/// Curve a pool is created with, along with its parameters.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoolCurve {
+    ConstantProduct,
+    StableSwap {
+        amp: u64,
+    },
+    Weighted {
+        weight0: u64,
+        weight1: u64,
+    }, // Relative weights, normalized on creation
+    OraclePegged {
+        amp: u64,
+        oracle: Pubkey,      // Pyth price account pricing token0 in token1
+        max_age: u64,        // Slots since the price was published before it is stale
+        max_confidence: u64, // Widest confidence interval accepted, in basis points of the price
+    },
+}
 
 #[account]
 #[derive(InitSpace)] // Automatically calculates the space required for the struct.
 pub struct Pool {
+    pub bump: u8,
+    pub creator: Pubkey,
     pub token0: Pubkey,
     pub token1: Pubkey,
-    pub k_last: u128,
+    pub vault0: Pubkey,
+    pub vault1: Pubkey,
+    pub fee_tier: Pubkey,
+    pub fee: u64,
+    pub reserve0: u64,
+    pub reserve1: u64,
+    pub k_last: u128, // Curve invariant after the last liquidity event while the protocol fee is on
+    pub k_last_amp: u64, // Amplification coefficient k_last was measured at, amplified curves only
+    pub paused: bool,
+    pub price0_cumulative_last: u128, // Time-weighted sum of token1/token0 as UQ64.64, wraps on overflow
+    pub price1_cumulative_last: u128, // Time-weighted sum of token0/token1 as UQ64.64, wraps on overflow
+    pub block_timestamp_last: i64,
+    pub flash_loan_vault: Pubkey, // Vault lent from by the open flash loan, default when none is open
+    pub flash_loan_repayment: u64, // Balance the vault must hold again when the flash loan ends
+    pub flash_loan_fee: u64, // Fee of the open flash loan, credited to the reserves when it ends
+    pub unlocked: bool,      // Cleared while an instruction is using the pool, rejects nested calls
+    pub curve_type: CurveType,
+    pub amp_initial: u64, // StableSwap amplification coefficient at the start of the ramp
+    pub amp_target: u64,  // StableSwap amplification coefficient at the end of the ramp
+    pub amp_ramp_start: i64,
+    pub amp_ramp_end: i64,
+    pub rate0: u64, // StableSwap multiplier bringing token0 amounts to the larger of the two decimals
+    pub rate1: u64, // StableSwap multiplier bringing token1 amounts to the larger of the two decimals
+    pub weight0: u64, // Normalized weight of token0 in basis points, weighted pools only
+    pub weight1: u64, // Normalized weight of token1 in basis points, weighted pools only
+    pub oracle: Pubkey, // Pyth price account of an oracle-pegged pool, default otherwise
+    pub oracle_max_age: u64, // Slots since the oracle price was published before it is stale
+    pub oracle_max_confidence: u64, // Widest oracle confidence interval accepted, in basis points of the price
+    pub oracle_decimals: i8, // Decimals of token1 less those of token0, scaling the oracle price to token amounts
+    pub oracle_price: u128,  // Last oracle price of token0 in token1 as UQ64.64
+    pub oracle_publish_slot: u64, // Slot the last oracle price was published in
 }
 
 impl Pool {
-    /// Initializes the pool with two tokens, ensuring they are in the correct order.
-    pub fn initialize(&mut self, token0: Pubkey, token1: Pubkey) -> Result<()> {
+    /// Initializes the pool with its creator, two tokens, their vaults and its fee tier, ensuring the tokens are in the correct order.
+    #[allow(clippy::too_many_arguments)]
+    pub fn initialize(
+        &mut self,
+        bump: u8,
+        creator: Pubkey,
+        token0: Pubkey,
+        token1: Pubkey,
+        vault0: Pubkey,
+        vault1: Pubkey,
+        decimals0: u8,
+        decimals1: u8,
+        fee_tier: &Account<FeeTier>,
+        curve: PoolCurve,
+    ) -> Result<()> {
         // Ensure token0 is less than token1 to maintain consistent ordering.
         require!(
             token0.to_string() < token1.to_string(),
             ErrorCode::InvalidMintOrder
         );
+        self.bump = bump;
+        self.creator = creator;
         self.token0 = token0;
         self.token1 = token1;
+        self.vault0 = vault0;
+        self.vault1 = vault1;
+        self.fee_tier = fee_tier.key();
+        self.fee = fee_tier.fee;
+        self.reserve0 = 0;
+        self.reserve1 = 0;
         self.k_last = 0;
+        self.k_last_amp = 0;
+        self.paused = false;
+        self.price0_cumulative_last = 0;
+        self.price1_cumulative_last = 0;
+        self.block_timestamp_last = 0;
+        self.flash_loan_vault = Pubkey::default();
+        self.flash_loan_repayment = 0;
+        self.flash_loan_fee = 0;
+        self.unlocked = true;
+        self.amp_ramp_start = 0;
+        self.amp_ramp_end = 0;
+        self.rate0 = 1;
+        self.rate1 = 1;
+        self.weight0 = 0;
+        self.weight1 = 0;
+        self.oracle = Pubkey::default();
+        self.oracle_max_age = 0;
+        self.oracle_max_confidence = 0;
+        self.oracle_decimals = 0;
+        self.oracle_price = 0;
+        self.oracle_publish_slot = 0;
+        match curve {
+            PoolCurve::ConstantProduct => {
+                self.curve_type = CurveType::ConstantProduct;
+                self.amp_initial = 0;
+                self.amp_target = 0;
+            }
+            PoolCurve::StableSwap { amp } => {
+                require!((MIN_AMP..=MAX_AMP).contains(&amp), ErrorCode::InvalidAmp);
+                self.curve_type = CurveType::StableSwap;
+                self.amp_initial = amp;
+                self.amp_target = amp;
+
+                // Like Curve's rates, so the invariant compares whole tokens rather than raw amounts
+                let decimals = decimals0.max(decimals1);
+                let rate = |decimals_token: u8| {
+                    10u64
+                        .checked_pow((decimals - decimals_token) as u32)
+                        .ok_or(ErrorCode::InvalidDecimals)
+                };
+                self.rate0 = rate(decimals0)?;
+                self.rate1 = rate(decimals1)?;
+            }
+            PoolCurve::Weighted { weight0, weight1 } => {
+                // Normalize the weights to basis points, neither may fall below the minimum
+                let total = weight0 as u128 + weight1 as u128;
+                require!(total > 0, ErrorCode::InvalidWeights);
+                let weight0 = (weight0 as u128 * BASIS_POINTS as u128 / total) as u64;
+                require!(
+                    (MIN_WEIGHT..=BASIS_POINTS - MIN_WEIGHT).contains(&weight0),
+                    ErrorCode::InvalidWeights
+                );
+                self.curve_type = CurveType::Weighted;
+                self.amp_initial = 0;
+                self.amp_target = 0;
+                self.weight0 = weight0;
+                self.weight1 = BASIS_POINTS - weight0;
+            }
+            PoolCurve::OraclePegged {
+                amp,
+                oracle,
+                max_age,
+                max_confidence,
+            } => {
+                require!((MIN_AMP..=MAX_AMP).contains(&amp), ErrorCode::InvalidAmp);
+                require!(
+                    oracle != Pubkey::default() && max_age > 0 && max_confidence <= BASIS_POINTS,
+                    ErrorCode::InvalidOracle
+                );
+                self.curve_type = CurveType::OraclePegged;
+                self.amp_initial = amp;
+                self.amp_target = amp;
+                self.oracle = oracle;
+                self.oracle_max_age = max_age;
+                self.oracle_max_confidence = max_confidence;
+                self.oracle_decimals = i8::try_from(decimals1 as i16 - decimals0 as i16)
+                    .map_err(|_| ErrorCode::InvalidDecimals)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true if the given account is one of the pool's vaults.
+    pub fn is_vault(&self, vault: &Pubkey) -> bool {
+        *vault == self.vault0 || *vault == self.vault1
+    }
+
+    /// Sets the swap fee charged by the pool.
+    pub fn set_fee(&mut self, fee: u64) -> Result<()> {
+        self.fee = fee;
+        Ok(())
+    }
+
+    /// Returns true if the pool's curve has an amplification coefficient.
+    pub fn has_amp(&self) -> bool {
+        matches!(
+            self.curve_type,
+            CurveType::StableSwap | CurveType::OraclePegged
+        )
+    }
+
+    /// Returns the amplification coefficient at the unix timestamp `now`, moving linearly along the ramp.
+    pub fn amp(&self, now: i64) -> u64 {
+        if now >= self.amp_ramp_end {
+            return self.amp_target;
+        }
+
+        let elapsed = (now - self.amp_ramp_start) as i128;
+        let duration = (self.amp_ramp_end - self.amp_ramp_start) as i128;
+        let change = self.amp_target as i128 - self.amp_initial as i128;
+        (self.amp_initial as i128 + change * elapsed / duration) as u64
+    }
+
+    /// Starts moving the amplification coefficient towards `amp_target`, reaching it at `amp_ramp_end`.
+    pub fn ramp_amp(&mut self, amp_target: u64, amp_ramp_end: i64, now: i64) -> Result<()> {
+        require!(self.has_amp(), ErrorCode::InvalidCurve);
+        require!(
+            (MIN_AMP..=MAX_AMP).contains(&amp_target),
+            ErrorCode::InvalidAmp
+        );
+
+        // One ramp at a time, lasting long enough and moving by a bounded factor
+        let amp = self.amp(now);
+        require!(
+            now >= self.amp_ramp_end
+                && amp_ramp_end >= now + MIN_AMP_RAMP_DURATION
+                && amp_target <= amp * MAX_AMP_CHANGE
+                && amp_target * MAX_AMP_CHANGE >= amp,
+            ErrorCode::InvalidAmpRamp
+        );
+
+        self.amp_initial = amp;
+        self.amp_target = amp_target;
+        self.amp_ramp_start = now;
+        self.amp_ramp_end = amp_ramp_end;
         Ok(())
     }
 
-    /// Updates the stored product of reserves, k_last, for the pool.
-    pub fn update_k_last(&mut self, reserve0: u64, reserve1: u64) {
-        self.k_last = reserve0 as u128 * reserve1 as u128;
+    /// Stops the amplification ramp, holding the coefficient reached at `now`.
+    pub fn stop_ramp_amp(&mut self, now: i64) -> Result<()> {
+        require!(self.has_amp(), ErrorCode::InvalidCurve);
+
+        let amp = self.amp(now);
+        self.amp_initial = amp;
+        self.amp_target = amp;
+        self.amp_ramp_start = now;
+        self.amp_ramp_end = now;
+        Ok(())
+    }
+
+    /// Pauses or unpauses trading on the pool.
+    pub fn set_paused(&mut self, paused: bool) -> Result<()> {
+        self.paused = paused;
+        Ok(())
+    }
+
+    /// Ensures neither the pool nor the whole AMM is paused.
+    pub fn require_not_paused(&self, config: &Config) -> Result<()> {
+        require!(!config.paused && !self.paused, ErrorCode::Paused);
+        Ok(())
+    }
+
+    /// Locks the pool, failing if an instruction further up the call stack already holds it.
+    pub fn lock(&mut self) -> Result<()> {
+        require!(self.unlocked, ErrorCode::Locked);
+        self.unlocked = false;
+        Ok(())
+    }
+
+    /// Unlocks the pool at the end of an instruction.
+    pub fn unlock(&mut self) {
+        self.unlocked = true;
+    }
+
+    /// Ensures no flash loan is open, the vault balances are short until it is repaid.
+    pub fn require_no_flash_loan(&self) -> Result<()> {
+        require!(
+            self.flash_loan_vault == Pubkey::default(),
+            ErrorCode::FlashLoanActive
+        );
+        Ok(())
+    }
+
+    /// Opens a flash loan from the given vault, which must hold `repayment` again when it ends.
+    pub fn begin_flash_loan(&mut self, vault: Pubkey, repayment: u64, fee: u64) -> Result<()> {
+        self.require_no_flash_loan()?;
+        self.flash_loan_vault = vault;
+        self.flash_loan_repayment = repayment;
+        self.flash_loan_fee = fee;
+        Ok(())
+    }
+
+    /// Closes the open flash loan, returning the vault lent from, the balance it must hold and the fee.
+    pub fn end_flash_loan(&mut self) -> Result<(Pubkey, u64, u64)> {
+        require!(
+            self.flash_loan_vault != Pubkey::default(),
+            ErrorCode::FlashLoanNotActive
+        );
+        let loan = (
+            self.flash_loan_vault,
+            self.flash_loan_repayment,
+            self.flash_loan_fee,
+        );
+        self.flash_loan_vault = Pubkey::default();
+        self.flash_loan_repayment = 0;
+        self.flash_loan_fee = 0;
+        Ok(loan)
+    }
+
+    /// Returns the (input, output) reserves for a swap out of the given source vault.
+    pub fn swap_reserves(&self, vault_src: &Pubkey) -> (u64, u64) {
+        if *vault_src == self.vault0 {
+            (self.reserve0, self.reserve1)
+        } else {
+            (self.reserve1, self.reserve0)
+        }
+    }
+
+    /// Records the reserves after a swap of `amount_in` into the given source vault.
+    pub fn update_swap_reserves(
+        &mut self,
+        vault_src: &Pubkey,
+        amount_in: u64,
+        amount_out: u64,
+        observations: &mut Observations,
+    ) -> Result<()> {
+        let (reserve_in, reserve_out) = self.swap_reserves(vault_src);
+        let reserve_in = reserve_in
+            .checked_add(amount_in)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let reserve_out = reserve_out
+            .checked_sub(amount_out)
+            .ok_or(ErrorCode::InsufficientLiquidity)?;
+
+        if *vault_src == self.vault0 {
+            self.update_reserves(reserve_in, reserve_out, observations)
+        } else {
+            self.update_reserves(reserve_out, reserve_in, observations)
+        }
+    }
+
+    /// Returns the curve the pool prices its swaps along.
+    pub fn curve(&self) -> Result<Box<dyn Curve>> {
+        Ok(self.curve_at(self.amp(Clock::get()?.unix_timestamp)))
+    }
+
+    // Returns the pool's curve at the amplification coefficient `amp`, ignored by curves without one
+    fn curve_at(&self, amp: u64) -> Box<dyn Curve> {
+        match self.curve_type {
+            CurveType::ConstantProduct => Box::new(ConstantProduct { fee: self.fee }),
+            CurveType::StableSwap => Box::new(StableSwap {
+                amp,
+                rate0: self.rate0,
+                rate1: self.rate1,
+                fee: self.fee,
+            }),
+            CurveType::Weighted => Box::new(Weighted {
+                weight0: self.weight0,
+                weight1: self.weight1,
+                fee: self.fee,
+            }),
+            CurveType::OraclePegged => Box::new(OraclePegged {
+                amp,
+                price: self.oracle_price,
+                fee: self.fee,
+            }),
+        }
+    }
+
+    /// Returns the curve to price a swap along, ensuring the oracle price of an oracle-pegged pool is fresh.
+    pub fn swap_curve(&self) -> Result<Box<dyn Curve>> {
+        if self.curve_type == CurveType::OraclePegged {
+            let age = Clock::get()?.slot.saturating_sub(self.oracle_publish_slot);
+            require!(
+                self.oracle_price > 0 && age <= self.oracle_max_age,
+                ErrorCode::OracleStale
+            );
+        }
+        self.curve()
+    }
+
+    /// Updates the oracle price of an oracle-pegged pool from its Pyth price account, rejecting stale prices and
+    /// wide confidence intervals.
+    pub fn update_oracle_price(&mut self, oracle: &AccountInfo) -> Result<()> {
+        require!(
+            self.curve_type == CurveType::OraclePegged,
+            ErrorCode::InvalidCurve
+        );
+        require_keys_eq!(oracle.key(), self.oracle, ErrorCode::InvalidOracle);
+
+        let price = PythPrice::load(oracle)?;
+        require!(price.price > 0, ErrorCode::OracleUnavailable);
+        let age = Clock::get()?.slot.saturating_sub(price.publish_slot);
+        require!(age <= self.oracle_max_age, ErrorCode::OracleStale);
+        require!(
+            price.confidence as u128 * BASIS_POINTS as u128
+                <= price.price as u128 * self.oracle_max_confidence as u128,
+            ErrorCode::OracleConfidenceTooWide
+        );
+
+        // price * 10^(exponent + decimals) as UQ64.64
+        let exponent = price.exponent + self.oracle_decimals as i32;
+        let scale = 10u128
+            .checked_pow(exponent.unsigned_abs())
+            .ok_or(ErrorCode::InvalidOracle)?;
+        let oracle_price = if exponent >= 0 {
+            (price.price as u128)
+                .checked_mul(scale)
+                .and_then(|price| price.checked_mul(1 << 64))
+        } else {
+            mul_div(price.price as u128, 1 << 64, scale)
+        }
+        .filter(|price| *price > 0)
+        .ok_or(ErrorCode::InvalidOracle)?;
+
+        self.oracle_price = oracle_price;
+        self.oracle_publish_slot = price.publish_slot;
+        Ok(())
+    }
+
+    /// Updates the oracle price of an oracle-pegged pool if its price account is among `accounts`.
+    pub fn load_oracle_price(&mut self, accounts: &[AccountInfo]) -> Result<()> {
+        if self.curve_type != CurveType::OraclePegged {
+            return Ok(());
+        }
+        match accounts.iter().find(|account| account.key() == self.oracle) {
+            Some(oracle) => self.update_oracle_price(oracle),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the UQ64.64 spot prices of token0 in token1 and token1 in token0, zero while a reserve is empty.
+    pub fn prices(&self) -> Result<(u128, u128)> {
+        if self.reserve0 == 0 || self.reserve1 == 0 {
+            return Ok((0, 0));
+        }
+
+        let curve = self.curve()?;
+        Ok((
+            curve.spot_price(self.reserve0, self.reserve1, true)?,
+            curve.spot_price(self.reserve1, self.reserve0, false)?,
+        ))
+    }
+
+    /// Returns the pool's liquidity, the square root of the product of its reserves.
+    pub fn liquidity(&self) -> u64 {
+        U128F0::from_num(self.reserve0 as u128 * self.reserve1 as u128)
+            .sqrt()
+            .to_num::<u64>()
+    }
+
+    /// Updates the tracked reserves of the pool, first accumulating the prices held since the last update.
+    pub fn update_reserves(
+        &mut self,
+        reserve0: u64,
+        reserve1: u64,
+        observations: &mut Observations,
+    ) -> Result<()> {
+        let block_timestamp = Clock::get()?.unix_timestamp;
+        let time_elapsed = block_timestamp - self.block_timestamp_last;
+
+        if time_elapsed > 0 {
+            // Overflow is desired, consumers take the difference of two observations
+            let (price0, price1) = self.prices()?;
+            self.price0_cumulative_last = self
+                .price0_cumulative_last
+                .wrapping_add(price0.wrapping_mul(time_elapsed as u128));
+            self.price1_cumulative_last = self
+                .price1_cumulative_last
+                .wrapping_add(price1.wrapping_mul(time_elapsed as u128));
+        }
+
+        // Record the cumulatives for the oracle before the reserves change
+        observations.write(
+            block_timestamp,
+            self.price0_cumulative_last,
+            self.price1_cumulative_last,
+            self.liquidity(),
+        );
+
+        self.reserve0 = reserve0;
+        self.reserve1 = reserve1;
+        self.block_timestamp_last = block_timestamp;
+        Ok(())
+    }
+
+    /// Updates the stored invariant of the reserves, k_last, for the pool.
+    pub fn update_k_last(&mut self, reserve0: u64, reserve1: u64) -> Result<()> {
+        self.k_last_amp = self.amp(Clock::get()?.unix_timestamp);
+        self.k_last = self
+            .curve_at(self.k_last_amp)
+            .invariant(reserve0, reserve1)?;
+        Ok(())
+    }
+
+    /// Returns the protocol's share of the swap fees earned since k_last, as liquidity to mint out of
+    /// `lp_supply` (mirrors Uniswap V2's `_mintFee`). The invariant is measured at the amplification
+    /// coefficient of k_last, as D also moves with the coefficient along a ramp.
+    pub fn protocol_fee_liquidity(
+        &self,
+        reserve0: u64,
+        reserve1: u64,
+        lp_supply: u64,
+    ) -> Result<u64> {
+        // The invariant is measured like sqrt(k), growing in proportion to the reserves
+        let root_k = self
+            .curve_at(self.k_last_amp)
+            .invariant(reserve0, reserve1)?;
+        let root_k_last = self.k_last;
+        if root_k <= root_k_last {
+            return Ok(0);
+        }
+
+        let denominator = root_k
+            .checked_mul(5)
+            .and_then(|denominator| denominator.checked_add(root_k_last))
+            .ok_or(ErrorCode::MathOverflow)?;
+        mul_div(lp_supply as u128, root_k - root_k_last, denominator)
+            .and_then(|liquidity| u64::try_from(liquidity).ok())
+            .ok_or(error!(ErrorCode::MathOverflow))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An imbalanced StableSwap pool with k_last measured at `amp`
+    fn stable_pool(amp: u64, reserve0: u64, reserve1: u64) -> Pool {
+        let mut pool = Pool::deserialize(&mut &[0u8; Pool::INIT_SPACE][..]).unwrap();
+        pool.curve_type = CurveType::StableSwap;
+        pool.rate0 = 1;
+        pool.rate1 = 1;
+        pool.amp_initial = amp;
+        pool.amp_target = amp;
+        pool.k_last_amp = amp;
+        pool.k_last = pool.curve_at(amp).invariant(reserve0, reserve1).unwrap();
+        pool
+    }
+
+    #[test]
+    fn amp_ramp_mints_no_protocol_fee() {
+        let (reserve0, reserve1) = (1_000_000_000, 100_000_000);
+        for (amp, amp_target) in [(10, 100), (100, 10)] {
+            let mut pool = stable_pool(amp, reserve0, reserve1);
+            pool.ramp_amp(amp_target, MIN_AMP_RAMP_DURATION, 0).unwrap();
+
+            // D moves with the coefficient on imbalanced reserves, but no fees were earned
+            let d = pool
+                .curve_at(pool.amp(MIN_AMP_RAMP_DURATION))
+                .invariant(reserve0, reserve1)
+                .unwrap();
+            assert_ne!(d, pool.k_last);
+            assert_eq!(
+                pool.protocol_fee_liquidity(reserve0, reserve1, 1_000_000)
+                    .unwrap(),
+                0
+            );
+        }
+    }
+
+    #[test]
+    fn protocol_fee_on_fee_growth() {
+        let mut pool = stable_pool(10, 1_000_000_000, 100_000_000);
+        pool.ramp_amp(100, MIN_AMP_RAMP_DURATION, 0).unwrap();
+
+        // Reserves grown by fees still mint the protocol its share
+        let liquidity = pool
+            .protocol_fee_liquidity(1_010_000_000, 101_000_000, 1_000_000)
+            .unwrap();
+        assert!(liquidity > 0);
     }
 }
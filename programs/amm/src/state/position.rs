@@ -0,0 +1,84 @@
+use crate::error::ErrorCode;
+use crate::utils::mul_div;
+use anchor_lang::prelude::*;
+
+#[account]
+#[derive(InitSpace)] // Automatically calculates the space required for the struct.
+pub struct Position {
+    pub bump: u8,
+    pub pool: Pubkey,
+    pub owner: Pubkey,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub liquidity: u128,
+    pub fee_growth_inside0_last: u128, // Fee growth of token0 inside the range at the last update, Q64.64
+    pub fee_growth_inside1_last: u128, // Fee growth of token1 inside the range at the last update, Q64.64
+    pub tokens_owed0: u64,             // Fees in token0 earned and not yet collected
+    pub tokens_owed1: u64,             // Fees in token1 earned and not yet collected
+}
+
+impl Position {
+    /// Initializes an empty position of `owner` between two ticks.
+    pub fn initialize(
+        &mut self,
+        bump: u8,
+        pool: Pubkey,
+        owner: Pubkey,
+        tick_lower: i32,
+        tick_upper: i32,
+    ) -> Result<()> {
+        require!(tick_lower < tick_upper, ErrorCode::InvalidTick);
+        self.bump = bump;
+        self.pool = pool;
+        self.owner = owner;
+        self.tick_lower = tick_lower;
+        self.tick_upper = tick_upper;
+        self.liquidity = 0;
+        self.fee_growth_inside0_last = 0;
+        self.fee_growth_inside1_last = 0;
+        self.tokens_owed0 = 0;
+        self.tokens_owed1 = 0;
+        Ok(())
+    }
+
+    /// Credits the fees earned since the last update, then changes the liquidity by `liquidity_delta`.
+    pub fn update(
+        &mut self,
+        liquidity_delta: i128,
+        fee_growth_inside0: u128,
+        fee_growth_inside1: u128,
+    ) -> Result<()> {
+        // Fees must be collected before they exceed a u64
+        let fees_earned = |growth: u128, growth_last: u128| -> Result<u64> {
+            mul_div(self.liquidity, growth.wrapping_sub(growth_last), 1 << 64)
+                .and_then(|fees| u64::try_from(fees).ok())
+                .ok_or(error!(ErrorCode::MathOverflow))
+        };
+        let fees0 = fees_earned(fee_growth_inside0, self.fee_growth_inside0_last)?;
+        let fees1 = fees_earned(fee_growth_inside1, self.fee_growth_inside1_last)?;
+        self.tokens_owed0 = self
+            .tokens_owed0
+            .checked_add(fees0)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.tokens_owed1 = self
+            .tokens_owed1
+            .checked_add(fees1)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        self.liquidity = self
+            .liquidity
+            .checked_add_signed(liquidity_delta)
+            .ok_or(ErrorCode::InsufficientLiquidity)?;
+        self.fee_growth_inside0_last = fee_growth_inside0;
+        self.fee_growth_inside1_last = fee_growth_inside1;
+        Ok(())
+    }
+
+    /// Takes the fees owed to the position, leaving none.
+    pub fn take_fees(&mut self) -> (u64, u64) {
+        let fees = (self.tokens_owed0, self.tokens_owed1);
+        self.tokens_owed0 = 0;
+        self.tokens_owed1 = 0;
+        fees
+    }
+}
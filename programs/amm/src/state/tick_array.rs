@@ -0,0 +1,123 @@
+use crate::constant::TICK_ARRAY_SIZE;
+use crate::error::ErrorCode;
+use anchor_lang::prelude::*;
+
+#[zero_copy]
+#[derive(Default)]
+pub struct Tick {
+    pub liquidity_net: i128, // Liquidity added when the price crosses the tick upwards, removed crossing downwards
+    pub liquidity_gross: u128, // Liquidity of the positions bounded by the tick, zero while uninitialized
+    pub fee_growth_outside0: u128, // Fee growth of token0 on the side of the tick away from the price, Q64.64
+    pub fee_growth_outside1: u128, // Fee growth of token1 on the side of the tick away from the price, Q64.64
+}
+
+impl Tick {
+    /// Returns true if a position is bounded by the tick.
+    pub fn is_initialized(&self) -> bool {
+        self.liquidity_gross != 0
+    }
+
+    /// Adds `liquidity_delta` to the tick as the lower or upper bound of a position.
+    pub fn update(
+        &mut self,
+        tick: i32,
+        tick_current: i32,
+        liquidity_delta: i128,
+        fee_growth_global0: u128,
+        fee_growth_global1: u128,
+        upper: bool,
+    ) -> Result<()> {
+        let liquidity_gross = self
+            .liquidity_gross
+            .checked_add_signed(liquidity_delta)
+            .ok_or(ErrorCode::CalculationFailure)?;
+
+        // All growth so far is taken to have happened below the tick, when initializing it at or below the price
+        if !self.is_initialized() && tick <= tick_current {
+            self.fee_growth_outside0 = fee_growth_global0;
+            self.fee_growth_outside1 = fee_growth_global1;
+        }
+
+        self.liquidity_gross = liquidity_gross;
+        self.liquidity_net = if upper {
+            self.liquidity_net.checked_sub(liquidity_delta)
+        } else {
+            self.liquidity_net.checked_add(liquidity_delta)
+        }
+        .ok_or(ErrorCode::CalculationFailure)?;
+        Ok(())
+    }
+
+    /// Flips the fee growth outside the tick as the price crosses it, returning its net liquidity.
+    pub fn cross(&mut self, fee_growth_global0: u128, fee_growth_global1: u128) -> i128 {
+        self.fee_growth_outside0 = fee_growth_global0.wrapping_sub(self.fee_growth_outside0);
+        self.fee_growth_outside1 = fee_growth_global1.wrapping_sub(self.fee_growth_outside1);
+        self.liquidity_net
+    }
+}
+
+#[account(zero_copy)]
+pub struct TickArray {
+    pub pool: Pubkey,
+    pub start_tick: i32, // First tick of the array, a multiple of the tick spacing times the array size
+    pub padding: [u8; 12],
+    pub ticks: [Tick; TICK_ARRAY_SIZE as usize],
+}
+
+impl TickArray {
+    /// Initializes an empty array of ticks from `start_tick`.
+    pub fn initialize(&mut self, pool: Pubkey, start_tick: i32) -> Result<()> {
+        self.pool = pool;
+        self.start_tick = start_tick;
+        Ok(())
+    }
+
+    // Returns the index of `tick` in the array
+    fn offset(&self, tick: i32, tick_spacing: u16) -> Result<usize> {
+        let tick_spacing = tick_spacing as i32;
+        let offset = (tick - self.start_tick) / tick_spacing;
+        require!(
+            tick >= self.start_tick
+                && offset < TICK_ARRAY_SIZE
+                && (tick - self.start_tick) % tick_spacing == 0,
+            ErrorCode::InvalidTickArray
+        );
+        Ok(offset as usize)
+    }
+
+    /// Returns the tick at index `tick`, which must be in the array.
+    pub fn tick(&self, tick: i32, tick_spacing: u16) -> Result<&Tick> {
+        Ok(&self.ticks[self.offset(tick, tick_spacing)?])
+    }
+
+    /// Returns the tick at index `tick` for update, which must be in the array.
+    pub fn tick_mut(&mut self, tick: i32, tick_spacing: u16) -> Result<&mut Tick> {
+        let offset = self.offset(tick, tick_spacing)?;
+        Ok(&mut self.ticks[offset])
+    }
+
+    /// Returns the next initialized tick from `tick` in the swap direction, searching down from and including `tick`
+    /// if `zero_for_one`, else up from it. Without one, returns the last tick of the array in that direction.
+    pub fn next_initialized_tick(
+        &self,
+        tick: i32,
+        tick_spacing: u16,
+        zero_for_one: bool,
+    ) -> Result<(i32, bool)> {
+        let offset = self.offset(tick, tick_spacing)?;
+        let tick_at = |offset: usize| self.start_tick + offset as i32 * tick_spacing as i32;
+
+        let next = if zero_for_one {
+            (0..=offset)
+                .rev()
+                .find(|offset| self.ticks[*offset].is_initialized())
+        } else {
+            (offset..TICK_ARRAY_SIZE as usize).find(|offset| self.ticks[*offset].is_initialized())
+        };
+        Ok(match next {
+            Some(offset) => (tick_at(offset), true),
+            None if zero_for_one => (tick_at(0), false),
+            None => (tick_at(TICK_ARRAY_SIZE as usize - 1), false),
+        })
+    }
+}
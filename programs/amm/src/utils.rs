@@ -0,0 +1,314 @@
+use crate::error::ErrorCode;
+use crate::state::{ConcentratedPool, Config, Pool};
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{
+    allocate, assign, create_account, transfer, Allocate, Assign, CreateAccount, Transfer,
+};
+use anchor_spl::token::spl_token::state::Account as TokenAccountState;
+use anchor_spl::token::{
+    close_account, initialize_account3, CloseAccount, InitializeAccount3, Token,
+};
+use anchor_spl::token_2022::spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+use anchor_spl::token_2022::spl_token_2022::extension::transfer_hook::TransferHook;
+use anchor_spl::token_2022::spl_token_2022::extension::{
+    BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+};
+use anchor_spl::token_2022::spl_token_2022::solana_program::program_pack::Pack;
+use anchor_spl::token_2022::spl_token_2022::state::Mint as MintState;
+use anchor_spl::token_2022::Token2022;
+use anchor_spl::token_interface::{transfer_checked, Mint, TransferChecked};
+
+/// Returns the mint's transfer fee configuration, if it is a Token-2022 mint with one.
+fn transfer_fee_config(mint: &InterfaceAccount<Mint>) -> Result<Option<TransferFeeConfig>> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != Token2022::id() {
+        return Ok(None);
+    }
+
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<MintState>::unpack(&mint_data)?;
+    Ok(mint_state
+        .get_extension::<TransferFeeConfig>()
+        .ok()
+        .copied())
+}
+
+/// Returns the fee withheld by the mint when transferring `amount`.
+pub fn get_transfer_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    match transfer_fee_config(mint)? {
+        Some(config) => Ok(config
+            .calculate_epoch_fee(Clock::get()?.epoch, amount)
+            .ok_or(ErrorCode::InvalidTransferFee)?),
+        None => Ok(0),
+    }
+}
+
+/// Returns the fee to add to a transfer so that exactly `amount` arrives.
+pub fn get_transfer_inverse_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    match transfer_fee_config(mint)? {
+        Some(config) => Ok(config
+            .calculate_inverse_epoch_fee(Clock::get()?.epoch, amount)
+            .ok_or(ErrorCode::InvalidTransferFee)?),
+        None => Ok(0),
+    }
+}
+
+/// Ensures the mint has no transfer fee, for pools that cannot price on the amounts received.
+pub fn require_no_transfer_fee(mint: &InterfaceAccount<Mint>) -> Result<()> {
+    require!(
+        transfer_fee_config(mint)?.is_none(),
+        ErrorCode::UnsupportedMint
+    );
+    Ok(())
+}
+
+/// Ensures the transaction lands no later than the unix timestamp `deadline`.
+pub fn require_not_expired(deadline: i64) -> Result<()> {
+    require!(Clock::get()?.unix_timestamp <= deadline, ErrorCode::Expired);
+    Ok(())
+}
+
+/// Ensures the mint only uses extensions on the config's allow-list.
+pub fn require_allowed_mint(config: &Config, mint: &InterfaceAccount<Mint>) -> Result<()> {
+    require!(
+        config.freeze_authority_allowed || mint.freeze_authority.is_none(),
+        ErrorCode::FreezeAuthorityNotAllowed
+    );
+
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != Token2022::id() {
+        return Ok(());
+    }
+
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<MintState>::unpack(&mint_data)?;
+    for extension in mint_state.get_extension_types()? {
+        // Transfers do not pass a hook's extra accounts, so no hook program may be set, its mint
+        // could never leave or enter the vaults
+        if extension == ExtensionType::TransferHook {
+            let hook = mint_state.get_extension::<TransferHook>()?;
+            require!(
+                Option::<Pubkey>::from(hook.program_id).is_none(),
+                ErrorCode::TransferHookNotAllowed
+            );
+            continue;
+        }
+
+        let bit = 1u64.checked_shl(extension as u32).unwrap_or(0);
+        if config.allowed_extensions & bit != 0 {
+            continue;
+        }
+
+        return Err(match extension {
+            ExtensionType::PermanentDelegate => ErrorCode::PermanentDelegateNotAllowed,
+            ExtensionType::NonTransferable => ErrorCode::NonTransferableNotAllowed,
+            ExtensionType::ConfidentialTransferMint
+            | ExtensionType::ConfidentialTransferFeeConfig => {
+                ErrorCode::ConfidentialTransferNotAllowed
+            }
+            ExtensionType::DefaultAccountState => ErrorCode::DefaultAccountStateNotAllowed,
+            ExtensionType::MintCloseAuthority => ErrorCode::MintCloseAuthorityNotAllowed,
+            _ => ErrorCode::ExtensionNotAllowed,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Creates the owner's temporary wSOL account at its PDA, wrapping `amount` lamports.
+pub fn wrap_sol<'info>(
+    owner: &AccountInfo<'info>,
+    wsol: &AccountInfo<'info>,
+    wsol_bump: u8,
+    native_mint: &AccountInfo<'info>,
+    amount: u64,
+    system_program: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+) -> Result<()> {
+    let owner_key = owner.key();
+    let wsol_sign = &[b"wsol", owner_key.as_ref(), &[wsol_bump]];
+    let space = TokenAccountState::LEN;
+    let lamports = Rent::get()?.minimum_balance(space) + amount;
+
+    if wsol.lamports() == 0 {
+        create_account(
+            CpiContext::new(
+                system_program.clone(),
+                CreateAccount {
+                    from: owner.clone(),
+                    to: wsol.clone(),
+                },
+            )
+            .with_signer(&[wsol_sign]),
+            lamports,
+            space as u64,
+            &Token::id(),
+        )?;
+    } else {
+        // Lamports sent to the address beforehand would make create_account fail
+        let top_up = lamports.saturating_sub(wsol.lamports());
+        if top_up > 0 {
+            transfer(
+                CpiContext::new(
+                    system_program.clone(),
+                    Transfer {
+                        from: owner.clone(),
+                        to: wsol.clone(),
+                    },
+                ),
+                top_up,
+            )?;
+        }
+        allocate(
+            CpiContext::new(
+                system_program.clone(),
+                Allocate {
+                    account_to_allocate: wsol.clone(),
+                },
+            )
+            .with_signer(&[wsol_sign]),
+            space as u64,
+        )?;
+        assign(
+            CpiContext::new(
+                system_program.clone(),
+                Assign {
+                    account_to_assign: wsol.clone(),
+                },
+            )
+            .with_signer(&[wsol_sign]),
+            &Token::id(),
+        )?;
+    }
+
+    initialize_account3(CpiContext::new(
+        token_program.clone(),
+        InitializeAccount3 {
+            account: wsol.clone(),
+            mint: native_mint.clone(),
+            authority: owner.clone(),
+        },
+    ))
+}
+
+/// Closes the owner's temporary wSOL account, returning its lamports to the owner.
+pub fn unwrap_sol<'info>(
+    owner: &AccountInfo<'info>,
+    wsol: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+) -> Result<()> {
+    close_account(CpiContext::new(
+        token_program.clone(),
+        CloseAccount {
+            account: wsol.clone(),
+            destination: owner.clone(),
+            authority: owner.clone(),
+        },
+    ))
+}
+
+/// Returns the full 256-bit product of two u128 values as (high, low) halves, which compare like the product.
+pub fn full_mul(a: u128, b: u128) -> (u128, u128) {
+    let mask = u64::MAX as u128;
+    let (a_hi, a_lo) = (a >> 64, a & mask);
+    let (b_hi, b_lo) = (b >> 64, b & mask);
+
+    let lo_lo = a_lo * b_lo;
+    let hi_lo = a_hi * b_lo;
+    let lo_hi = a_lo * b_hi;
+    let hi_hi = a_hi * b_hi;
+
+    let cross = (lo_lo >> 64) + (hi_lo & mask) + (lo_hi & mask);
+    let low = (cross << 64) | (lo_lo & mask);
+    let high = hi_hi + (hi_lo >> 64) + (lo_hi >> 64) + (cross >> 64);
+    (high, low)
+}
+
+/// Divides the 256-bit value (high, low) by `denominator`, returning None if the quotient overflows a u128.
+pub fn div_wide(high: u128, low: u128, denominator: u128) -> Option<u128> {
+    if denominator == 0 || high >= denominator {
+        return None;
+    }
+
+    // Long division one bit at a time, the remainder stays below the denominator
+    let mut quotient = 0u128;
+    let mut remainder = high;
+    for i in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> i) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= denominator {
+            remainder = remainder.wrapping_sub(denominator);
+            quotient |= 1;
+        }
+    }
+    Some(quotient)
+}
+
+/// Returns `a * b / denominator` rounded down, computed without the product overflowing.
+pub fn mul_div(a: u128, b: u128, denominator: u128) -> Option<u128> {
+    let (high, low) = full_mul(a, b);
+    div_wide(high, low, denominator)
+}
+
+/// Divides the 256-bit value (high, low) by `denominator` rounding up, returning None if the quotient overflows a u128.
+pub fn div_wide_ceil(high: u128, low: u128, denominator: u128) -> Option<u128> {
+    let quotient = div_wide(high, low, denominator)?;
+    // Round up unless the quotient times the denominator gives back the dividend
+    let (product_high, product_low) = full_mul(quotient, denominator);
+    if (product_high, product_low) == (high, low) {
+        Some(quotient)
+    } else {
+        quotient.checked_add(1)
+    }
+}
+
+/// Returns `a * b / denominator` rounded up, computed without the product overflowing.
+pub fn mul_div_ceil(a: u128, b: u128, denominator: u128) -> Option<u128> {
+    let (high, low) = full_mul(a, b);
+    div_wide_ceil(high, low, denominator)
+}
+
+/// Locks the pool and writes it back, so a nested call made through a CPI sees the lock.
+pub fn lock_pool(pool: &mut Account<Pool>) -> Result<()> {
+    pool.lock()?;
+    pool.exit(&crate::ID)
+}
+
+/// Locks the concentrated pool and writes it back, so a nested call made through a CPI sees the lock.
+pub fn lock_concentrated_pool(pool: &mut Account<ConcentratedPool>) -> Result<()> {
+    pool.lock()?;
+    pool.exit(&crate::ID)
+}
+
+/// Transfers `amount` of `mint` between token accounts, signing with `signer_seeds` if the authority is a PDA.
+/// Nothing is transferred for a zero amount.
+pub fn transfer_tokens<'info>(
+    token_program: &AccountInfo<'info>,
+    from: &AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    to: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+
+    transfer_checked(
+        CpiContext::new(
+            token_program.clone(),
+            TransferChecked {
+                from: from.clone(),
+                mint: mint.to_account_info(),
+                to: to.clone(),
+                authority: authority.clone(),
+            },
+        )
+        .with_signer(signer_seeds),
+        amount,
+        mint.decimals,
+    )
+}
@@ -0,0 +1,115 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke;
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+
+declare_id!("GL6eBy2Sqy14icobaxfQh7gyJLyxYf2AhHGjM7wRYe9E");
+
+/// Callback data of a flash swap, set by the borrower when calling `flash_swap`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct Repayment {
+    pub amount0: u64,         // Paid into vault0 from source0
+    pub amount1: u64,         // Paid into vault1 from source1
+    pub instruction: Vec<u8>, // Data of an instruction to call before paying, none if empty
+}
+
+/// Flash swap borrower for the AMM's tests, paying the pool back the amounts it is told to.
+#[program]
+pub mod flash_borrower {
+    use super::*;
+
+    /// Called by the AMM once the flash swap output is sent, the pool checks its invariant on return.
+    pub fn flash_swap_callback(
+        ctx: Context<FlashSwapCallback>,
+        _sender: Pubkey,
+        _amount0: u64,
+        _amount1: u64,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let repayment = Repayment::try_from_slice(&data)?;
+        let accounts = &ctx.accounts;
+
+        // Call the program first in the remaining accounts with the ones after it
+        if !repayment.instruction.is_empty() {
+            let (program, instruction_accounts) = ctx
+                .remaining_accounts
+                .split_first()
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            let metas = instruction_accounts
+                .iter()
+                .map(|account| match account.is_writable {
+                    true => AccountMeta::new(account.key(), account.is_signer),
+                    false => AccountMeta::new_readonly(account.key(), account.is_signer),
+                })
+                .collect();
+            invoke(
+                &Instruction {
+                    program_id: program.key(),
+                    accounts: metas,
+                    data: repayment.instruction,
+                },
+                ctx.remaining_accounts,
+            )?;
+        }
+
+        for (source, vault, mint, amount) in [
+            (
+                &accounts.source0,
+                &accounts.vault0,
+                &accounts.mint0,
+                repayment.amount0,
+            ),
+            (
+                &accounts.source1,
+                &accounts.vault1,
+                &accounts.mint1,
+                repayment.amount1,
+            ),
+        ] {
+            if amount == 0 {
+                continue;
+            }
+            transfer_checked(
+                CpiContext::new(
+                    accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: source.to_account_info(),
+                        mint: mint.to_account_info(),
+                        to: vault.to_account_info(),
+                        authority: accounts.sender.to_account_info(),
+                    },
+                ),
+                amount,
+                mint.decimals,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct FlashSwapCallback<'info> {
+    // Borrower, signing for the repayment
+    pub sender: Signer<'info>,
+
+    /// CHECK: pool flash swapping, passed by the AMM and not read
+    pub pool: AccountInfo<'info>,
+
+    // Borrower's token accounts and the pool's vaults they repay
+    #[account(mut, token::mint = mint0, token::authority = sender)]
+    pub source0: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, token::mint = mint0)]
+    pub vault0: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub mint0: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut, token::mint = mint1, token::authority = sender)]
+    pub source1: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, token::mint = mint1)]
+    pub vault1: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub mint1: Box<InterfaceAccount<'info, Mint>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    // The program to call and its accounts follow in the remaining accounts
+}
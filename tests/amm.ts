@@ -2,6 +2,7 @@ import * as anchor from '@coral-xyz/anchor';
 import { Program, web3, AnchorError } from '@coral-xyz/anchor';
 import * as token from '@solana/spl-token';
 import { Amm } from '../target/types/amm';
+import { FlashBorrower } from '../target/types/flash_borrower';
 import { expect } from 'chai';
 import { readLogs } from './helper';
 import { BN } from 'bn.js';
@@ -15,6 +16,7 @@ interface Pool {
     vault0: web3.PublicKey;
     vault1: web3.PublicKey;
     vaultLP: web3.PublicKey;
+    feeTier: web3.PublicKey;
     poolMint: web3.PublicKey;
     poolState: web3.PublicKey;
     poolAuthority: web3.PublicKey;
@@ -28,6 +30,7 @@ interface LPProvider {
 }
 
 const BASIS_POINTS = 10000;
+const MINIMUM_LIQUIDITY = new BN(1000);
 let fee = 30;
 
 describe('Amm', () => {
@@ -38,6 +41,7 @@ describe('Amm', () => {
     const wallet = provider.wallet;
 
     const program = anchor.workspace.amm as Program<Amm>;
+    const borrower = anchor.workspace.flashBorrower as Program<FlashBorrower>;
 
     const [configPDA] = web3.PublicKey.findProgramAddressSync(
         [Buffer.from(anchor.utils.bytes.utf8.encode('config'))],
@@ -45,17 +49,18 @@ describe('Amm', () => {
     );
     let n_decimals = 9;
     let pool: Pool;
+    let feeTo: web3.PublicKey = wallet.publicKey;
 
     it('Initialize', async () => {
         const tx = await program.methods
-            .initialize(provider.wallet.publicKey, new BN(fee))
+            .initialize(provider.wallet.publicKey)
             .rpc();
 
         const config = await program.account.config.fetch(configPDA);
 
         expect(config.owner.toBase58()).to.eq(wallet.publicKey.toBase58());
         expect(config.feeTo.toBase58()).to.eq(wallet.publicKey.toBase58());
-        expect(config.fee.eq(new BN(fee))).to.be.true;
+        expect(config.feeOn).to.be.true;
     });
 
     it('SetFeeTo', async () => {
@@ -68,12 +73,101 @@ describe('Amm', () => {
 
         const config = await program.account.config.fetch(configPDA);
         expect(config.feeTo.toBase58()).to.eq(newFeeTo.publicKey.toBase58());
+        feeTo = newFeeTo.publicKey;
     });
 
-    it('Set fee', async () => {
+    it('Transfer ownership', async () => {
+        const newOwner = web3.Keypair.generate();
+
+        // A cancelled proposal cannot be accepted
+        await program.methods
+            .proposeOwner(newOwner.publicKey)
+            .accounts({ config: configPDA })
+            .rpc();
+        let config = await program.account.config.fetch(configPDA);
+        expect(config.pendingOwner.toBase58()).to.eq(
+            newOwner.publicKey.toBase58()
+        );
+
+        await program.methods
+            .cancelOwnerTransfer()
+            .accounts({ config: configPDA })
+            .rpc();
+        config = await program.account.config.fetch(configPDA);
+        expect(config.pendingOwner.toBase58()).to.eq(
+            web3.PublicKey.default.toBase58()
+        );
+
+        try {
+            await program.methods
+                .acceptOwner()
+                .accounts({
+                    config: configPDA,
+                    pendingOwner: newOwner.publicKey,
+                })
+                .signers([newOwner])
+                .rpc();
+            expect.fail('should reject accepting a cancelled transfer');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq(
+                'NotPendingOwner'
+            );
+        }
+
+        // The pending owner accepts, then hands ownership back
+        await program.methods
+            .proposeOwner(newOwner.publicKey)
+            .accounts({ config: configPDA })
+            .rpc();
+        await program.methods
+            .acceptOwner()
+            .accounts({ config: configPDA, pendingOwner: newOwner.publicKey })
+            .signers([newOwner])
+            .rpc();
+        config = await program.account.config.fetch(configPDA);
+        expect(config.owner.toBase58()).to.eq(newOwner.publicKey.toBase58());
+        expect(config.pendingOwner.toBase58()).to.eq(
+            web3.PublicKey.default.toBase58()
+        );
+
+        // The old owner held the pause authority, so it moved with ownership
+        expect(config.pauseAuthority.toBase58()).to.eq(
+            newOwner.publicKey.toBase58()
+        );
+        try {
+            await program.methods
+                .setPaused(true)
+                .accounts({ config: configPDA })
+                .rpc();
+            expect.fail('should reject the old owner pausing');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq(
+                'ConstraintHasOne'
+            );
+        }
+
+        await program.methods
+            .proposeOwner(wallet.publicKey)
+            .accounts({ config: configPDA, owner: newOwner.publicKey })
+            .signers([newOwner])
+            .rpc();
+        await program.methods
+            .acceptOwner()
+            .accounts({ config: configPDA, pendingOwner: wallet.publicKey })
+            .rpc();
+        config = await program.account.config.fetch(configPDA);
+        expect(config.owner.toBase58()).to.eq(wallet.publicKey.toBase58());
+        expect(config.pauseAuthority.toBase58()).to.eq(
+            wallet.publicKey.toBase58()
+        );
+    });
+
+    it('Create fee tiers', async () => {
         try {
             await program.methods
-                .setFee(new BN(BASIS_POINTS))
+                .createFeeTier(new BN(BASIS_POINTS))
                 .accounts({ config: configPDA })
                 .rpc();
         } catch (e) {
@@ -84,13 +178,17 @@ describe('Amm', () => {
         // assign fee to 50
         fee = 50;
 
-        const tx = await program.methods
-            .setFee(new BN(fee))
-            .accounts({ config: configPDA })
-            .rpc();
+        for (let tier of [30, fee, 100]) {
+            await program.methods
+                .createFeeTier(new BN(tier))
+                .accounts({ config: configPDA })
+                .rpc();
 
-        const config = await program.account.config.fetch(configPDA);
-        expect(config.fee.eq(new BN(fee))).to.be.true;
+            const feeTier = await program.account.feeTier.fetch(
+                feeTierPDA(tier)
+            );
+            expect(feeTier.fee.eq(new BN(tier))).to.be.true;
+        }
     });
 
     it('Create pool', async () => {
@@ -120,8 +218,14 @@ describe('Amm', () => {
             [mint0, mint1] = [mint1, mint0];
         }
 
+        let feeTier = feeTierPDA(fee);
         let [poolState, poolState_b] = web3.PublicKey.findProgramAddressSync(
-            [Buffer.from('pool'), mint0.toBuffer(), mint1.toBuffer()],
+            [
+                Buffer.from('pool'),
+                mint0.toBuffer(),
+                mint1.toBuffer(),
+                feeTier.toBuffer(),
+            ],
             program.programId
         );
 
@@ -152,13 +256,17 @@ describe('Amm', () => {
 
         try {
             await program.methods
-                .createPool()
+                .createPool({ constantProduct: {} })
                 .accounts({
-                    owner: wallet.publicKey,
+                    creator: wallet.publicKey,
+                    feeTo,
                     mint0: mint0,
                     mint1: mint1,
+                    feeTier,
                     vault0: vault0.address,
                     vault1: vault1Address,
+                    tokenProgram0: token.TOKEN_PROGRAM_ID,
+                    tokenProgram1: token.TOKEN_PROGRAM_ID,
                 })
                 .rpc();
         } catch (e) {
@@ -175,25 +283,36 @@ describe('Amm', () => {
         );
 
         const tx = await program.methods
-            .createPool()
+            .createPool({ constantProduct: {} })
             .accounts({
-                owner: wallet.publicKey,
+                creator: wallet.publicKey,
+                feeTo,
                 mint0: mint0,
                 mint1: mint1,
+                feeTier,
                 vault0: vault0.address,
                 vault1: vault1.address,
+                tokenProgram0: token.TOKEN_PROGRAM_ID,
+                tokenProgram1: token.TOKEN_PROGRAM_ID,
             })
             .rpc();
 
         const poolData = await program.account.pool.fetch(poolState);
 
+        expect(poolData.bump).to.eq(poolState_b);
+        expect(poolData.creator.toBase58()).to.eq(wallet.publicKey.toBase58());
         expect(poolData.token0.toBase58()).to.eq(mint0.toBase58());
         expect(poolData.token1.toBase58()).to.eq(mint1.toBase58());
+        expect(poolData.vault0.toBase58()).to.eq(vault0.address.toBase58());
+        expect(poolData.vault1.toBase58()).to.eq(vault1.address.toBase58());
+        expect(poolData.feeTier.toBase58()).to.eq(feeTier.toBase58());
+        expect(poolData.fee.eq(new BN(fee))).to.be.true;
+        expect(poolData.reserve0.eq(new BN(0))).to.be.true;
+        expect(poolData.reserve1.eq(new BN(0))).to.be.true;
         expect(poolData.kLast.eq(new BN(0))).to.be.true;
+        expect(poolData.unlocked).to.be.true;
 
-        let vaultLp = await token.getOrCreateAssociatedTokenAccount(
-            connection,
-            auth,
+        let vaultLp = token.getAssociatedTokenAddressSync(
             poolMint,
             poolAuthority,
             true
@@ -206,13 +325,200 @@ describe('Amm', () => {
             mint1,
             vault0: vault0.address,
             vault1: vault1.address,
-            vaultLP: vaultLp.address,
+            vaultLP: vaultLp,
+            feeTier,
             poolMint,
             poolState,
             poolAuthority,
         };
     });
 
+    it('Create pool in another fee tier', async () => {
+        let feeTier = feeTierPDA(30);
+        let [poolState] = web3.PublicKey.findProgramAddressSync(
+            [
+                Buffer.from('pool'),
+                pool.mint0.toBuffer(),
+                pool.mint1.toBuffer(),
+                feeTier.toBuffer(),
+            ],
+            program.programId
+        );
+        let [poolAuthority] = web3.PublicKey.findProgramAddressSync(
+            [Buffer.from('authority'), poolState.toBuffer()],
+            program.programId
+        );
+        let vault0 = await token.getOrCreateAssociatedTokenAccount(
+            connection,
+            pool.auth,
+            pool.mint0,
+            poolAuthority,
+            true
+        );
+        let vault1 = await token.getOrCreateAssociatedTokenAccount(
+            connection,
+            pool.auth,
+            pool.mint1,
+            poolAuthority,
+            true
+        );
+
+        await program.methods
+            .createPool({ constantProduct: {} })
+            .accounts({
+                creator: wallet.publicKey,
+                feeTo,
+                mint0: pool.mint0,
+                mint1: pool.mint1,
+                feeTier,
+                vault0: vault0.address,
+                vault1: vault1.address,
+                tokenProgram0: token.TOKEN_PROGRAM_ID,
+                tokenProgram1: token.TOKEN_PROGRAM_ID,
+            })
+            .rpc();
+
+        // The same pair now has a pool per fee tier
+        expect(poolState.equals(pool.poolState)).to.be.false;
+        const poolData = await program.account.pool.fetch(poolState);
+        expect(poolData.feeTier.toBase58()).to.eq(feeTier.toBase58());
+        expect(poolData.fee.eq(new BN(30))).to.be.true;
+    });
+
+    it('Permissionless pool creation', async () => {
+        let creator = web3.Keypair.generate();
+        let sig = await connection.requestAirdrop(
+            creator.publicKey,
+            10 * web3.LAMPORTS_PER_SOL
+        );
+        await connection.confirmTransaction(sig, 'confirmed');
+
+        let mint0 = await token.createMint(
+            connection,
+            creator,
+            creator.publicKey,
+            null,
+            n_decimals
+        );
+        let mint1 = await token.createMint(
+            connection,
+            creator,
+            creator.publicKey,
+            null,
+            n_decimals
+        );
+        if (mint0.toBase58() > mint1.toBase58()) {
+            [mint0, mint1] = [mint1, mint0];
+        }
+
+        let [poolState] = web3.PublicKey.findProgramAddressSync(
+            [
+                Buffer.from('pool'),
+                mint0.toBuffer(),
+                mint1.toBuffer(),
+                pool.feeTier.toBuffer(),
+            ],
+            program.programId
+        );
+        let [poolAuthority] = web3.PublicKey.findProgramAddressSync(
+            [Buffer.from('authority'), poolState.toBuffer()],
+            program.programId
+        );
+        let vault0 = await token.getOrCreateAssociatedTokenAccount(
+            connection,
+            creator,
+            mint0,
+            poolAuthority,
+            true
+        );
+        let vault1 = await token.getOrCreateAssociatedTokenAccount(
+            connection,
+            creator,
+            mint1,
+            poolAuthority,
+            true
+        );
+        let createPool = () =>
+            program.methods
+                .createPool({ constantProduct: {} })
+                .accounts({
+                    creator: creator.publicKey,
+                    feeTo,
+                    mint0,
+                    mint1,
+                    feeTier: pool.feeTier,
+                    vault0: vault0.address,
+                    vault1: vault1.address,
+                    tokenProgram0: token.TOKEN_PROGRAM_ID,
+                    tokenProgram1: token.TOKEN_PROGRAM_ID,
+                })
+                .signers([creator])
+                .rpc();
+
+        // Only the owner can create pools by default
+        try {
+            await createPool();
+            expect.fail('should restrict pool creation to the owner');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq(
+                'PoolCreationRestricted'
+            );
+        }
+
+        let creationFee = new BN(web3.LAMPORTS_PER_SOL / 10);
+        await program.methods
+            .setPoolCreation(true, creationFee)
+            .accounts({ config: configPDA })
+            .rpc();
+
+        let feeToLamportsBefore = await connection.getBalance(feeTo);
+        await createPool();
+        let feeToLamportsAfter = await connection.getBalance(feeTo);
+
+        expect(feeToLamportsAfter - feeToLamportsBefore).to.eq(
+            creationFee.toNumber()
+        );
+        const poolData = await program.account.pool.fetch(poolState);
+        expect(poolData.creator.toBase58()).to.eq(
+            creator.publicKey.toBase58()
+        );
+
+        await program.methods
+            .setPoolCreation(false, new BN(0))
+            .accounts({ config: configPDA })
+            .rpc();
+    });
+
+    it('Set pool fee', async () => {
+        await program.methods
+            .setPoolFee()
+            .accounts({ pool: pool.poolState, feeTier: feeTierPDA(100) })
+            .rpc();
+        let poolData = await program.account.pool.fetch(pool.poolState);
+        expect(poolData.fee.eq(new BN(100))).to.be.true;
+
+        // The pool keeps its address, only the fee changes
+        expect(poolData.feeTier.toBase58()).to.eq(pool.feeTier.toBase58());
+
+        await program.methods
+            .setPoolFee()
+            .accounts({ pool: pool.poolState, feeTier: pool.feeTier })
+            .rpc();
+        poolData = await program.account.pool.fetch(pool.poolState);
+        expect(poolData.fee.eq(new BN(fee))).to.be.true;
+    });
+
+    it('Delete fee tier', async () => {
+        await program.methods
+            .deleteFeeTier()
+            .accounts({ config: configPDA, feeTier: feeTierPDA(100) })
+            .rpc();
+
+        let feeTier = await connection.getAccountInfo(feeTierPDA(100));
+        expect(feeTier).to.be.null;
+    });
+
     let lpUser0: LPProvider;
     let liquidityAdded: anchor.BN;
     let src_amount0_in = lp_amount(50);
@@ -243,17 +549,24 @@ describe('Amm', () => {
                 pool: pool.poolState,
                 vault0: pool.vault0,
                 vault1: pool.vault1,
-                vaultLp: pool.vaultLP,
+                feeTo,
                 userAta0: lpUser0.userAta0,
                 userAta1: lpUser0.userAta1,
+                mint0: pool.mint0,
+                mint1: pool.mint1,
+                tokenProgram0: token.TOKEN_PROGRAM_ID,
+                tokenProgram1: token.TOKEN_PROGRAM_ID,
                 userLpAta: lpUser0.lpAta,
             })
             .signers([lpUser0.signer])
             .rpc();
 
         let poolData = await program.account.pool.fetch(pool.poolState);
-        expect(poolData.kLast.eq(src_amount0_in.mul(src_amount1_in))).to.be
-            .true;
+        expect(poolData.reserve0.eq(src_amount0_in)).to.be.true;
+        expect(poolData.reserve1.eq(src_amount1_in)).to.be.true;
+        expect(
+            poolData.kLast.eq(sqrt(src_amount0_in.mul(src_amount1_in)))
+        ).to.be.true;
         let userMint0Balance = await connection.getTokenAccountBalance(
             userAta0
         );
@@ -268,9 +581,18 @@ describe('Amm', () => {
         expect(userMint1Balance.value.amount).to.be.eq(
             lp_amount(100).sub(src_amount1_in).toString()
         );
-        liquidityAdded = sqrt(src_amount0_in.mul(src_amount1_in));
+        liquidityAdded = sqrt(src_amount0_in.mul(src_amount1_in)).sub(
+            MINIMUM_LIQUIDITY
+        );
         expect(userLpBalance.value.amount).to.be.eq(liquidityAdded.toString());
 
+        let vaultLpBalance = await connection.getTokenAccountBalance(
+            pool.vaultLP
+        );
+        expect(vaultLpBalance.value.amount).to.be.eq(
+            MINIMUM_LIQUIDITY.toString()
+        );
+
         let poolMint0Balance = await connection.getTokenAccountBalance(
             pool.vault0
         );
@@ -285,29 +607,78 @@ describe('Amm', () => {
         );
 
         let poolLp = await connection.getTokenSupply(pool.poolMint);
-        expect(poolLp.value.amount).to.be.eq(userLpBalance.value.amount);
+        expect(poolLp.value.amount).to.be.eq(
+            liquidityAdded.add(MINIMUM_LIQUIDITY).toString()
+        );
 
-        // Reserve pool input
+        // Reversed vaults are rejected, vaults are bound to the pool
 
-        await program.methods
-            .addLiquidity(
-                src_amount1_in,
-                src_amount0_in,
-                src_amount1_in,
-                src_amount0_in
-            )
-            .accounts({
-                owner: lp_user_signer.publicKey,
-                pool: pool.poolState,
-                vault0: pool.vault1,
-                vault1: pool.vault0,
-                vaultLp: pool.vaultLP,
-                userAta0: lpUser0.userAta1,
-                userAta1: lpUser0.userAta0,
-                userLpAta: lpUser0.lpAta,
-            })
-            .signers([lpUser0.signer])
-            .rpc();
+        try {
+            await program.methods
+                .addLiquidity(
+                    src_amount1_in,
+                    src_amount0_in,
+                    src_amount1_in,
+                    src_amount0_in
+                )
+                .accounts({
+                    owner: lp_user_signer.publicKey,
+                    pool: pool.poolState,
+                    vault0: pool.vault1,
+                    vault1: pool.vault0,
+                    feeTo,
+                    userAta0: lpUser0.userAta1,
+                    userAta1: lpUser0.userAta0,
+                    mint0: pool.mint0,
+                    mint1: pool.mint1,
+                    tokenProgram0: token.TOKEN_PROGRAM_ID,
+                    tokenProgram1: token.TOKEN_PROGRAM_ID,
+                    userLpAta: lpUser0.lpAta,
+                })
+                .signers([lpUser0.signer])
+                .rpc();
+            expect.fail('should reject vaults in the wrong order');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq(
+                'InvalidVault'
+            );
+        }
+    });
+
+    it('Swap with foreign vault', async () => {
+        let foreignVault = await token.createAccount(
+            connection,
+            pool.payer,
+            pool.mint1,
+            pool.auth.publicKey,
+            web3.Keypair.generate()
+        );
+
+        try {
+            await program.methods
+                .swapExactInput(lp_amount(1), new anchor.BN(0))
+                .accounts({
+                    owner: lpUser0.signer.publicKey,
+                    pool: pool.poolState,
+                    userAtaSrc: lpUser0.userAta0,
+                    userAtaDes: lpUser0.userAta1,
+                    vaultSrc: pool.vault0,
+                    vaultDes: foreignVault,
+                    mintSrc: pool.mint0,
+                    mintDes: pool.mint1,
+                    tokenProgramSrc: token.TOKEN_PROGRAM_ID,
+                    tokenProgramDes: token.TOKEN_PROGRAM_ID,
+                })
+                .signers([lpUser0.signer])
+                .rpc();
+            expect.fail('should reject vaults not bound to the pool');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq(
+                'InvalidVault'
+            );
+        }
     });
 
     it('Remove liquidity', async () => {
@@ -320,20 +691,31 @@ describe('Amm', () => {
         let userLpBalanceBefore = await connection.getTokenAccountBalance(
             lpUser0.lpAta
         );
+        let lpSupply = await connection.getTokenSupply(pool.poolMint);
+
+        // The locked minimum liquidity keeps a sliver of each reserve in the pool
+        let liquidity = liquidityAdded.div(new anchor.BN(2));
+        let amount0 = liquidity
+            .mul(src_amount0_in)
+            .div(new BN(lpSupply.value.amount));
+        let amount1 = liquidity
+            .mul(src_amount1_in)
+            .div(new BN(lpSupply.value.amount));
+
         await program.methods
-            .removeLiquidity(
-                liquidityAdded.div(new anchor.BN(2)),
-                src_amount0_in.div(new anchor.BN(2)),
-                src_amount1_in.div(new anchor.BN(2))
-            )
+            .removeLiquidity(liquidity, amount0, amount1)
             .accounts({
                 owner: lpUser0.signer.publicKey,
                 pool: pool.poolState,
                 vault0: pool.vault0,
                 vault1: pool.vault1,
-                vaultLp: pool.vaultLP,
+                feeTo,
                 userAta0: lpUser0.userAta0,
                 userAta1: lpUser0.userAta1,
+                mint0: pool.mint0,
+                mint1: pool.mint1,
+                tokenProgram0: token.TOKEN_PROGRAM_ID,
+                tokenProgram1: token.TOKEN_PROGRAM_ID,
                 userLpAta: lpUser0.lpAta,
             })
             .signers([lpUser0.signer])
@@ -351,17 +733,17 @@ describe('Amm', () => {
         expect(
             new BN(userMint0BalanceAfter.value.amount)
                 .sub(new BN(userMint0BalanceBefore.value.amount))
-                .eq(src_amount0_in.div(new anchor.BN(2)))
+                .eq(amount0)
         ).to.be.true;
         expect(
             new BN(userMint1BalanceAfter.value.amount)
                 .sub(new BN(userMint1BalanceBefore.value.amount))
-                .eq(src_amount1_in.div(new anchor.BN(2)))
+                .eq(amount1)
         ).to.be.true;
         expect(
             new BN(userLpBalanceBefore.value.amount)
                 .sub(new BN(userLpBalanceAfter.value.amount))
-                .eq(liquidityAdded.div(new anchor.BN(2)))
+                .eq(liquidity)
         ).to.be.true;
     });
 
@@ -398,6 +780,10 @@ describe('Amm', () => {
                 userAtaDes: lpUser0.userAta1,
                 vaultSrc: pool.vault0,
                 vaultDes: pool.vault1,
+                mintSrc: pool.mint0,
+                mintDes: pool.mint1,
+                tokenProgramSrc: token.TOKEN_PROGRAM_ID,
+                tokenProgramDes: token.TOKEN_PROGRAM_ID,
             })
             .signers([lpUser0.signer])
             .rpc();
@@ -419,6 +805,10 @@ describe('Amm', () => {
                 .sub(new BN(userMint1BalanceBefore.value.amount))
                 .eq(amountOut)
         ).to.be.true;
+
+        // The pool is unlocked again once the swap returns
+        let poolData = await program.account.pool.fetch(pool.poolState);
+        expect(poolData.unlocked).to.be.true;
     });
 
     it('Swap exact output', async () => {
@@ -454,6 +844,10 @@ describe('Amm', () => {
                 userAtaDes: lpUser0.userAta1,
                 vaultSrc: pool.vault0,
                 vaultDes: pool.vault1,
+                mintSrc: pool.mint0,
+                mintDes: pool.mint1,
+                tokenProgramSrc: token.TOKEN_PROGRAM_ID,
+                tokenProgramDes: token.TOKEN_PROGRAM_ID,
             })
             .signers([lpUser0.signer])
             .rpc();
@@ -476,26 +870,3110 @@ describe('Amm', () => {
                 .sub(new BN(userMint1BalanceBefore.value.amount))
                 .eq(amountOut)
         ).to.be.true;
+
+        // The whole reserve cannot be bought at any price
+        let poolData = await program.account.pool.fetch(pool.poolState);
+        try {
+            await program.methods
+                .swapExactOutput(
+                    poolData.reserve1,
+                    new BN('18446744073709551615')
+                )
+                .accounts({
+                    owner: lpUser0.signer.publicKey,
+                    pool: pool.poolState,
+                    userAtaSrc: lpUser0.userAta0,
+                    userAtaDes: lpUser0.userAta1,
+                    vaultSrc: pool.vault0,
+                    vaultDes: pool.vault1,
+                    mintSrc: pool.mint0,
+                    mintDes: pool.mint1,
+                    tokenProgramSrc: token.TOKEN_PROGRAM_ID,
+                    tokenProgramDes: token.TOKEN_PROGRAM_ID,
+                })
+                .signers([lpUser0.signer])
+                .rpc();
+            expect.fail('should reject buying the whole reserve');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq(
+                'InsufficientLiquidity'
+            );
+        }
     });
 
-    async function setup_lp_provider(user: web3.PublicKey, amount: number) {
-        // setup token accs for deposit
-        let mint0_ata = await token.createAssociatedTokenAccount(
-            connection,
-            pool.payer,
-            pool.mint0,
-            user
+    it('Swap deadline', async () => {
+        let accounts = {
+            owner: lpUser0.signer.publicKey,
+            pool: pool.poolState,
+            userAtaSrc: lpUser0.userAta0,
+            userAtaDes: lpUser0.userAta1,
+            vaultSrc: pool.vault0,
+            vaultDes: pool.vault1,
+            mintSrc: pool.mint0,
+            mintDes: pool.mint1,
+            tokenProgramSrc: token.TOKEN_PROGRAM_ID,
+            tokenProgramDes: token.TOKEN_PROGRAM_ID,
+        };
+        let now = Math.floor(Date.now() / 1000);
+
+        try {
+            await program.methods
+                .swapExactInputWithDeadline(
+                    lp_amount(1),
+                    new BN(0),
+                    new BN(now - 60)
+                )
+                .accounts(accounts)
+                .signers([lpUser0.signer])
+                .rpc();
+            expect.fail('should reject a swap past its deadline');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq('Expired');
+        }
+
+        let userMint0BalanceBefore = await connection.getTokenAccountBalance(
+            lpUser0.userAta0
         );
-        let mint1_ata = await token.createAssociatedTokenAccount(
-            connection,
-            pool.payer,
-            pool.mint1,
-            user
+        await program.methods
+            .swapExactInputWithDeadline(
+                lp_amount(1),
+                new BN(0),
+                new BN(now + 60)
+            )
+            .accounts(accounts)
+            .signers([lpUser0.signer])
+            .rpc();
+        let userMint0BalanceAfter = await connection.getTokenAccountBalance(
+            lpUser0.userAta0
         );
+        expect(
+            new BN(userMint0BalanceBefore.value.amount)
+                .sub(new BN(userMint0BalanceAfter.value.amount))
+                .eq(lp_amount(1))
+        ).to.be.true;
 
-        // setup token accs for LP pool tokens
-        let lp_mint_ata = await token.createAssociatedTokenAccount(
-            connection,
+        try {
+            await program.methods
+                .swapExactOutputWithDeadline(
+                    lp_amount(1),
+                    lp_amount(10),
+                    new BN(now - 60)
+                )
+                .accounts(accounts)
+                .signers([lpUser0.signer])
+                .rpc();
+            expect.fail('should reject a swap past its deadline');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq('Expired');
+        }
+
+        let userMint1BalanceBefore = await connection.getTokenAccountBalance(
+            lpUser0.userAta1
+        );
+        await program.methods
+            .swapExactOutputWithDeadline(
+                lp_amount(1),
+                lp_amount(10),
+                new BN(now + 60)
+            )
+            .accounts(accounts)
+            .signers([lpUser0.signer])
+            .rpc();
+        let userMint1BalanceAfter = await connection.getTokenAccountBalance(
+            lpUser0.userAta1
+        );
+        expect(
+            new BN(userMint1BalanceAfter.value.amount)
+                .sub(new BN(userMint1BalanceBefore.value.amount))
+                .eq(lp_amount(1))
+        ).to.be.true;
+    });
+
+    it('Protocol fee', async () => {
+        let poolData = await program.account.pool.fetch(pool.poolState);
+        let vault0Balance = await connection.getTokenAccountBalance(
+            pool.vault0
+        );
+        let vault1Balance = await connection.getTokenAccountBalance(
+            pool.vault1
+        );
+        let lpSupply = await connection.getTokenSupply(pool.poolMint);
+
+        // Swaps grew k, so the protocol takes 1/6th of the growth in sqrt(k)
+        let rootK = sqrt(
+            new BN(vault0Balance.value.amount).mul(
+                new BN(vault1Balance.value.amount)
+            )
+        );
+        let rootKLast = poolData.kLast;
+        let expectedFee = new BN(lpSupply.value.amount)
+            .mul(rootK.sub(rootKLast))
+            .div(rootK.mul(new BN(5)).add(rootKLast));
+        expect(expectedFee.gt(new BN(0))).to.be.true;
+
+        let feeToLpAta = token.getAssociatedTokenAddressSync(
+            pool.poolMint,
+            feeTo
+        );
+
+        await program.methods
+            .removeLiquidity(new BN(1000), new BN(0), new BN(0))
+            .accounts({
+                owner: lpUser0.signer.publicKey,
+                pool: pool.poolState,
+                vault0: pool.vault0,
+                vault1: pool.vault1,
+                feeTo,
+                userAta0: lpUser0.userAta0,
+                userAta1: lpUser0.userAta1,
+                mint0: pool.mint0,
+                mint1: pool.mint1,
+                tokenProgram0: token.TOKEN_PROGRAM_ID,
+                tokenProgram1: token.TOKEN_PROGRAM_ID,
+                userLpAta: lpUser0.lpAta,
+            })
+            .signers([lpUser0.signer])
+            .rpc();
+
+        let feeToLpBalance = await connection.getTokenAccountBalance(
+            feeToLpAta
+        );
+        expect(feeToLpBalance.value.amount).to.eq(expectedFee.toString());
+    });
+
+    it('Set fee on', async () => {
+        await program.methods
+            .setFeeOn(false)
+            .accounts({ config: configPDA })
+            .rpc();
+
+        let config = await program.account.config.fetch(configPDA);
+        expect(config.feeOn).to.be.false;
+
+        await program.methods
+            .removeLiquidity(new BN(1000), new BN(0), new BN(0))
+            .accounts({
+                owner: lpUser0.signer.publicKey,
+                pool: pool.poolState,
+                vault0: pool.vault0,
+                vault1: pool.vault1,
+                feeTo,
+                userAta0: lpUser0.userAta0,
+                userAta1: lpUser0.userAta1,
+                mint0: pool.mint0,
+                mint1: pool.mint1,
+                tokenProgram0: token.TOKEN_PROGRAM_ID,
+                tokenProgram1: token.TOKEN_PROGRAM_ID,
+                userLpAta: lpUser0.lpAta,
+            })
+            .signers([lpUser0.signer])
+            .rpc();
+
+        // With the fee off, k_last is cleared so no fee accrues retroactively
+        let poolData = await program.account.pool.fetch(pool.poolState);
+        expect(poolData.kLast.eq(new BN(0))).to.be.true;
+
+        await program.methods
+            .setFeeOn(true)
+            .accounts({ config: configPDA })
+            .rpc();
+    });
+
+    it('Liquidity deadline', async () => {
+        let accounts = {
+            owner: lpUser0.signer.publicKey,
+            pool: pool.poolState,
+            vault0: pool.vault0,
+            vault1: pool.vault1,
+            feeTo,
+            userAta0: lpUser0.userAta0,
+            userAta1: lpUser0.userAta1,
+            mint0: pool.mint0,
+            mint1: pool.mint1,
+            tokenProgram0: token.TOKEN_PROGRAM_ID,
+            tokenProgram1: token.TOKEN_PROGRAM_ID,
+            userLpAta: lpUser0.lpAta,
+        };
+        let lpBalance = async () =>
+            new BN(
+                (
+                    await connection.getTokenAccountBalance(lpUser0.lpAta)
+                ).value.amount
+            );
+        let now = Math.floor(Date.now() / 1000);
+        let expectExpired = async (operation: () => Promise<string>) => {
+            try {
+                await operation();
+                expect.fail('should reject an operation past its deadline');
+            } catch (e) {
+                expect(e).to.be.instanceOf(AnchorError);
+                expect((e as AnchorError).error.errorCode.code).to.eq(
+                    'Expired'
+                );
+            }
+        };
+        let addLiquidity = (deadline: number) =>
+            program.methods
+                .addLiquidityWithDeadline(
+                    lp_amount(1),
+                    lp_amount(1),
+                    new BN(0),
+                    new BN(0),
+                    new BN(deadline)
+                )
+                .accounts(accounts)
+                .signers([lpUser0.signer])
+                .rpc();
+        let removeLiquidity = (deadline: number) =>
+            program.methods
+                .removeLiquidityWithDeadline(
+                    new BN(1000),
+                    new BN(0),
+                    new BN(0),
+                    new BN(deadline)
+                )
+                .accounts(accounts)
+                .signers([lpUser0.signer])
+                .rpc();
+
+        await expectExpired(() => addLiquidity(now - 60));
+        let before = await lpBalance();
+        await addLiquidity(now + 60);
+        expect((await lpBalance()).gt(before)).to.be.true;
+
+        await expectExpired(() => removeLiquidity(now - 60));
+        before = await lpBalance();
+        await removeLiquidity(now + 60);
+        expect(before.sub(await lpBalance()).eqn(1000)).to.be.true;
+    });
+
+    it('Pause', async () => {
+        let swap = () =>
+            program.methods
+                .swapExactInput(lp_amount(1), new BN(0))
+                .accounts({
+                    owner: lpUser0.signer.publicKey,
+                    pool: pool.poolState,
+                    userAtaSrc: lpUser0.userAta0,
+                    userAtaDes: lpUser0.userAta1,
+                    vaultSrc: pool.vault0,
+                    vaultDes: pool.vault1,
+                    mintSrc: pool.mint0,
+                    mintDes: pool.mint1,
+                    tokenProgramSrc: token.TOKEN_PROGRAM_ID,
+                    tokenProgramDes: token.TOKEN_PROGRAM_ID,
+                })
+                .signers([lpUser0.signer])
+                .rpc();
+        let liquidityAccounts = {
+            owner: lpUser0.signer.publicKey,
+            pool: pool.poolState,
+            vault0: pool.vault0,
+            vault1: pool.vault1,
+            feeTo,
+            userAta0: lpUser0.userAta0,
+            userAta1: lpUser0.userAta1,
+            mint0: pool.mint0,
+            mint1: pool.mint1,
+            tokenProgram0: token.TOKEN_PROGRAM_ID,
+            tokenProgram1: token.TOKEN_PROGRAM_ID,
+            userLpAta: lpUser0.lpAta,
+        };
+        let expectPaused = async (tx: Promise<string>) => {
+            try {
+                await tx;
+                expect.fail('should be paused');
+            } catch (e) {
+                expect(e).to.be.instanceOf(AnchorError);
+                expect((e as AnchorError).error.errorCode.code).to.eq(
+                    'Paused'
+                );
+            }
+        };
+
+        // A global pause blocks swaps and deposits, but LPs can still exit
+        await program.methods
+            .setPaused(true)
+            .accounts({ config: configPDA })
+            .rpc();
+        let config = await program.account.config.fetch(configPDA);
+        expect(config.paused).to.be.true;
+
+        await expectPaused(swap());
+        await expectPaused(
+            program.methods
+                .addLiquidity(lp_amount(1), lp_amount(1), new BN(0), new BN(0))
+                .accounts(liquidityAccounts)
+                .signers([lpUser0.signer])
+                .rpc()
+        );
+        await program.methods
+            .removeLiquidity(new BN(1000), new BN(0), new BN(0))
+            .accounts(liquidityAccounts)
+            .signers([lpUser0.signer])
+            .rpc();
+
+        await program.methods
+            .setPaused(false)
+            .accounts({ config: configPDA })
+            .rpc();
+
+        // A pool pause only blocks that pool
+        await program.methods
+            .setPoolPaused(true)
+            .accounts({ pool: pool.poolState })
+            .rpc();
+        let poolData = await program.account.pool.fetch(pool.poolState);
+        expect(poolData.paused).to.be.true;
+        await expectPaused(swap());
+
+        await program.methods
+            .setPoolPaused(false)
+            .accounts({ pool: pool.poolState })
+            .rpc();
+        await swap();
+    });
+
+    it('Set pause authority', async () => {
+        let pauseAuthority = web3.Keypair.generate();
+        let expectHasOne = async (tx: Promise<string>) => {
+            try {
+                await tx;
+                expect.fail('should reject the signer');
+            } catch (e) {
+                expect(e).to.be.instanceOf(AnchorError);
+                expect((e as AnchorError).error.errorCode.code).to.eq(
+                    'ConstraintHasOne'
+                );
+            }
+        };
+
+        // Only the owner can rotate the pause authority
+        await expectHasOne(
+            program.methods
+                .setPauseAuthority(pauseAuthority.publicKey)
+                .accounts({
+                    config: configPDA,
+                    owner: pauseAuthority.publicKey,
+                })
+                .signers([pauseAuthority])
+                .rpc()
+        );
+        await program.methods
+            .setPauseAuthority(pauseAuthority.publicKey)
+            .accounts({ config: configPDA })
+            .rpc();
+        let config = await program.account.config.fetch(configPDA);
+        expect(config.pauseAuthority.toBase58()).to.eq(
+            pauseAuthority.publicKey.toBase58()
+        );
+
+        // The old authority can no longer pause, the new one can
+        await expectHasOne(
+            program.methods
+                .setPaused(true)
+                .accounts({ config: configPDA })
+                .rpc()
+        );
+        await expectHasOne(
+            program.methods
+                .setPoolPaused(true)
+                .accounts({ pool: pool.poolState })
+                .rpc()
+        );
+        for (let paused of [true, false]) {
+            await program.methods
+                .setPaused(paused)
+                .accounts({
+                    config: configPDA,
+                    pauseAuthority: pauseAuthority.publicKey,
+                })
+                .signers([pauseAuthority])
+                .rpc();
+            config = await program.account.config.fetch(configPDA);
+            expect(config.paused).to.eq(paused);
+        }
+
+        await program.methods
+            .setPauseAuthority(wallet.publicKey)
+            .accounts({ config: configPDA })
+            .rpc();
+    });
+
+    it('Sync and skim', async () => {
+        let donation = lp_amount(1);
+        let recipient = web3.Keypair.generate();
+        let to0 = await token.createAssociatedTokenAccount(
+            connection,
+            pool.payer,
+            pool.mint0,
+            recipient.publicKey
+        );
+        let to1 = await token.createAssociatedTokenAccount(
+            connection,
+            pool.payer,
+            pool.mint1,
+            recipient.publicKey
+        );
+
+        // Donations do not move the tracked reserves
+        let poolBefore = await program.account.pool.fetch(pool.poolState);
+        await token.mintTo(
+            connection,
+            pool.payer,
+            pool.mint0,
+            pool.vault0,
+            pool.auth,
+            BigInt(donation.toString())
+        );
+        let poolData = await program.account.pool.fetch(pool.poolState);
+        expect(poolData.reserve0.eq(poolBefore.reserve0)).to.be.true;
+
+        // Skim sweeps the excess out of the vaults
+        await program.methods
+            .skim()
+            .accounts({
+                pool: pool.poolState,
+                vault0: pool.vault0,
+                vault1: pool.vault1,
+                to0,
+                to1,
+                mint0: pool.mint0,
+                mint1: pool.mint1,
+                tokenProgram0: token.TOKEN_PROGRAM_ID,
+                tokenProgram1: token.TOKEN_PROGRAM_ID,
+            })
+            .rpc();
+
+        let to0Balance = await connection.getTokenAccountBalance(to0);
+        let to1Balance = await connection.getTokenAccountBalance(to1);
+        expect(to0Balance.value.amount).to.eq(donation.toString());
+        expect(to1Balance.value.amount).to.eq('0');
+
+        // Sync absorbs the excess into the reserves
+        await token.mintTo(
+            connection,
+            pool.payer,
+            pool.mint1,
+            pool.vault1,
+            pool.auth,
+            BigInt(donation.toString())
+        );
+        await program.methods
+            .sync()
+            .accounts({
+                pool: pool.poolState,
+                vault0: pool.vault0,
+                vault1: pool.vault1,
+            })
+            .rpc();
+
+        poolData = await program.account.pool.fetch(pool.poolState);
+        let vault0Balance = await connection.getTokenAccountBalance(
+            pool.vault0
+        );
+        let vault1Balance = await connection.getTokenAccountBalance(
+            pool.vault1
+        );
+        expect(poolData.reserve0.toString()).to.eq(vault0Balance.value.amount);
+        expect(poolData.reserve1.toString()).to.eq(vault1Balance.value.amount);
+        expect(poolData.reserve1.eq(poolBefore.reserve1.add(donation))).to.be
+            .true;
+    });
+
+    it('Price accumulators', async () => {
+        let before = await program.account.pool.fetch(pool.poolState);
+        expect(before.blockTimestampLast.gtn(0)).to.be.true;
+
+        // Let time pass, then record the reserves again
+        await new Promise((resolve) => setTimeout(resolve, 2000));
+        await program.methods
+            .sync()
+            .accounts({
+                pool: pool.poolState,
+                vault0: pool.vault0,
+                vault1: pool.vault1,
+            })
+            .rpc();
+        let after = await program.account.pool.fetch(pool.poolState);
+
+        // Each accumulator grows by the UQ64.64 price held times the seconds elapsed
+        let elapsed = after.blockTimestampLast.sub(before.blockTimestampLast);
+        expect(elapsed.gtn(0)).to.be.true;
+        let price0 = before.reserve1.shln(64).div(before.reserve0);
+        let price1 = before.reserve0.shln(64).div(before.reserve1);
+        expect(
+            after.price0CumulativeLast
+                .sub(before.price0CumulativeLast)
+                .eq(price0.mul(elapsed))
+        ).to.be.true;
+        expect(
+            after.price1CumulativeLast
+                .sub(before.price1CumulativeLast)
+                .eq(price1.mul(elapsed))
+        ).to.be.true;
+    });
+
+    it('Observation oracle', async () => {
+        let [observationsPDA] = web3.PublicKey.findProgramAddressSync(
+            [Buffer.from('observations'), pool.poolState.toBuffer()],
+            program.programId
+        );
+
+        // Anyone can grow the ring buffer, up to its capacity
+        await program.methods
+            .increaseObservationCardinality(4)
+            .accounts({ pool: pool.poolState })
+            .rpc();
+        let observations = await program.account.observations.fetch(
+            observationsPDA
+        );
+        expect(observations.pool.equals(pool.poolState)).to.be.true;
+        expect(observations.cardinalityNext).to.eq(4);
+
+        try {
+            await program.methods
+                .increaseObservationCardinality(1000)
+                .accounts({ pool: pool.poolState })
+                .rpc();
+            expect.fail('should reject a cardinality above the capacity');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq(
+                'ObservationCardinalityTooLarge'
+            );
+        }
+
+        // The reserves have not moved, so one second adds exactly one price
+        let poolData = await program.account.pool.fetch(pool.poolState);
+        let [now, oneSecondAgo] = await program.methods
+            .observe([0, 1])
+            .accounts({ pool: pool.poolState })
+            .view();
+        let price0 = poolData.reserve1.shln(64).div(poolData.reserve0);
+        let price1 = poolData.reserve0.shln(64).div(poolData.reserve1);
+        expect(
+            now.price0Cumulative.sub(oneSecondAgo.price0Cumulative).eq(price0)
+        ).to.be.true;
+        expect(
+            now.price1Cumulative.sub(oneSecondAgo.price1Cumulative).eq(price1)
+        ).to.be.true;
+        expect(
+            now.liquidityCumulative
+                .sub(oneSecondAgo.liquidityCumulative)
+                .eq(sqrt(poolData.reserve0.mul(poolData.reserve1)))
+        ).to.be.true;
+    });
+
+    it('Observation oracle interpolates after growing', async () => {
+        let [observationsPDA] = web3.PublicKey.findProgramAddressSync(
+            [Buffer.from('observations'), pool.poolState.toBuffer()],
+            program.programId
+        );
+
+        // Grow well past the observations recorded below, so the ring is left partly unwritten
+        await program.methods
+            .increaseObservationCardinality(10)
+            .accounts({ pool: pool.poolState })
+            .rpc();
+        for (let i = 0; i < 3; i++) {
+            await new Promise((resolve) => setTimeout(resolve, 4000));
+            await program.methods
+                .sync()
+                .accounts({
+                    pool: pool.poolState,
+                    vault0: pool.vault0,
+                    vault1: pool.vault1,
+                })
+                .rpc();
+        }
+        let observations = await program.account.observations.fetch(
+            observationsPDA
+        );
+        expect(observations.cardinality).to.eq(10);
+        expect(observations.index).to.eq(3);
+        expect(observations.observations[4].initialized).to.eq(0);
+
+        // Ask for a time between the first two observations recorded after growing
+        let before = observations.observations[1];
+        let after = observations.observations[2];
+        let now = await connection.getBlockTime(await connection.getSlot());
+        let target = before.blockTimestamp.toNumber() + 1;
+        let [observed] = await program.methods
+            .observe([now - target])
+            .accounts({ pool: pool.poolState })
+            .view();
+
+        // The reserves have not moved, so the interpolated cumulative is a whole number of prices past the earlier one
+        let poolData = await program.account.pool.fetch(pool.poolState);
+        let price0 = poolData.reserve1.shln(64).div(poolData.reserve0);
+        expect(observed.price0Cumulative.gte(before.price0Cumulative)).to.be
+            .true;
+        expect(observed.price0Cumulative.lt(after.price0Cumulative)).to.be.true;
+        expect(
+            observed.price0Cumulative
+                .sub(before.price0Cumulative)
+                .mod(price0)
+                .isZero()
+        ).to.be.true;
+    });
+
+    it('First-depositor inflation attack does not profit', async () => {
+        // A fresh pool, so the attacker is the first depositor
+        let mint0 = await token.createMint(
+            connection,
+            pool.auth,
+            pool.auth.publicKey,
+            pool.auth.publicKey,
+            n_decimals
+        );
+        let mint1 = await token.createMint(
+            connection,
+            pool.auth,
+            pool.auth.publicKey,
+            pool.auth.publicKey,
+            n_decimals
+        );
+        if (mint0.toBase58() > mint1.toBase58()) {
+            [mint0, mint1] = [mint1, mint0];
+        }
+
+        let [poolState] = web3.PublicKey.findProgramAddressSync(
+            [
+                Buffer.from('pool'),
+                mint0.toBuffer(),
+                mint1.toBuffer(),
+                pool.feeTier.toBuffer(),
+            ],
+            program.programId
+        );
+        let [poolAuthority] = web3.PublicKey.findProgramAddressSync(
+            [Buffer.from('authority'), poolState.toBuffer()],
+            program.programId
+        );
+        let [poolMint] = web3.PublicKey.findProgramAddressSync(
+            [Buffer.from('lp_mint'), poolState.toBuffer()],
+            program.programId
+        );
+        let vault0 = await token.getOrCreateAssociatedTokenAccount(
+            connection,
+            pool.auth,
+            mint0,
+            poolAuthority,
+            true
+        );
+        let vault1 = await token.getOrCreateAssociatedTokenAccount(
+            connection,
+            pool.auth,
+            mint1,
+            poolAuthority,
+            true
+        );
+
+        await program.methods
+            .createPool({ constantProduct: {} })
+            .accounts({
+                creator: wallet.publicKey,
+                feeTo,
+                mint0,
+                mint1,
+                feeTier: pool.feeTier,
+                vault0: vault0.address,
+                vault1: vault1.address,
+                tokenProgram0: token.TOKEN_PROGRAM_ID,
+                tokenProgram1: token.TOKEN_PROGRAM_ID,
+            })
+            .rpc();
+
+        let fund = async (user: web3.Keypair, amount: anchor.BN) => {
+            let sig = await connection.requestAirdrop(
+                user.publicKey,
+                web3.LAMPORTS_PER_SOL
+            );
+            await connection.confirmTransaction(sig, 'confirmed');
+            let ata0 = await token.createAssociatedTokenAccount(
+                connection,
+                pool.payer,
+                mint0,
+                user.publicKey
+            );
+            let ata1 = await token.createAssociatedTokenAccount(
+                connection,
+                pool.payer,
+                mint1,
+                user.publicKey
+            );
+            await token.mintTo(
+                connection,
+                pool.payer,
+                mint0,
+                ata0,
+                pool.auth,
+                BigInt(amount.toString())
+            );
+            await token.mintTo(
+                connection,
+                pool.payer,
+                mint1,
+                ata1,
+                pool.auth,
+                BigInt(amount.toString())
+            );
+            return [ata0, ata1];
+        };
+        let balance = async (ata: web3.PublicKey) =>
+            new BN((await connection.getTokenAccountBalance(ata)).value.amount);
+
+        let donation = lp_amount(10);
+        let seed = MINIMUM_LIQUIDITY.add(new BN(1));
+        let attacker = web3.Keypair.generate();
+        let victim = web3.Keypair.generate();
+        let [attackerAta0, attackerAta1] = await fund(
+            attacker,
+            donation.add(seed).addn(1)
+        );
+        let [victimAta0, victimAta1] = await fund(victim, donation);
+        let accounts = (
+            user: web3.Keypair,
+            ata0: web3.PublicKey,
+            ata1: web3.PublicKey
+        ) => ({
+            owner: user.publicKey,
+            pool: poolState,
+            vault0: vault0.address,
+            vault1: vault1.address,
+            feeTo,
+            userAta0: ata0,
+            userAta1: ata1,
+            mint0,
+            mint1,
+            tokenProgram0: token.TOKEN_PROGRAM_ID,
+            tokenProgram1: token.TOKEN_PROGRAM_ID,
+        });
+
+        // Donations cannot be synced into the empty pool, which would leave reserve1 empty and
+        // reject every deposit
+        await token.transfer(
+            connection,
+            attacker,
+            attackerAta0,
+            vault0.address,
+            attacker,
+            1
+        );
+        try {
+            await program.methods
+                .sync()
+                .accounts({
+                    pool: poolState,
+                    vault0: vault0.address,
+                    vault1: vault1.address,
+                })
+                .rpc();
+            expect.fail('should reject syncing an empty pool');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq(
+                'NoLiquidity'
+            );
+        }
+        let poolData = await program.account.pool.fetch(poolState);
+        expect(poolData.reserve0.isZero()).to.be.true;
+
+        // The attacker seeds the pool with the smallest possible deposit...
+        await program.methods
+            .addLiquidity(seed, seed, seed, seed)
+            .accounts(accounts(attacker, attackerAta0, attackerAta1))
+            .signers([attacker])
+            .rpc();
+        let attackerLp = token.getAssociatedTokenAddressSync(
+            poolMint,
+            attacker.publicKey
+        );
+        expect((await balance(attackerLp)).eq(new BN(1))).to.be.true;
+
+        // ...then donates to the vaults to inflate the value of each LP token
+        await token.transfer(
+            connection,
+            attacker,
+            attackerAta0,
+            vault0.address,
+            attacker,
+            BigInt(donation.toString())
+        );
+        await token.transfer(
+            connection,
+            attacker,
+            attackerAta1,
+            vault1.address,
+            attacker,
+            BigInt(donation.toString())
+        );
+        await program.methods
+            .sync()
+            .accounts({
+                pool: poolState,
+                vault0: vault0.address,
+                vault1: vault1.address,
+            })
+            .rpc();
+
+        // The victim still receives a fair share of the pool
+        await program.methods
+            .addLiquidity(donation, donation, new BN(0), new BN(0))
+            .accounts(accounts(victim, victimAta0, victimAta1))
+            .signers([victim])
+            .rpc();
+
+        let victimLp = token.getAssociatedTokenAddressSync(
+            poolMint,
+            victim.publicKey
+        );
+        await program.methods
+            .removeLiquidity(await balance(victimLp), new BN(0), new BN(0))
+            .accounts(accounts(victim, victimAta0, victimAta1))
+            .signers([victim])
+            .rpc();
+        await program.methods
+            .removeLiquidity(new BN(1), new BN(0), new BN(0))
+            .accounts(accounts(attacker, attackerAta0, attackerAta1))
+            .signers([attacker])
+            .rpc();
+
+        let victimFloor = donation.muln(999).divn(1000);
+        expect((await balance(victimAta0)).gte(victimFloor)).to.be.true;
+        expect((await balance(victimAta1)).gte(victimFloor)).to.be.true;
+
+        let attackerSpent = donation.add(seed);
+        expect((await balance(attackerAta0)).lt(attackerSpent)).to.be.true;
+        expect((await balance(attackerAta1)).lt(attackerSpent)).to.be.true;
+    });
+
+    it('Token-2022 transfer fee mint', async () => {
+        // A Token-2022 mint withholding 1% of every transfer, paired with a classic mint
+        let feeBps = 100;
+        let feeMint = web3.Keypair.generate();
+        let mintLen = token.getMintLen([token.ExtensionType.TransferFeeConfig]);
+        let lamports = await connection.getMinimumBalanceForRentExemption(
+            mintLen
+        );
+        await web3.sendAndConfirmTransaction(
+            connection,
+            new web3.Transaction().add(
+                web3.SystemProgram.createAccount({
+                    fromPubkey: pool.auth.publicKey,
+                    newAccountPubkey: feeMint.publicKey,
+                    space: mintLen,
+                    lamports,
+                    programId: token.TOKEN_2022_PROGRAM_ID,
+                }),
+                token.createInitializeTransferFeeConfigInstruction(
+                    feeMint.publicKey,
+                    pool.auth.publicKey,
+                    pool.auth.publicKey,
+                    feeBps,
+                    BigInt('18446744073709551615'),
+                    token.TOKEN_2022_PROGRAM_ID
+                ),
+                token.createInitializeMintInstruction(
+                    feeMint.publicKey,
+                    n_decimals,
+                    pool.auth.publicKey,
+                    null,
+                    token.TOKEN_2022_PROGRAM_ID
+                )
+            ),
+            [pool.auth, feeMint]
+        );
+        let plainMint = await token.createMint(
+            connection,
+            pool.auth,
+            pool.auth.publicKey,
+            pool.auth.publicKey,
+            n_decimals
+        );
+
+        let mints = [
+            { mint: feeMint.publicKey, program: token.TOKEN_2022_PROGRAM_ID },
+            { mint: plainMint, program: token.TOKEN_PROGRAM_ID },
+        ].sort((a, b) => (a.mint.toBase58() < b.mint.toBase58() ? -1 : 1));
+        let [mint0, mint1] = mints.map((m) => m.mint);
+        let [tokenProgram0, tokenProgram1] = mints.map((m) => m.program);
+        let feeSide = mint0.equals(feeMint.publicKey) ? 0 : 1;
+        let transferFee = (amount: anchor.BN) =>
+            amount.muln(feeBps).addn(BASIS_POINTS - 1).divn(BASIS_POINTS);
+
+        let [poolState] = web3.PublicKey.findProgramAddressSync(
+            [
+                Buffer.from('pool'),
+                mint0.toBuffer(),
+                mint1.toBuffer(),
+                pool.feeTier.toBuffer(),
+            ],
+            program.programId
+        );
+        let [poolAuthority] = web3.PublicKey.findProgramAddressSync(
+            [Buffer.from('authority'), poolState.toBuffer()],
+            program.programId
+        );
+        let vaults = [];
+        let userAtas = [];
+        let user = web3.Keypair.generate();
+        let sig = await connection.requestAirdrop(
+            user.publicKey,
+            web3.LAMPORTS_PER_SOL
+        );
+        await connection.confirmTransaction(sig, 'confirmed');
+        for (let { mint, program: programId } of mints) {
+            let vault = await token.getOrCreateAssociatedTokenAccount(
+                connection,
+                pool.auth,
+                mint,
+                poolAuthority,
+                true,
+                undefined,
+                undefined,
+                programId
+            );
+            vaults.push(vault.address);
+            let ata = await token.createAssociatedTokenAccount(
+                connection,
+                pool.auth,
+                mint,
+                user.publicKey,
+                undefined,
+                programId
+            );
+            await token.mintTo(
+                connection,
+                pool.auth,
+                mint,
+                ata,
+                pool.auth,
+                BigInt(lp_amount(100).toString()),
+                [],
+                undefined,
+                programId
+            );
+            userAtas.push(ata);
+        }
+
+        await program.methods
+            .createPool({ constantProduct: {} })
+            .accounts({
+                creator: wallet.publicKey,
+                feeTo,
+                mint0,
+                mint1,
+                feeTier: pool.feeTier,
+                vault0: vaults[0],
+                vault1: vaults[1],
+                tokenProgram0,
+                tokenProgram1,
+            })
+            .rpc();
+
+        let balance = async (account: web3.PublicKey) =>
+            new BN(
+                (await connection.getTokenAccountBalance(account)).value.amount
+            );
+
+        let deposit = lp_amount(10);
+        let addLiquidity = (amountMin: anchor.BN) =>
+            program.methods
+                .addLiquidity(deposit, deposit, amountMin, amountMin)
+                .accounts({
+                    owner: user.publicKey,
+                    pool: poolState,
+                    vault0: vaults[0],
+                    vault1: vaults[1],
+                    feeTo,
+                    userAta0: userAtas[0],
+                    userAta1: userAtas[1],
+                    mint0,
+                    mint1,
+                    tokenProgram0,
+                    tokenProgram1,
+                })
+                .signers([user])
+                .rpc();
+
+        // The minimums apply to what the vaults receive, net of the transfer fee
+        try {
+            await addLiquidity(deposit);
+            expect.fail('should check the minimums net of the transfer fee');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq(
+                'InsufficientAmount'
+            );
+        }
+
+        // Reserves are credited with what the vaults received, net of the transfer fee
+        await addLiquidity(deposit.sub(transferFee(deposit)));
+
+        let poolData = await program.account.pool.fetch(poolState);
+        let reserves = [poolData.reserve0, poolData.reserve1];
+        expect(reserves[feeSide].eq(deposit.sub(transferFee(deposit)))).to.be
+            .true;
+        expect(reserves[1 - feeSide].eq(deposit)).to.be.true;
+        expect(reserves[0].eq(await balance(vaults[0]))).to.be.true;
+        expect(reserves[1].eq(await balance(vaults[1]))).to.be.true;
+
+        // Swapping the fee mint in prices the swap on the amount the vault received
+        let amountIn = lp_amount(1);
+        let amountOut = getAmountOut(
+            amountIn.sub(transferFee(amountIn)),
+            reserves[feeSide],
+            reserves[1 - feeSide],
+            fee
+        );
+        let desBefore = await balance(userAtas[1 - feeSide]);
+        await program.methods
+            .swapExactInput(amountIn, amountOut)
+            .accounts({
+                owner: user.publicKey,
+                pool: poolState,
+                userAtaSrc: userAtas[feeSide],
+                userAtaDes: userAtas[1 - feeSide],
+                vaultSrc: vaults[feeSide],
+                vaultDes: vaults[1 - feeSide],
+                mintSrc: mints[feeSide].mint,
+                mintDes: mints[1 - feeSide].mint,
+                tokenProgramSrc: mints[feeSide].program,
+                tokenProgramDes: mints[1 - feeSide].program,
+            })
+            .signers([user])
+            .rpc();
+
+        expect(
+            (await balance(userAtas[1 - feeSide])).sub(desBefore).eq(amountOut)
+        ).to.be.true;
+        poolData = await program.account.pool.fetch(poolState);
+        expect(poolData.reserve0.eq(await balance(vaults[0]))).to.be.true;
+        expect(poolData.reserve1.eq(await balance(vaults[1]))).to.be.true;
+    });
+
+    it('Extension allow-list', async () => {
+        // A Token-2022 mint with a permanent delegate, paired with a classic mint
+        let delegateMint = web3.Keypair.generate();
+        let mintLen = token.getMintLen([token.ExtensionType.PermanentDelegate]);
+        let lamports = await connection.getMinimumBalanceForRentExemption(
+            mintLen
+        );
+        await web3.sendAndConfirmTransaction(
+            connection,
+            new web3.Transaction().add(
+                web3.SystemProgram.createAccount({
+                    fromPubkey: pool.auth.publicKey,
+                    newAccountPubkey: delegateMint.publicKey,
+                    space: mintLen,
+                    lamports,
+                    programId: token.TOKEN_2022_PROGRAM_ID,
+                }),
+                token.createInitializePermanentDelegateInstruction(
+                    delegateMint.publicKey,
+                    pool.auth.publicKey,
+                    token.TOKEN_2022_PROGRAM_ID
+                ),
+                token.createInitializeMintInstruction(
+                    delegateMint.publicKey,
+                    n_decimals,
+                    pool.auth.publicKey,
+                    null,
+                    token.TOKEN_2022_PROGRAM_ID
+                )
+            ),
+            [pool.auth, delegateMint]
+        );
+        let plainMint = await token.createMint(
+            connection,
+            pool.auth,
+            pool.auth.publicKey,
+            pool.auth.publicKey,
+            n_decimals
+        );
+
+        let mints = [
+            {
+                mint: delegateMint.publicKey,
+                program: token.TOKEN_2022_PROGRAM_ID,
+            },
+            { mint: plainMint, program: token.TOKEN_PROGRAM_ID },
+        ].sort((a, b) => (a.mint.toBase58() < b.mint.toBase58() ? -1 : 1));
+        let [poolState] = web3.PublicKey.findProgramAddressSync(
+            [
+                Buffer.from('pool'),
+                mints[0].mint.toBuffer(),
+                mints[1].mint.toBuffer(),
+                pool.feeTier.toBuffer(),
+            ],
+            program.programId
+        );
+        let [poolAuthority] = web3.PublicKey.findProgramAddressSync(
+            [Buffer.from('authority'), poolState.toBuffer()],
+            program.programId
+        );
+        let vaults = [];
+        for (let { mint, program: programId } of mints) {
+            let vault = await token.getOrCreateAssociatedTokenAccount(
+                connection,
+                pool.auth,
+                mint,
+                poolAuthority,
+                true,
+                undefined,
+                undefined,
+                programId
+            );
+            vaults.push(vault.address);
+        }
+        let createPool = () =>
+            program.methods
+                .createPool({ constantProduct: {} })
+                .accounts({
+                    creator: wallet.publicKey,
+                    feeTo,
+                    mint0: mints[0].mint,
+                    mint1: mints[1].mint,
+                    feeTier: pool.feeTier,
+                    vault0: vaults[0],
+                    vault1: vaults[1],
+                    tokenProgram0: mints[0].program,
+                    tokenProgram1: mints[1].program,
+                })
+                .rpc();
+        let expectRejected = async (code: string) => {
+            try {
+                await createPool();
+                expect.fail('should reject the mint');
+            } catch (e) {
+                expect(e).to.be.instanceOf(AnchorError);
+                expect((e as AnchorError).error.errorCode.code).to.eq(code);
+            }
+        };
+
+        let config = await program.account.config.fetch(configPDA);
+        let defaultExtensions = config.allowedExtensions;
+        expect(config.freezeAuthorityAllowed).to.be.true;
+
+        // Permanent delegates are rejected by default
+        await expectRejected('PermanentDelegateNotAllowed');
+
+        // Freeze authorities can be rejected too, the classic mint has one
+        let allowed = defaultExtensions.or(
+            new BN(1).shln(token.ExtensionType.PermanentDelegate)
+        );
+        await program.methods
+            .setExtensionAllowList(allowed, false)
+            .accounts({ config: configPDA })
+            .rpc();
+        await expectRejected('FreezeAuthorityNotAllowed');
+
+        // Once both are allowed the pool can be created
+        await program.methods
+            .setExtensionAllowList(allowed, true)
+            .accounts({ config: configPDA })
+            .rpc();
+        await createPool();
+        let poolData = await program.account.pool.fetch(poolState);
+        expect(poolData.token0.toBase58()).to.eq(mints[0].mint.toBase58());
+
+        await program.methods
+            .setExtensionAllowList(defaultExtensions, true)
+            .accounts({ config: configPDA })
+            .rpc();
+        config = await program.account.config.fetch(configPDA);
+        expect(config.allowedExtensions.eq(defaultExtensions)).to.be.true;
+    });
+
+    it('Transfer hook mint', async () => {
+        // A Token-2022 mint calling a transfer hook program, paired with a classic mint
+        let hookProgram = web3.Keypair.generate().publicKey;
+        let hookMint = web3.Keypair.generate();
+        let mintLen = token.getMintLen([token.ExtensionType.TransferHook]);
+        let lamports = await connection.getMinimumBalanceForRentExemption(
+            mintLen
+        );
+        await web3.sendAndConfirmTransaction(
+            connection,
+            new web3.Transaction().add(
+                web3.SystemProgram.createAccount({
+                    fromPubkey: pool.auth.publicKey,
+                    newAccountPubkey: hookMint.publicKey,
+                    space: mintLen,
+                    lamports,
+                    programId: token.TOKEN_2022_PROGRAM_ID,
+                }),
+                token.createInitializeTransferHookInstruction(
+                    hookMint.publicKey,
+                    pool.auth.publicKey,
+                    hookProgram,
+                    token.TOKEN_2022_PROGRAM_ID
+                ),
+                token.createInitializeMintInstruction(
+                    hookMint.publicKey,
+                    n_decimals,
+                    pool.auth.publicKey,
+                    null,
+                    token.TOKEN_2022_PROGRAM_ID
+                )
+            ),
+            [pool.auth, hookMint]
+        );
+        let plainMint = await token.createMint(
+            connection,
+            pool.auth,
+            pool.auth.publicKey,
+            null,
+            n_decimals
+        );
+
+        let mints = [
+            { mint: hookMint.publicKey, program: token.TOKEN_2022_PROGRAM_ID },
+            { mint: plainMint, program: token.TOKEN_PROGRAM_ID },
+        ].sort((a, b) => (a.mint.toBase58() < b.mint.toBase58() ? -1 : 1));
+        let [poolState] = web3.PublicKey.findProgramAddressSync(
+            [
+                Buffer.from('pool'),
+                mints[0].mint.toBuffer(),
+                mints[1].mint.toBuffer(),
+                pool.feeTier.toBuffer(),
+            ],
+            program.programId
+        );
+        let [poolAuthority] = web3.PublicKey.findProgramAddressSync(
+            [Buffer.from('authority'), poolState.toBuffer()],
+            program.programId
+        );
+        let vaults = [];
+        for (let { mint, program: programId } of mints) {
+            let vault = await token.getOrCreateAssociatedTokenAccount(
+                connection,
+                pool.auth,
+                mint,
+                poolAuthority,
+                true,
+                undefined,
+                undefined,
+                programId
+            );
+            vaults.push(vault.address);
+        }
+
+        // Transfers cannot pass the hook's accounts, so every hook program is rejected
+        try {
+            await program.methods
+                .createPool({ constantProduct: {} })
+                .accounts({
+                    creator: wallet.publicKey,
+                    feeTo,
+                    mint0: mints[0].mint,
+                    mint1: mints[1].mint,
+                    feeTier: pool.feeTier,
+                    vault0: vaults[0],
+                    vault1: vaults[1],
+                    tokenProgram0: mints[0].program,
+                    tokenProgram1: mints[1].program,
+                })
+                .rpc();
+            expect.fail('should reject the transfer hook mint');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq(
+                'TransferHookNotAllowed'
+            );
+        }
+
+    });
+
+    it('Native SOL', async () => {
+        // A SOL pool, the native mint has no freeze authority
+        let tokenMint = await token.createMint(
+            connection,
+            pool.auth,
+            pool.auth.publicKey,
+            null,
+            n_decimals
+        );
+        let [mint0, mint1] = [token.NATIVE_MINT, tokenMint].sort((a, b) =>
+            a.toBase58() < b.toBase58() ? -1 : 1
+        );
+        let solSide = mint0.equals(token.NATIVE_MINT) ? 0 : 1;
+
+        let [poolState] = web3.PublicKey.findProgramAddressSync(
+            [
+                Buffer.from('pool'),
+                mint0.toBuffer(),
+                mint1.toBuffer(),
+                pool.feeTier.toBuffer(),
+            ],
+            program.programId
+        );
+        let [poolAuthority] = web3.PublicKey.findProgramAddressSync(
+            [Buffer.from('authority'), poolState.toBuffer()],
+            program.programId
+        );
+        let vaults = [];
+        for (let mint of [mint0, mint1]) {
+            let vault = await token.getOrCreateAssociatedTokenAccount(
+                connection,
+                pool.auth,
+                mint,
+                poolAuthority,
+                true
+            );
+            vaults.push(vault.address);
+        }
+        let poolAccounts = {
+            pool: poolState,
+            vault0: vaults[0],
+            vault1: vaults[1],
+            mint0,
+            mint1,
+            tokenProgram0: token.TOKEN_PROGRAM_ID,
+            tokenProgram1: token.TOKEN_PROGRAM_ID,
+        };
+        await program.methods
+            .createPool({ constantProduct: {} })
+            .accounts({
+                creator: wallet.publicKey,
+                feeTo,
+                feeTier: pool.feeTier,
+                ...poolAccounts,
+            })
+            .rpc();
+
+        // The user only holds lamports and the other token
+        let user = web3.Keypair.generate();
+        let sig = await connection.requestAirdrop(
+            user.publicKey,
+            100 * web3.LAMPORTS_PER_SOL
+        );
+        await connection.confirmTransaction(sig, 'confirmed');
+        let userAta = await token.createAssociatedTokenAccount(
+            connection,
+            pool.auth,
+            tokenMint,
+            user.publicKey
+        );
+        await token.mintTo(
+            connection,
+            pool.auth,
+            tokenMint,
+            userAta,
+            pool.auth,
+            BigInt(lp_amount(100).toString())
+        );
+        let [userWsol] = web3.PublicKey.findProgramAddressSync(
+            [Buffer.from('wsol'), user.publicKey.toBuffer()],
+            program.programId
+        );
+        let liquidityAccounts = {
+            owner: user.publicKey,
+            userAta,
+            feeTo,
+            ...poolAccounts,
+        };
+        let lamports = async () =>
+            new BN(await connection.getBalance(user.publicKey));
+        let tokens = async () =>
+            new BN(
+                (await connection.getTokenAccountBalance(userAta)).value.amount
+            );
+
+        // Deposits wrap SOL, the temporary wSOL account is closed afterwards
+        let deposit = lp_amount(10);
+        await program.methods
+            .addLiquiditySol(deposit, deposit, deposit, deposit)
+            .accounts(liquidityAccounts)
+            .signers([user])
+            .rpc();
+        let poolData = await program.account.pool.fetch(poolState);
+        expect(poolData.reserve0.eq(deposit)).to.be.true;
+        expect(poolData.reserve1.eq(deposit)).to.be.true;
+        expect(await connection.getAccountInfo(userWsol)).to.be.null;
+
+        let swapAccounts = (solIn: boolean) => {
+            let [src, des] = solIn
+                ? [solSide, 1 - solSide]
+                : [1 - solSide, solSide];
+            return {
+                owner: user.publicKey,
+                userAta,
+                pool: poolState,
+                vaultSrc: vaults[src],
+                vaultDes: vaults[des],
+                mintSrc: [mint0, mint1][src],
+                mintDes: [mint0, mint1][des],
+                tokenProgramSrc: token.TOKEN_PROGRAM_ID,
+                tokenProgramDes: token.TOKEN_PROGRAM_ID,
+            };
+        };
+
+        // SOL in, tokens out
+        let amountIn = lp_amount(1);
+        let amountOut = getAmountOut(amountIn, deposit, deposit, fee);
+        let tokensBefore = await tokens();
+        await program.methods
+            .swapExactInputSol(amountIn, amountOut)
+            .accounts(swapAccounts(true))
+            .signers([user])
+            .rpc();
+        expect((await tokens()).sub(tokensBefore).eq(amountOut)).to.be.true;
+
+        // Tokens in, SOL out, unwrapped straight to the user's lamports
+        poolData = await program.account.pool.fetch(poolState);
+        let reserves = [poolData.reserve0, poolData.reserve1];
+        amountOut = lp_amount(1).divn(2);
+        amountIn = getAmountIn(
+            amountOut,
+            reserves[1 - solSide],
+            reserves[solSide],
+            fee
+        );
+        let lamportsBefore = await lamports();
+        await program.methods
+            .swapExactOutputSol(amountOut, amountIn)
+            .accounts(swapAccounts(false))
+            .signers([user])
+            .rpc();
+        expect((await lamports()).sub(lamportsBefore).eq(amountOut)).to.be
+            .true;
+        expect(await connection.getAccountInfo(userWsol)).to.be.null;
+
+        // Withdrawals unwrap the SOL side
+        let [poolMint] = web3.PublicKey.findProgramAddressSync(
+            [Buffer.from('lp_mint'), poolState.toBuffer()],
+            program.programId
+        );
+        let lpAta = token.getAssociatedTokenAddressSync(
+            poolMint,
+            user.publicKey
+        );
+        let liquidity = new BN(
+            (await connection.getTokenAccountBalance(lpAta)).value.amount
+        );
+        lamportsBefore = await lamports();
+        await program.methods
+            .removeLiquiditySol(liquidity, new BN(0), new BN(0))
+            .accounts(liquidityAccounts)
+            .signers([user])
+            .rpc();
+        expect((await lamports()).gt(lamportsBefore)).to.be.true;
+        expect(await connection.getAccountInfo(userWsol)).to.be.null;
+    });
+
+    it('Multi-hop route', async () => {
+        // A second pool pairing the main pool's mint1 with a new mint
+        let mint2 = await token.createMint(
+            connection,
+            pool.auth,
+            pool.auth.publicKey,
+            pool.auth.publicKey,
+            n_decimals
+        );
+        let [mintA, mintB] = [pool.mint1, mint2].sort((a, b) =>
+            a.toBase58() < b.toBase58() ? -1 : 1
+        );
+        let [poolState] = web3.PublicKey.findProgramAddressSync(
+            [
+                Buffer.from('pool'),
+                mintA.toBuffer(),
+                mintB.toBuffer(),
+                pool.feeTier.toBuffer(),
+            ],
+            program.programId
+        );
+        let [poolAuthority] = web3.PublicKey.findProgramAddressSync(
+            [Buffer.from('authority'), poolState.toBuffer()],
+            program.programId
+        );
+        let vaults = [];
+        for (let mint of [mintA, mintB]) {
+            let vault = await token.getOrCreateAssociatedTokenAccount(
+                connection,
+                pool.auth,
+                mint,
+                poolAuthority,
+                true
+            );
+            vaults.push(vault.address);
+        }
+        await program.methods
+            .createPool({ constantProduct: {} })
+            .accounts({
+                creator: wallet.publicKey,
+                feeTo,
+                mint0: mintA,
+                mint1: mintB,
+                feeTier: pool.feeTier,
+                vault0: vaults[0],
+                vault1: vaults[1],
+                tokenProgram0: token.TOKEN_PROGRAM_ID,
+                tokenProgram1: token.TOKEN_PROGRAM_ID,
+            })
+            .rpc();
+
+        let user = web3.Keypair.generate();
+        let sig = await connection.requestAirdrop(
+            user.publicKey,
+            web3.LAMPORTS_PER_SOL
+        );
+        await connection.confirmTransaction(sig, 'confirmed');
+        let [userAta0, userAta1] = await setup_lp_provider(user.publicKey, 100);
+        let userAta2 = await token.createAssociatedTokenAccount(
+            connection,
+            pool.payer,
+            mint2,
+            user.publicKey
+        );
+        await token.mintTo(
+            connection,
+            pool.payer,
+            mint2,
+            userAta2,
+            pool.auth,
+            100 * 10 ** n_decimals
+        );
+
+        let [userAtaA, userAtaB] = mintA.equals(pool.mint1)
+            ? [userAta1, userAta2]
+            : [userAta2, userAta1];
+        await program.methods
+            .addLiquidity(lp_amount(10), lp_amount(20), new BN(0), new BN(0))
+            .accounts({
+                owner: user.publicKey,
+                pool: poolState,
+                vault0: vaults[0],
+                vault1: vaults[1],
+                feeTo,
+                userAta0: userAtaA,
+                userAta1: userAtaB,
+                mint0: mintA,
+                mint1: mintB,
+                tokenProgram0: token.TOKEN_PROGRAM_ID,
+                tokenProgram1: token.TOKEN_PROGRAM_ID,
+            })
+            .signers([user])
+            .rpc();
+
+        // Route mint0 -> mint1 through the main pool, then mint1 -> mint2
+        let hop = (
+            poolState: web3.PublicKey,
+            vaultSrc: web3.PublicKey,
+            vaultDes: web3.PublicKey,
+            mintDes: web3.PublicKey
+        ) => [
+            { pubkey: poolState, isWritable: true, isSigner: false },
+            {
+                pubkey: web3.PublicKey.findProgramAddressSync(
+                    [Buffer.from('authority'), poolState.toBuffer()],
+                    program.programId
+                )[0],
+                isWritable: false,
+                isSigner: false,
+            },
+            {
+                pubkey: web3.PublicKey.findProgramAddressSync(
+                    [Buffer.from('observations'), poolState.toBuffer()],
+                    program.programId
+                )[0],
+                isWritable: true,
+                isSigner: false,
+            },
+            { pubkey: vaultSrc, isWritable: true, isSigner: false },
+            { pubkey: vaultDes, isWritable: true, isSigner: false },
+            { pubkey: mintDes, isWritable: false, isSigner: false },
+            {
+                pubkey: token.TOKEN_PROGRAM_ID,
+                isWritable: false,
+                isSigner: false,
+            },
+        ];
+        let [vault1B, vault2B] = mintA.equals(pool.mint1)
+            ? vaults
+            : [vaults[1], vaults[0]];
+        let path = [
+            ...hop(pool.poolState, pool.vault0, pool.vault1, pool.mint1),
+            ...hop(poolState, vault1B, vault2B, mint2),
+        ];
+        let accounts = {
+            owner: user.publicKey,
+            userAtaSrc: userAta0,
+            userAtaDes: userAta2,
+            mintSrc: pool.mint0,
+            tokenProgramSrc: token.TOKEN_PROGRAM_ID,
+        };
+        let balance = async (account: web3.PublicKey) =>
+            new BN(
+                (await connection.getTokenAccountBalance(account)).value.amount
+            );
+        let hops = async () => {
+            let first = await program.account.pool.fetch(pool.poolState);
+            let second = await program.account.pool.fetch(poolState);
+            let flip = !mintA.equals(pool.mint1);
+            return [
+                {
+                    reserveIn: first.reserve0,
+                    reserveOut: first.reserve1,
+                    fee: first.fee.toNumber(),
+                },
+                {
+                    reserveIn: flip ? second.reserve1 : second.reserve0,
+                    reserveOut: flip ? second.reserve0 : second.reserve1,
+                    fee: second.fee.toNumber(),
+                },
+            ];
+        };
+
+        // Exact input chains get_amount_out across the hops
+        let amountIn = lp_amount(1);
+        let amountOut = (await hops()).reduce(
+            (amount, h) =>
+                getAmountOut(amount, h.reserveIn, h.reserveOut, h.fee),
+            amountIn
+        );
+        try {
+            await program.methods
+                .swapRouteExactInput(amountIn, amountOut.addn(1))
+                .accounts(accounts)
+                .remainingAccounts(path)
+                .signers([user])
+                .rpc();
+            expect.fail('should enforce the end-to-end minimum output');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq(
+                'InsufficientOutputAmount'
+            );
+        }
+
+        let srcBefore = await balance(userAta0);
+        let desBefore = await balance(userAta2);
+        let midBefore = await balance(userAta1);
+        await program.methods
+            .swapRouteExactInput(amountIn, amountOut)
+            .accounts(accounts)
+            .remainingAccounts(path)
+            .signers([user])
+            .rpc();
+        expect(srcBefore.sub(await balance(userAta0)).eq(amountIn)).to.be.true;
+        expect((await balance(userAta2)).sub(desBefore).eq(amountOut)).to.be
+            .true;
+        expect((await balance(userAta1)).eq(midBefore)).to.be.true;
+
+        // Exact output chains get_amount_in back from the last hop
+        let amountOutExact = lp_amount(1);
+        let amountInExact = (await hops()).reduceRight(
+            (amount, h) =>
+                getAmountIn(amount, h.reserveIn, h.reserveOut, h.fee),
+            amountOutExact
+        );
+        srcBefore = await balance(userAta0);
+        desBefore = await balance(userAta2);
+        await program.methods
+            .swapRouteExactOutput(amountOutExact, amountInExact)
+            .accounts(accounts)
+            .remainingAccounts(path)
+            .signers([user])
+            .rpc();
+        expect(srcBefore.sub(await balance(userAta0)).eq(amountInExact)).to.be
+            .true;
+        expect((await balance(userAta2)).sub(desBefore).eq(amountOutExact)).to
+            .be.true;
+
+        // Every hop's pool must not be paused
+        await program.methods
+            .setPoolPaused(true)
+            .accounts({ pool: poolState })
+            .rpc();
+        try {
+            await program.methods
+                .swapRouteExactInput(amountIn, new BN(0))
+                .accounts(accounts)
+                .remainingAccounts(path)
+                .signers([user])
+                .rpc();
+            expect.fail('should reject a route through a paused pool');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq('Paused');
+        }
+        await program.methods
+            .setPoolPaused(false)
+            .accounts({ pool: poolState })
+            .rpc();
+
+        // Accounts after the last hop must be oracles of the route's pools
+        try {
+            await program.methods
+                .swapRouteExactInput(amountIn, new BN(0))
+                .accounts(accounts)
+                .remainingAccounts(path.concat(path.slice(0, 1)))
+                .signers([user])
+                .rpc();
+            expect.fail('should reject a trailing account that is no oracle');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq(
+                'InvalidRoute'
+            );
+        }
+
+        // Hops must chain from the source mint
+        try {
+            await program.methods
+                .swapRouteExactInput(amountIn, new BN(0))
+                .accounts(accounts)
+                .remainingAccounts(path.slice(7).concat(path.slice(0, 7)))
+                .signers([user])
+                .rpc();
+            expect.fail('should reject a route that does not chain');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq(
+                'InvalidRoute'
+            );
+        }
+    });
+
+    it('Flash swap', async () => {
+        let accounts = {
+            owner: lpUser0.signer.publicKey,
+            pool: pool.poolState,
+            vault0: pool.vault0,
+            vault1: pool.vault1,
+            recipient0: lpUser0.userAta0,
+            recipient1: lpUser0.userAta1,
+            mint0: pool.mint0,
+            mint1: pool.mint1,
+            tokenProgram0: token.TOKEN_PROGRAM_ID,
+            tokenProgram1: token.TOKEN_PROGRAM_ID,
+        };
+
+        // The pool can not be its own borrower
+        try {
+            await program.methods
+                .flashSwap(lp_amount(1), new BN(0), Buffer.from([]))
+                .accounts({ ...accounts, callbackProgram: program.programId })
+                .signers([lpUser0.signer])
+                .rpc();
+            expect.fail('should reject the pool as callback program');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq(
+                'InvalidCallbackProgram'
+            );
+        }
+
+        // A callback that does not pay the pool back reverts the optimistic transfer
+        let vault0Before = await connection.getTokenAccountBalance(
+            pool.vault0
+        );
+        try {
+            await program.methods
+                .flashSwap(lp_amount(1), new BN(0), Buffer.from([]))
+                .accounts({
+                    ...accounts,
+                    callbackProgram: web3.SystemProgram.programId,
+                })
+                .signers([lpUser0.signer])
+                .rpc();
+            expect.fail('should fail when the callback fails');
+        } catch (e) {
+            expect(e).to.be.instanceOf(web3.SendTransactionError);
+        }
+        let vault0After = await connection.getTokenAccountBalance(pool.vault0);
+        expect(vault0After.value.amount).to.eq(vault0Before.value.amount);
+
+        // The test borrower pays the vaults back from the owner's accounts
+        let flashSwap = (amount0Out: anchor.BN, repay0: anchor.BN) =>
+            program.methods
+                .flashSwap(amount0Out, new BN(0), repayment(repay0, new BN(0)))
+                .accounts({ ...accounts, callbackProgram: borrower.programId })
+                .remainingAccounts(callbackAccounts(lpUser0))
+                .signers([lpUser0.signer])
+                .rpc();
+        let balance = async (account: web3.PublicKey) =>
+            new BN(
+                (await connection.getTokenAccountBalance(account)).value.amount
+            );
+        let before = await program.account.pool.fetch(pool.poolState);
+        expect(before.reserve0.eq(await balance(pool.vault0))).to.be.true;
+        expect(before.reserve1.eq(await balance(pool.vault1))).to.be.true;
+
+        // Repaying the output grossed up for the fee keeps k, a unit less breaks it
+        let amount0Out = lp_amount(1);
+        let poolFee = before.fee.toNumber();
+        let repay0 = amount0Out
+            .muln(BASIS_POINTS)
+            .addn(BASIS_POINTS - poolFee - 1)
+            .divn(BASIS_POINTS - poolFee);
+        try {
+            await flashSwap(amount0Out, repay0.subn(1));
+            expect.fail('should reject a repayment short of the fee');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq(
+                'InvariantViolated'
+            );
+        }
+
+        await flashSwap(amount0Out, repay0);
+        let after = await program.account.pool.fetch(pool.poolState);
+        expect(
+            after.reserve0.eq(before.reserve0.sub(amount0Out).add(repay0))
+        ).to.be.true;
+        expect(after.reserve1.eq(before.reserve1)).to.be.true;
+        expect(after.reserve0.eq(await balance(pool.vault0))).to.be.true;
+        expect(
+            after.reserve0
+                .mul(after.reserve1)
+                .gte(before.reserve0.mul(before.reserve1))
+        ).to.be.true;
+        expect(after.unlocked).to.be.true;
+    });
+
+    it('Reentrancy', async () => {
+        // A flash swap callback swapping against the pool it borrows from
+        let swap = await program.methods
+            .swapExactInput(lp_amount(1), new BN(0))
+            .accounts({
+                owner: lpUser0.signer.publicKey,
+                pool: pool.poolState,
+                userAtaSrc: lpUser0.userAta0,
+                userAtaDes: lpUser0.userAta1,
+                vaultSrc: pool.vault0,
+                vaultDes: pool.vault1,
+                mintSrc: pool.mint0,
+                mintDes: pool.mint1,
+                tokenProgramSrc: token.TOKEN_PROGRAM_ID,
+                tokenProgramDes: token.TOKEN_PROGRAM_ID,
+            })
+            .instruction();
+        let before = await program.account.pool.fetch(pool.poolState);
+
+        // The runtime refuses to call the AMM back through the borrower, before
+        // the pool's own lock is reached, so neither the swap nor the loan lands
+        try {
+            await program.methods
+                .flashSwap(
+                    lp_amount(1),
+                    new BN(0),
+                    repayment(lp_amount(2), new BN(0), swap.data)
+                )
+                .accounts({
+                    owner: lpUser0.signer.publicKey,
+                    pool: pool.poolState,
+                    vault0: pool.vault0,
+                    vault1: pool.vault1,
+                    recipient0: lpUser0.userAta0,
+                    recipient1: lpUser0.userAta1,
+                    mint0: pool.mint0,
+                    mint1: pool.mint1,
+                    callbackProgram: borrower.programId,
+                    tokenProgram0: token.TOKEN_PROGRAM_ID,
+                    tokenProgram1: token.TOKEN_PROGRAM_ID,
+                })
+                .remainingAccounts([
+                    ...callbackAccounts(lpUser0),
+                    {
+                        pubkey: program.programId,
+                        isWritable: false,
+                        isSigner: false,
+                    },
+                    ...swap.keys,
+                ])
+                .signers([lpUser0.signer])
+                .rpc();
+            expect.fail('should reject the nested swap');
+        } catch (e) {
+            expect(e).to.be.instanceOf(web3.SendTransactionError);
+            expect((e as web3.SendTransactionError).message).to.contain(
+                'reentrancy not allowed'
+            );
+        }
+
+        let after = await program.account.pool.fetch(pool.poolState);
+        expect(after.reserve0.eq(before.reserve0)).to.be.true;
+        expect(after.reserve1.eq(before.reserve1)).to.be.true;
+        expect(after.unlocked).to.be.true;
+    });
+
+    it('Flash loan', async () => {
+        let flashLoanFee = 9;
+        await program.methods
+            .setFlashLoanFee(new BN(flashLoanFee))
+            .accounts({ config: configPDA })
+            .rpc();
+
+        let amount = lp_amount(1);
+        let loanFee = amount
+            .muln(flashLoanFee)
+            .addn(BASIS_POINTS - 1)
+            .divn(BASIS_POINTS);
+        let begin = () =>
+            program.methods
+                .flashLoanBegin(amount)
+                .accounts({
+                    owner: lpUser0.signer.publicKey,
+                    pool: pool.poolState,
+                    vault: pool.vault0,
+                    mint: pool.mint0,
+                    recipient: lpUser0.userAta0,
+                    tokenProgram: token.TOKEN_PROGRAM_ID,
+                })
+                .instruction();
+        let end = () =>
+            program.methods
+                .flashLoanEnd()
+                .accounts({
+                    pool: pool.poolState,
+                    vault0: pool.vault0,
+                    vault1: pool.vault1,
+                })
+                .instruction();
+        let repay = (repayment: anchor.BN) =>
+            token.createTransferInstruction(
+                lpUser0.userAta0,
+                pool.vault0,
+                lpUser0.signer.publicKey,
+                BigInt(repayment.toString())
+            );
+        let send = (...instructions: web3.TransactionInstruction[]) =>
+            provider.sendAndConfirm(
+                new web3.Transaction().add(...instructions),
+                [lpUser0.signer]
+            );
+        let expectRejected = async (
+            instructions: web3.TransactionInstruction[],
+            code: string
+        ) => {
+            try {
+                await send(...instructions);
+                expect.fail('should reject the flash loan');
+            } catch (e) {
+                expect(e.logs.join('\n')).to.contain(`Error Code: ${code}.`);
+            }
+        };
+
+        // The loan must be closed later in the same transaction, and repaid with the fee
+        await expectRejected([await begin()], 'FlashLoanEndMissing');
+        await expectRejected(
+            [await begin(), repay(amount), await end()],
+            'FlashLoanNotRepaid'
+        );
+
+        let before = await program.account.pool.fetch(pool.poolState);
+        await send(await begin(), repay(amount.add(loanFee)), await end());
+        let after = await program.account.pool.fetch(pool.poolState);
+
+        // The fee accrues to the reserves, and the pool is usable again
+        expect(after.reserve0.sub(before.reserve0).eq(loanFee)).to.be.true;
+        expect(after.reserve1.eq(before.reserve1)).to.be.true;
+        expect(after.flashLoanVault.equals(web3.PublicKey.default)).to.be.true;
+
+        // Only the fee is credited, anything repaid above it is left for skim
+        let excess0 = async () => {
+            let pool0 = await program.account.pool.fetch(pool.poolState);
+            let vault0 = await connection.getTokenAccountBalance(pool.vault0);
+            return new BN(vault0.value.amount).sub(pool0.reserve0);
+        };
+        let surplus = new BN(5);
+        let excessBefore = await excess0();
+        before = await program.account.pool.fetch(pool.poolState);
+        await send(
+            await begin(),
+            repay(amount.add(loanFee).add(surplus)),
+            await end()
+        );
+        after = await program.account.pool.fetch(pool.poolState);
+        expect(after.reserve0.sub(before.reserve0).eq(loanFee)).to.be.true;
+        expect((await excess0()).sub(excessBefore).eq(surplus)).to.be.true;
+        await program.methods
+            .skim()
+            .accounts({
+                pool: pool.poolState,
+                vault0: pool.vault0,
+                vault1: pool.vault1,
+                to0: lpUser0.userAta0,
+                to1: lpUser0.userAta1,
+                mint0: pool.mint0,
+                mint1: pool.mint1,
+                tokenProgram0: token.TOKEN_PROGRAM_ID,
+                tokenProgram1: token.TOKEN_PROGRAM_ID,
+            })
+            .rpc();
+        expect((await excess0()).isZero()).to.be.true;
+
+        await program.methods
+            .setFlashLoanFee(new BN(0))
+            .accounts({ config: configPDA })
+            .rpc();
+    });
+
+    it('StableSwap pool', async () => {
+        // A StableSwap pool for the main pool's mints, in a low fee tier
+        let stableFee = 4;
+        await program.methods
+            .createFeeTier(new BN(stableFee))
+            .accounts({ config: configPDA })
+            .rpc();
+        let feeTier = feeTierPDA(stableFee);
+        let [poolState] = web3.PublicKey.findProgramAddressSync(
+            [
+                Buffer.from('pool'),
+                pool.mint0.toBuffer(),
+                pool.mint1.toBuffer(),
+                feeTier.toBuffer(),
+            ],
+            program.programId
+        );
+        let [poolAuthority] = web3.PublicKey.findProgramAddressSync(
+            [Buffer.from('authority'), poolState.toBuffer()],
+            program.programId
+        );
+        let vaults = [];
+        for (let mint of [pool.mint0, pool.mint1]) {
+            let vault = await token.getOrCreateAssociatedTokenAccount(
+                connection,
+                pool.auth,
+                mint,
+                poolAuthority,
+                true
+            );
+            vaults.push(vault.address);
+        }
+        let createPool = (amp: number) =>
+            program.methods
+                .createPool({ stableSwap: { amp: new BN(amp) } })
+                .accounts({
+                    creator: wallet.publicKey,
+                    feeTo,
+                    mint0: pool.mint0,
+                    mint1: pool.mint1,
+                    feeTier,
+                    vault0: vaults[0],
+                    vault1: vaults[1],
+                    tokenProgram0: token.TOKEN_PROGRAM_ID,
+                    tokenProgram1: token.TOKEN_PROGRAM_ID,
+                })
+                .rpc();
+
+        try {
+            await createPool(0);
+            expect.fail('should reject a zero amplification coefficient');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq(
+                'InvalidAmp'
+            );
+        }
+
+        let amp = 100;
+        await createPool(amp);
+        let poolData = await program.account.pool.fetch(poolState);
+        expect(poolData.curveType).to.deep.eq({ stableSwap: {} });
+        expect(poolData.ampTarget.eq(new BN(amp))).to.be.true;
+
+        await program.methods
+            .addLiquidity(lp_amount(10), lp_amount(10), new BN(0), new BN(0))
+            .accounts({
+                owner: lpUser0.signer.publicKey,
+                pool: poolState,
+                vault0: vaults[0],
+                vault1: vaults[1],
+                feeTo,
+                userAta0: lpUser0.userAta0,
+                userAta1: lpUser0.userAta1,
+                mint0: pool.mint0,
+                mint1: pool.mint1,
+                tokenProgram0: token.TOKEN_PROGRAM_ID,
+                tokenProgram1: token.TOKEN_PROGRAM_ID,
+            })
+            .signers([lpUser0.signer])
+            .rpc();
+
+        // The protocol fee is measured on D, the sum of balanced reserves
+        poolData = await program.account.pool.fetch(poolState);
+        expect(poolData.kLast.eq(lp_amount(20))).to.be.true;
+
+        // Near balance the curve prices close to 1:1, well above x * y = k
+        let amountIn = lp_amount(1);
+        let constantProductOut = getAmountOut(
+            amountIn,
+            lp_amount(10),
+            lp_amount(10),
+            stableFee
+        );
+        let balanceBefore = await connection.getTokenAccountBalance(
+            lpUser0.userAta1
+        );
+        await program.methods
+            .swapExactInput(amountIn, constantProductOut)
+            .accounts({
+                owner: lpUser0.signer.publicKey,
+                pool: poolState,
+                userAtaSrc: lpUser0.userAta0,
+                userAtaDes: lpUser0.userAta1,
+                vaultSrc: vaults[0],
+                vaultDes: vaults[1],
+                mintSrc: pool.mint0,
+                mintDes: pool.mint1,
+                tokenProgramSrc: token.TOKEN_PROGRAM_ID,
+                tokenProgramDes: token.TOKEN_PROGRAM_ID,
+            })
+            .signers([lpUser0.signer])
+            .rpc();
+        let balanceAfter = await connection.getTokenAccountBalance(
+            lpUser0.userAta1
+        );
+        let amountOut = new BN(balanceAfter.value.amount).sub(
+            new BN(balanceBefore.value.amount)
+        );
+        expect(amountOut.gt(constantProductOut)).to.be.true;
+        expect(amountOut.gt(amountIn.muln(99).divn(100))).to.be.true;
+
+        // Ramps must last a day or more and move the coefficient at most tenfold
+        let now = await connection.getBlockTime(await connection.getSlot());
+        let rampAmp = (
+            ampTarget: number,
+            ampRampEnd: number,
+            target = poolState
+        ) =>
+            program.methods
+                .rampAmp(new BN(ampTarget), new BN(ampRampEnd))
+                .accounts({ config: configPDA, pool: target })
+                .rpc();
+        for (let [ampTarget, ampRampEnd] of [
+            [1000, now + 3600],
+            [amp * 11, now + 2 * 86400],
+        ]) {
+            try {
+                await rampAmp(ampTarget, ampRampEnd);
+                expect.fail('should reject the ramp');
+            } catch (e) {
+                expect(e).to.be.instanceOf(AnchorError);
+                expect((e as AnchorError).error.errorCode.code).to.eq(
+                    'InvalidAmpRamp'
+                );
+            }
+        }
+        try {
+            await rampAmp(1000, now + 2 * 86400, pool.poolState);
+            expect.fail('should reject ramping a constant product pool');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq(
+                'InvalidCurve'
+            );
+        }
+
+        await rampAmp(1000, now + 2 * 86400);
+        poolData = await program.account.pool.fetch(poolState);
+        expect(poolData.ampInitial.eq(new BN(amp))).to.be.true;
+        expect(poolData.ampTarget.eq(new BN(1000))).to.be.true;
+        expect(poolData.ampRampEnd.eq(new BN(now + 2 * 86400))).to.be.true;
+
+        // Stopping holds the coefficient reached so far
+        await program.methods
+            .stopRampAmp()
+            .accounts({ config: configPDA, pool: poolState })
+            .rpc();
+        poolData = await program.account.pool.fetch(poolState);
+        expect(poolData.ampTarget.eq(poolData.ampInitial)).to.be.true;
+        expect(poolData.ampTarget.lt(new BN(1000))).to.be.true;
+        expect(poolData.ampRampEnd.eq(poolData.ampRampStart)).to.be.true;
+    });
+
+    it('StableSwap pool across decimals', async () => {
+        // Pegged tokens with 6 and 9 decimals, in the StableSwap fee tier
+        let mints = [];
+        for (let decimals of [6, 9]) {
+            let mint = await token.createMint(
+                connection,
+                pool.auth,
+                pool.auth.publicKey,
+                pool.auth.publicKey,
+                decimals
+            );
+            let ata = await token.createAssociatedTokenAccount(
+                connection,
+                pool.payer,
+                mint,
+                wallet.publicKey
+            );
+            await token.mintTo(
+                connection,
+                pool.payer,
+                mint,
+                ata,
+                pool.auth,
+                100 * 10 ** decimals
+            );
+            mints.push({ mint, ata, unit: new BN(10 ** decimals) });
+        }
+        if (mints[0].mint.toBase58() > mints[1].mint.toBase58()) {
+            mints.reverse();
+        }
+        let [token0, token1] = mints;
+
+        let feeTier = feeTierPDA(4);
+        let [poolState] = web3.PublicKey.findProgramAddressSync(
+            [
+                Buffer.from('pool'),
+                token0.mint.toBuffer(),
+                token1.mint.toBuffer(),
+                feeTier.toBuffer(),
+            ],
+            program.programId
+        );
+        let [poolAuthority] = web3.PublicKey.findProgramAddressSync(
+            [Buffer.from('authority'), poolState.toBuffer()],
+            program.programId
+        );
+        let vaults = [];
+        for (let { mint } of mints) {
+            let vault = await token.getOrCreateAssociatedTokenAccount(
+                connection,
+                pool.auth,
+                mint,
+                poolAuthority,
+                true
+            );
+            vaults.push(vault.address);
+        }
+        await program.methods
+            .createPool({ stableSwap: { amp: new BN(100) } })
+            .accounts({
+                creator: wallet.publicKey,
+                feeTo,
+                mint0: token0.mint,
+                mint1: token1.mint,
+                feeTier,
+                vault0: vaults[0],
+                vault1: vaults[1],
+                tokenProgram0: token.TOKEN_PROGRAM_ID,
+                tokenProgram1: token.TOKEN_PROGRAM_ID,
+            })
+            .rpc();
+
+        // Amounts of the token with fewer decimals are scaled up to the other's
+        let poolData = await program.account.pool.fetch(poolState);
+        let rate = (unit: anchor.BN) => new BN(10 ** 9).div(unit);
+        expect(poolData.rate0.eq(rate(token0.unit))).to.be.true;
+        expect(poolData.rate1.eq(rate(token1.unit))).to.be.true;
+
+        await program.methods
+            .addLiquidity(
+                token0.unit.muln(10),
+                token1.unit.muln(10),
+                new BN(0),
+                new BN(0)
+            )
+            .accounts({
+                owner: wallet.publicKey,
+                pool: poolState,
+                vault0: vaults[0],
+                vault1: vaults[1],
+                feeTo,
+                userAta0: token0.ata,
+                userAta1: token1.ata,
+                mint0: token0.mint,
+                mint1: token1.mint,
+                tokenProgram0: token.TOKEN_PROGRAM_ID,
+                tokenProgram1: token.TOKEN_PROGRAM_ID,
+            })
+            .rpc();
+
+        // One whole token buys close to one whole token of the other
+        let balanceBefore = await connection.getTokenAccountBalance(
+            token1.ata
+        );
+        await program.methods
+            .swapExactInput(token0.unit, token1.unit.muln(99).divn(100))
+            .accounts({
+                owner: wallet.publicKey,
+                pool: poolState,
+                userAtaSrc: token0.ata,
+                userAtaDes: token1.ata,
+                vaultSrc: vaults[0],
+                vaultDes: vaults[1],
+                mintSrc: token0.mint,
+                mintDes: token1.mint,
+                tokenProgramSrc: token.TOKEN_PROGRAM_ID,
+                tokenProgramDes: token.TOKEN_PROGRAM_ID,
+            })
+            .rpc();
+        let balanceAfter = await connection.getTokenAccountBalance(token1.ata);
+        let amountOut = new BN(balanceAfter.value.amount).sub(
+            new BN(balanceBefore.value.amount)
+        );
+        expect(amountOut.lt(token1.unit)).to.be.true;
+        expect(amountOut.gte(token1.unit.muln(99).divn(100))).to.be.true;
+    });
+
+    it('Weighted pool', async () => {
+        // An 80/20 weighted pool for the main pool's mints
+        let weightedFee = 25;
+        await program.methods
+            .createFeeTier(new BN(weightedFee))
+            .accounts({ config: configPDA })
+            .rpc();
+        let feeTier = feeTierPDA(weightedFee);
+        let [poolState] = web3.PublicKey.findProgramAddressSync(
+            [
+                Buffer.from('pool'),
+                pool.mint0.toBuffer(),
+                pool.mint1.toBuffer(),
+                feeTier.toBuffer(),
+            ],
+            program.programId
+        );
+        let [poolAuthority] = web3.PublicKey.findProgramAddressSync(
+            [Buffer.from('authority'), poolState.toBuffer()],
+            program.programId
+        );
+        let [lpMint] = web3.PublicKey.findProgramAddressSync(
+            [Buffer.from('lp_mint'), poolState.toBuffer()],
+            program.programId
+        );
+        let vaults = [];
+        for (let mint of [pool.mint0, pool.mint1]) {
+            let vault = await token.getOrCreateAssociatedTokenAccount(
+                connection,
+                pool.auth,
+                mint,
+                poolAuthority,
+                true
+            );
+            vaults.push(vault.address);
+        }
+        let createPool = (weight0: number, weight1: number) =>
+            program.methods
+                .createPool({
+                    weighted: {
+                        weight0: new BN(weight0),
+                        weight1: new BN(weight1),
+                    },
+                })
+                .accounts({
+                    creator: wallet.publicKey,
+                    feeTo,
+                    mint0: pool.mint0,
+                    mint1: pool.mint1,
+                    feeTier,
+                    vault0: vaults[0],
+                    vault1: vaults[1],
+                    tokenProgram0: token.TOKEN_PROGRAM_ID,
+                    tokenProgram1: token.TOKEN_PROGRAM_ID,
+                })
+                .rpc();
+
+        // Each weight must be at least 1%
+        try {
+            await createPool(1, 999);
+            expect.fail('should reject a weight below the minimum');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq(
+                'InvalidWeights'
+            );
+        }
+
+        await createPool(80, 20);
+        let poolData = await program.account.pool.fetch(poolState);
+        expect(poolData.curveType).to.deep.eq({ weighted: {} });
+        expect(poolData.weight0.eq(new BN(8000))).to.be.true;
+        expect(poolData.weight1.eq(new BN(2000))).to.be.true;
+
+        // The first deposit mints the weighted geometric mean of the amounts
+        let [amount0, amount1] = [lp_amount(4), lp_amount(1)];
+        await program.methods
+            .addLiquidity(amount0, amount1, new BN(0), new BN(0))
+            .accounts({
+                owner: lpUser0.signer.publicKey,
+                pool: poolState,
+                vault0: vaults[0],
+                vault1: vaults[1],
+                feeTo,
+                userAta0: lpUser0.userAta0,
+                userAta1: lpUser0.userAta1,
+                mint0: pool.mint0,
+                mint1: pool.mint1,
+                tokenProgram0: token.TOKEN_PROGRAM_ID,
+                tokenProgram1: token.TOKEN_PROGRAM_ID,
+            })
+            .signers([lpUser0.signer])
+            .rpc();
+        let lpSupply = (await token.getMint(connection, lpMint)).supply;
+        let expectedSupply = 10 ** n_decimals * Math.pow(4, 0.8);
+        expect(
+            Math.abs(Number(lpSupply) - expectedSupply) / expectedSupply
+        ).to.be.lt(1e-9);
+
+        // At 4:1 reserves with 80/20 weights token0 trades near 1:1
+        let swap = (amountIn: anchor.BN) =>
+            program.methods
+                .swapExactInput(amountIn, new BN(0))
+                .accounts({
+                    owner: lpUser0.signer.publicKey,
+                    pool: poolState,
+                    userAtaSrc: lpUser0.userAta0,
+                    userAtaDes: lpUser0.userAta1,
+                    vaultSrc: vaults[0],
+                    vaultDes: vaults[1],
+                    mintSrc: pool.mint0,
+                    mintDes: pool.mint1,
+                    tokenProgramSrc: token.TOKEN_PROGRAM_ID,
+                    tokenProgramDes: token.TOKEN_PROGRAM_ID,
+                })
+                .signers([lpUser0.signer])
+                .rpc();
+        let amountIn = lp_amount(1).divn(100);
+        let balanceBefore = await connection.getTokenAccountBalance(
+            lpUser0.userAta1
+        );
+        await swap(amountIn);
+        let balanceAfter = await connection.getTokenAccountBalance(
+            lpUser0.userAta1
+        );
+        let amountOut = new BN(balanceAfter.value.amount).sub(
+            new BN(balanceBefore.value.amount)
+        );
+
+        // out = reserve_out * (1 - (reserve_in / (reserve_in + in))^(w_in / w_out))
+        let amountInWithFee =
+            (Number(amountIn) * (BASIS_POINTS - weightedFee)) / BASIS_POINTS;
+        let expectedOut =
+            Number(amount1) *
+            (1 -
+                Math.pow(
+                    Number(amount0) / (Number(amount0) + amountInWithFee),
+                    4
+                ));
+        expect(
+            Math.abs(amountOut.toNumber() - expectedOut) / expectedOut
+        ).to.be.lt(1e-9);
+        expect(amountOut.gt(amountIn.muln(98).divn(100))).to.be.true;
+
+        // A single swap may not move more than 30% of a reserve
+        try {
+            await swap(lp_amount(2));
+            expect.fail('should reject a swap that large');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq(
+                'InsufficientLiquidity'
+            );
+        }
+    });
+
+    it('Concentrated liquidity', async () => {
+        // A concentrated pool for the main pool's mints, starting at a price of 1
+        let clFee = 5;
+        await program.methods
+            .createFeeTier(new BN(clFee))
+            .accounts({ config: configPDA })
+            .rpc();
+        let feeTier = feeTierPDA(clFee);
+        let [poolState] = web3.PublicKey.findProgramAddressSync(
+            [
+                Buffer.from('concentrated_pool'),
+                pool.mint0.toBuffer(),
+                pool.mint1.toBuffer(),
+                feeTier.toBuffer(),
+            ],
+            program.programId
+        );
+        let [poolAuthority] = web3.PublicKey.findProgramAddressSync(
+            [Buffer.from('authority'), poolState.toBuffer()],
+            program.programId
+        );
+        let vaults = [];
+        for (let mint of [pool.mint0, pool.mint1]) {
+            let vault = await token.getOrCreateAssociatedTokenAccount(
+                connection,
+                pool.auth,
+                mint,
+                poolAuthority,
+                true
+            );
+            vaults.push(vault.address);
+        }
+        let tickSpacing = 10;
+        await program.methods
+            .createConcentratedPool(tickSpacing, new BN(1).shln(64))
+            .accounts({
+                creator: wallet.publicKey,
+                feeTo,
+                mint0: pool.mint0,
+                mint1: pool.mint1,
+                feeTier,
+                vault0: vaults[0],
+                vault1: vaults[1],
+                tokenProgram0: token.TOKEN_PROGRAM_ID,
+                tokenProgram1: token.TOKEN_PROGRAM_ID,
+            })
+            .rpc();
+        let poolData = await program.account.concentratedPool.fetch(poolState);
+        expect(poolData.tickCurrent).to.eq(0);
+        expect(poolData.liquidity.eqn(0)).to.be.true;
+
+        // Tick arrays of 64 ticks on each side of the price
+        let tickArrays = [];
+        for (let startTick of [-640, 0]) {
+            await program.methods
+                .initializeTickArray(startTick)
+                .accounts({ pool: poolState })
+                .rpc();
+            tickArrays.push(tickArrayPDA(poolState, startTick));
+        }
+
+        let user = web3.Keypair.generate();
+        let [userAta0, userAta1] = await setup_lp_provider(user.publicKey, 100);
+        let openPosition = (tickLower: number, tickUpper: number) =>
+            program.methods
+                .openPosition(tickLower, tickUpper)
+                .accounts({ owner: user.publicKey, pool: poolState })
+                .signers([user])
+                .rpc();
+
+        // Positions must start and end on the tick spacing
+        try {
+            await openPosition(-605, 600);
+            expect.fail('should reject a tick off the spacing');
+        } catch (e) {
+            expect(e).to.be.instanceOf(AnchorError);
+            expect((e as AnchorError).error.errorCode.code).to.eq(
+                'InvalidTickSpacing'
+            );
+        }
+
+        await openPosition(-600, 600);
+        let [position] = web3.PublicKey.findProgramAddressSync(
+            [
+                Buffer.from('position'),
+                poolState.toBuffer(),
+                user.publicKey.toBuffer(),
+                new BN(-600).toTwos(32).toArrayLike(Buffer, 'le', 4),
+                new BN(600).toArrayLike(Buffer, 'le', 4),
+            ],
+            program.programId
+        );
+        let positionAccounts = {
+            owner: user.publicKey,
+            userAta0,
+            userAta1,
+            pool: poolState,
+            position,
+            tickArrayLower: tickArrays[0],
+            tickArrayUpper: tickArrays[1],
+            vault0: vaults[0],
+            vault1: vaults[1],
+            mint0: pool.mint0,
+            mint1: pool.mint1,
+            tokenProgram0: token.TOKEN_PROGRAM_ID,
+            tokenProgram1: token.TOKEN_PROGRAM_ID,
+        };
+
+        // In range the position takes both tokens, L * (1 - 1/sqrt(p_upper))
+        // of token0 and L * (1 - sqrt(p_lower)) of token1
+        let liquidity = new BN(10).pow(new BN(11));
+        let sqrtLower = Math.pow(1.0001, -300);
+        let sqrtUpper = Math.pow(1.0001, 300);
+        let balances = async () =>
+            Promise.all(
+                [userAta0, userAta1].map(async (ata) =>
+                    Number(
+                        (await connection.getTokenAccountBalance(ata)).value
+                            .amount
+                    )
+                )
+            );
+        let [before0, before1] = await balances();
+        await program.methods
+            .increaseLiquidity(liquidity, lp_amount(100), lp_amount(100))
+            .accounts(positionAccounts)
+            .signers([user])
+            .rpc();
+        let [after0, after1] = await balances();
+        let deposited0 = before0 - after0;
+        let deposited1 = before1 - after1;
+        expect(
+            Math.abs(deposited0 - 1e11 * (1 - 1 / sqrtUpper)) / deposited0
+        ).to.be.lt(1e-6);
+        expect(
+            Math.abs(deposited1 - 1e11 * (1 - sqrtLower)) / deposited1
+        ).to.be.lt(1e-6);
+        poolData = await program.account.concentratedPool.fetch(poolState);
+        expect(poolData.liquidity.eq(liquidity)).to.be.true;
+
+        let swap = (amountIn: anchor.BN, sqrtPriceLimit: anchor.BN) =>
+            program.methods
+                .swapConcentratedExactInput(amountIn, new BN(0), sqrtPriceLimit)
+                .accounts({
+                    owner: user.publicKey,
+                    pool: poolState,
+                    userAtaSrc: userAta0,
+                    userAtaDes: userAta1,
+                    vaultSrc: vaults[0],
+                    vaultDes: vaults[1],
+                    mintSrc: pool.mint0,
+                    mintDes: pool.mint1,
+                    tokenProgramSrc: token.TOKEN_PROGRAM_ID,
+                    tokenProgramDes: token.TOKEN_PROGRAM_ID,
+                })
+                .remainingAccounts(
+                    tickArrays.map((pubkey) => ({
+                        pubkey,
+                        isSigner: false,
+                        isWritable: true,
+                    }))
+                )
+                .signers([user])
+                .rpc();
+
+        // A pool pause blocks swaps and deposits, not withdrawals
+        let setPaused = (paused: boolean) =>
+            program.methods
+                .setConcentratedPoolPaused(paused)
+                .accounts({ pool: poolState })
+                .rpc();
+        await setPaused(true);
+        poolData = await program.account.concentratedPool.fetch(poolState);
+        expect(poolData.paused).to.be.true;
+        for (let paused of [
+            () => swap(lp_amount(1).divn(10), new BN(0)),
+            () =>
+                program.methods
+                    .increaseLiquidity(
+                        liquidity,
+                        lp_amount(100),
+                        lp_amount(100)
+                    )
+                    .accounts(positionAccounts)
+                    .signers([user])
+                    .rpc(),
+        ]) {
+            try {
+                await paused();
+                expect.fail('should reject while the pool is paused');
+            } catch (e) {
+                expect(e).to.be.instanceOf(AnchorError);
+                expect((e as AnchorError).error.errorCode.code).to.eq(
+                    'Paused'
+                );
+            }
+        }
+        await setPaused(false);
+
+        // Within the range the pool trades like x * y = k with L^2 = k
+        let amountIn = lp_amount(1).divn(10);
+        [before0, before1] = await balances();
+        await swap(amountIn, new BN(0));
+        [after0, after1] = await balances();
+        let amountInLessFee =
+            (amountIn.toNumber() * (BASIS_POINTS - clFee)) / BASIS_POINTS;
+        let expectedOut = 1e11 * (1 - 1e11 / (1e11 + amountInLessFee));
+        expect(before0 - after0).to.eq(amountIn.toNumber());
+        expect(
+            Math.abs(after1 - before1 - expectedOut) / expectedOut
+        ).to.be.lt(1e-6);
+        let swappedIn = amountIn.toNumber();
+        let swappedOut = after1 - before1;
+
+        // A larger swap crosses the lower tick, leaving no liquidity in range,
+        // and stops at the price limit
+        let sqrtPriceLimit = new BN(
+            Math.floor(Math.pow(1.0001, -310) * 2 ** 64).toString()
+        );
+        [before0, before1] = await balances();
+        await swap(lp_amount(5), sqrtPriceLimit);
+        [after0, after1] = await balances();
+        swappedIn += before0 - after0;
+        swappedOut += after1 - before1;
+        expect(before0 - after0).to.be.lt(lp_amount(5).toNumber());
+        poolData = await program.account.concentratedPool.fetch(poolState);
+        expect(poolData.liquidity.eqn(0)).to.be.true;
+        expect(poolData.sqrtPrice.eq(sqrtPriceLimit)).to.be.true;
+        expect(poolData.tickCurrent).to.be.lt(-600);
+        expect(swappedOut).to.be.lte(deposited1);
+        expect(swappedOut).to.be.gte(deposited1 - 3);
+
+        // The position earned the fees on all of the input
+        [before0, before1] = await balances();
+        await program.methods
+            .collectFees()
+            .accounts(positionAccounts)
+            .signers([user])
+            .rpc();
+        [after0, after1] = await balances();
+        let expectedFees = (swappedIn * clFee) / BASIS_POINTS;
+        expect(
+            Math.abs(after0 - before0 - expectedFees) / expectedFees
+        ).to.be.lt(1e-3);
+        expect(after1).to.eq(before1);
+
+        // Out of range below, the position is all token0
+        await program.methods
+            .decreaseLiquidity(liquidity, new BN(0), new BN(0))
+            .accounts(positionAccounts)
+            .signers([user])
+            .rpc();
+        let positionData = await program.account.position.fetch(position);
+        expect(positionData.liquidity.eqn(0)).to.be.true;
+        let vault0 = await connection.getTokenAccountBalance(vaults[0]);
+        let vault1 = await connection.getTokenAccountBalance(vaults[1]);
+        expect(Number(vault0.value.amount)).to.be.lt(10);
+        expect(Number(vault1.value.amount)).to.be.lt(10);
+
+        // Swaps with a deadline, once liquidity is back in the pool
+        await program.methods
+            .increaseLiquidity(liquidity, lp_amount(100), lp_amount(100))
+            .accounts(positionAccounts)
+            .signers([user])
+            .rpc();
+        let now = Math.floor(Date.now() / 1000);
+        let swapAccounts = (src: number, des: number) => ({
+            owner: user.publicKey,
+            pool: poolState,
+            userAtaSrc: [userAta0, userAta1][src],
+            userAtaDes: [userAta0, userAta1][des],
+            vaultSrc: vaults[src],
+            vaultDes: vaults[des],
+            mintSrc: [pool.mint0, pool.mint1][src],
+            mintDes: [pool.mint0, pool.mint1][des],
+            tokenProgramSrc: token.TOKEN_PROGRAM_ID,
+            tokenProgramDes: token.TOKEN_PROGRAM_ID,
+        });
+        let tickArrayAccounts = tickArrays.map((pubkey) => ({
+            pubkey,
+            isSigner: false,
+            isWritable: true,
+        }));
+        // Token1 in moves the price back up into the range, token0 in moves it down
+        let swapWithDeadline = (exactInput: boolean, deadline: number) =>
+            exactInput
+                ? program.methods
+                      .swapConcentratedExactInputWithDeadline(
+                          lp_amount(1).divn(10),
+                          new BN(0),
+                          new BN(0),
+                          new BN(deadline)
+                      )
+                      .accounts(swapAccounts(1, 0))
+                      .remainingAccounts(tickArrayAccounts)
+                      .signers([user])
+                      .rpc()
+                : program.methods
+                      .swapConcentratedExactOutputWithDeadline(
+                          lp_amount(1).divn(1000),
+                          lp_amount(1),
+                          new BN(0),
+                          new BN(deadline)
+                      )
+                      .accounts(swapAccounts(0, 1))
+                      .remainingAccounts(tickArrayAccounts)
+                      .signers([user])
+                      .rpc();
+        for (let exactInput of [true, false]) {
+            try {
+                await swapWithDeadline(exactInput, now - 60);
+                expect.fail('should reject a swap past its deadline');
+            } catch (e) {
+                expect(e).to.be.instanceOf(AnchorError);
+                expect((e as AnchorError).error.errorCode.code).to.eq(
+                    'Expired'
+                );
+            }
+        }
+
+        [before0, before1] = await balances();
+        await swapWithDeadline(true, now + 60);
+        [after0, after1] = await balances();
+        expect(before1 - after1).to.eq(lp_amount(1).divn(10).toNumber());
+        expect(after0).to.be.gt(before0);
+
+        [before0, before1] = await balances();
+        await swapWithDeadline(false, now + 60);
+        [after0, after1] = await balances();
+        expect(after1 - before1).to.eq(lp_amount(1).divn(1000).toNumber());
+        expect(after0).to.be.lt(before0);
+    });
+
+    it('Oracle-pegged pool', async () => {
+        // Pyth price accounts loaded from tests/fixtures, all pricing token0 at
+        // 2 token1. The fresh and wide ones are published in the far future.
+        let oracles = {
+            fresh: new web3.PublicKey(
+                '6dSUYWfWf4rZb4HBpWCbtgnCsronVNiaRQpGhJdbnvgx'
+            ),
+            stale: new web3.PublicKey(
+                'GZ5YmTK65kF5P3YZyKax41g6EYbcCoX47aTe9kWhKmiS'
+            ),
+            wide: new web3.PublicKey(
+                '8A5VzYc4K8UFmVTiBowBGRiiSExwa5aP95aTjZ4meq8D'
+            ),
+        };
+        let createOraclePool = async (
+            poolFee: number,
+            oracle: web3.PublicKey
+        ) => {
+            await program.methods
+                .createFeeTier(new BN(poolFee))
+                .accounts({ config: configPDA })
+                .rpc();
+            let feeTier = feeTierPDA(poolFee);
+            let [poolState] = web3.PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from('pool'),
+                    pool.mint0.toBuffer(),
+                    pool.mint1.toBuffer(),
+                    feeTier.toBuffer(),
+                ],
+                program.programId
+            );
+            let [poolAuthority] = web3.PublicKey.findProgramAddressSync(
+                [Buffer.from('authority'), poolState.toBuffer()],
+                program.programId
+            );
+            let vaults = [];
+            for (let mint of [pool.mint0, pool.mint1]) {
+                let vault = await token.getOrCreateAssociatedTokenAccount(
+                    connection,
+                    pool.auth,
+                    mint,
+                    poolAuthority,
+                    true
+                );
+                vaults.push(vault.address);
+            }
+            await program.methods
+                .createPool({
+                    oraclePegged: {
+                        amp: new BN(100),
+                        oracle,
+                        maxAge: new BN(25),
+                        maxConfidence: new BN(100),
+                    },
+                })
+                .accounts({
+                    creator: wallet.publicKey,
+                    feeTo,
+                    mint0: pool.mint0,
+                    mint1: pool.mint1,
+                    feeTier,
+                    vault0: vaults[0],
+                    vault1: vaults[1],
+                    tokenProgram0: token.TOKEN_PROGRAM_ID,
+                    tokenProgram1: token.TOKEN_PROGRAM_ID,
+                })
+                .rpc();
+            return { poolState, vaults };
+        };
+        let expectError = async (promise: Promise<string>, code: string) => {
+            try {
+                await promise;
+                expect.fail(`should fail with ${code}`);
+            } catch (e) {
+                expect(e).to.be.instanceOf(AnchorError);
+                expect((e as AnchorError).error.errorCode.code).to.eq(code);
+            }
+        };
+
+        let pegFee = 6;
+        let { poolState, vaults } = await createOraclePool(
+            pegFee,
+            oracles.fresh
+        );
+        let poolData = await program.account.pool.fetch(poolState);
+        expect(poolData.curveType).to.deep.eq({ oraclePegged: {} });
+        expect(poolData.oracle.equals(oracles.fresh)).to.be.true;
+        expect(poolData.oracleDecimals).to.eq(0);
+
+        // The difference of the decimals scales the price and must fit an i8
+        let [wideMint0, wideMint1] = await Promise.all(
+            [0, 200].map((decimals) =>
+                token.createMint(
+                    connection,
+                    pool.auth,
+                    pool.auth.publicKey,
+                    pool.auth.publicKey,
+                    decimals
+                )
+            )
+        );
+        if (wideMint0.toBase58() > wideMint1.toBase58()) {
+            [wideMint0, wideMint1] = [wideMint1, wideMint0];
+        }
+        let [widePool] = web3.PublicKey.findProgramAddressSync(
+            [
+                Buffer.from('pool'),
+                wideMint0.toBuffer(),
+                wideMint1.toBuffer(),
+                feeTierPDA(pegFee).toBuffer(),
+            ],
+            program.programId
+        );
+        let [widePoolAuthority] = web3.PublicKey.findProgramAddressSync(
+            [Buffer.from('authority'), widePool.toBuffer()],
+            program.programId
+        );
+        let wideVaults = [];
+        for (let mint of [wideMint0, wideMint1]) {
+            let vault = await token.getOrCreateAssociatedTokenAccount(
+                connection,
+                pool.auth,
+                mint,
+                widePoolAuthority,
+                true
+            );
+            wideVaults.push(vault.address);
+        }
+        await expectError(
+            program.methods
+                .createPool({
+                    oraclePegged: {
+                        amp: new BN(100),
+                        oracle: oracles.fresh,
+                        maxAge: new BN(25),
+                        maxConfidence: new BN(100),
+                    },
+                })
+                .accounts({
+                    creator: wallet.publicKey,
+                    feeTo,
+                    mint0: wideMint0,
+                    mint1: wideMint1,
+                    feeTier: feeTierPDA(pegFee),
+                    vault0: wideVaults[0],
+                    vault1: wideVaults[1],
+                    tokenProgram0: token.TOKEN_PROGRAM_ID,
+                    tokenProgram1: token.TOKEN_PROGRAM_ID,
+                })
+                .rpc(),
+            'InvalidDecimals'
+        );
+
+        // Deposit reserves worth the same at the oracle price
+        let user = web3.Keypair.generate();
+        let [userAta0, userAta1] = await setup_lp_provider(user.publicKey, 100);
+        let [amount0, amount1] = [lp_amount(5), lp_amount(10)];
+        await program.methods
+            .addLiquidity(amount0, amount1, new BN(0), new BN(0))
+            .accounts({
+                owner: user.publicKey,
+                pool: poolState,
+                vault0: vaults[0],
+                vault1: vaults[1],
+                feeTo,
+                userAta0,
+                userAta1,
+                mint0: pool.mint0,
+                mint1: pool.mint1,
+                tokenProgram0: token.TOKEN_PROGRAM_ID,
+                tokenProgram1: token.TOKEN_PROGRAM_ID,
+            })
+            .signers([user])
+            .rpc();
+
+        let swap = (amountIn: anchor.BN, oracle?: web3.PublicKey) =>
+            program.methods
+                .swapExactInput(amountIn, new BN(0))
+                .accounts({
+                    owner: user.publicKey,
+                    pool: poolState,
+                    userAtaSrc: userAta0,
+                    userAtaDes: userAta1,
+                    vaultSrc: vaults[0],
+                    vaultDes: vaults[1],
+                    mintSrc: pool.mint0,
+                    mintDes: pool.mint1,
+                    tokenProgramSrc: token.TOKEN_PROGRAM_ID,
+                    tokenProgramDes: token.TOKEN_PROGRAM_ID,
+                })
+                .remainingAccounts(
+                    (oracle ? [oracle] : []).map((pubkey) => ({
+                        pubkey,
+                        isSigner: false,
+                        isWritable: false,
+                    }))
+                )
+                .signers([user])
+                .rpc();
+
+        // Swaps need a price, none has been read yet
+        let amountIn = lp_amount(1).divn(10);
+        await expectError(swap(amountIn), 'OracleStale');
+
+        // Near the peg the swap pays little beyond the fee, far less than on
+        // x * y = k
+        let balanceBefore = await connection.getTokenAccountBalance(userAta1);
+        await swap(amountIn, oracles.fresh);
+        let balanceAfter = await connection.getTokenAccountBalance(userAta1);
+        let amountOut = new BN(balanceAfter.value.amount).sub(
+            new BN(balanceBefore.value.amount)
+        );
+        let feeOnlyOut =
+            (amountIn.toNumber() * 2 * (BASIS_POINTS - pegFee)) / BASIS_POINTS;
+        expect((feeOnlyOut - amountOut.toNumber()) / feeOnlyOut).to.be.lt(5e-4);
+        expect(
+            amountOut.gt(getAmountOut(amountIn, amount0, amount1, pegFee))
+        ).to.be.true;
+        poolData = await program.account.pool.fetch(poolState);
+        expect(poolData.oraclePrice.eq(new BN(2).shln(64))).to.be.true;
+
+        // The price read stays usable while it is fresh
+        await swap(amountIn);
+
+        // Stale prices and wide confidence intervals are rejected
+        let stalePool = await createOraclePool(7, oracles.stale);
+        await expectError(
+            program.methods
+                .updateOraclePrice()
+                .accounts({
+                    pool: stalePool.poolState,
+                    oracle: oracles.stale,
+                })
+                .rpc(),
+            'OracleStale'
+        );
+        let widePool = await createOraclePool(8, oracles.wide);
+        await expectError(
+            program.methods
+                .updateOraclePrice()
+                .accounts({
+                    pool: widePool.poolState,
+                    oracle: oracles.wide,
+                })
+                .rpc(),
+            'OracleConfidenceTooWide'
+        );
+    });
+
+    async function setup_lp_provider(user: web3.PublicKey, amount: number) {
+        // setup token accs for deposit
+        let mint0_ata = await token.createAssociatedTokenAccount(
+            connection,
+            pool.payer,
+            pool.mint0,
+            user
+        );
+        let mint1_ata = await token.createAssociatedTokenAccount(
+            connection,
+            pool.payer,
+            pool.mint1,
+            user
+        );
+
+        // setup token accs for LP pool tokens
+        let lp_mint_ata = await token.createAssociatedTokenAccount(
+            connection,
             pool.payer,
             pool.poolMint,
             user
@@ -522,6 +4000,55 @@ describe('Amm', () => {
         return [mint0_ata, mint1_ata, lp_mint_ata];
     }
 
+    function feeTierPDA(fee: number) {
+        return web3.PublicKey.findProgramAddressSync(
+            [Buffer.from('fee_tier'), new BN(fee).toArrayLike(Buffer, 'le', 8)],
+            program.programId
+        )[0];
+    }
+
+    function tickArrayPDA(poolState: web3.PublicKey, startTick: number) {
+        return web3.PublicKey.findProgramAddressSync(
+            [
+                Buffer.from('tick_array'),
+                poolState.toBuffer(),
+                new BN(startTick).toTwos(32).toArrayLike(Buffer, 'le', 4),
+            ],
+            program.programId
+        )[0];
+    }
+
+    // Callback data telling the test borrower what to repay and what to call first
+    function repayment(
+        amount0: anchor.BN,
+        amount1: anchor.BN,
+        instruction: Buffer = Buffer.alloc(0)
+    ) {
+        return Buffer.concat([
+            amount0.toArrayLike(Buffer, 'le', 8),
+            amount1.toArrayLike(Buffer, 'le', 8),
+            new BN(instruction.length).toArrayLike(Buffer, 'le', 4),
+            instruction,
+        ]);
+    }
+
+    // Accounts the test borrower repays the main pool with, from the user's token accounts
+    function callbackAccounts(user: LPProvider) {
+        return [
+            { pubkey: user.userAta0, isWritable: true, isSigner: false },
+            { pubkey: pool.vault0, isWritable: true, isSigner: false },
+            { pubkey: pool.mint0, isWritable: false, isSigner: false },
+            { pubkey: user.userAta1, isWritable: true, isSigner: false },
+            { pubkey: pool.vault1, isWritable: true, isSigner: false },
+            { pubkey: pool.mint1, isWritable: false, isSigner: false },
+            {
+                pubkey: token.TOKEN_PROGRAM_ID,
+                isWritable: false,
+                isSigner: false,
+            },
+        ];
+    }
+
     function lp_amount(n) {
         return new anchor.BN(n * 10 ** n_decimals);
     }